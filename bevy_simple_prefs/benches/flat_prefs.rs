@@ -0,0 +1,87 @@
+//! Compares serializing and deserializing preferences with many primitive fields against plain
+//! reflection, which is what this crate used for them before they got a faster path.
+//!
+//! Run with `cargo bench --bench flat_prefs`.
+
+// `criterion_group!` generates an undocumented public function.
+#![allow(missing_docs)]
+
+use std::hint::black_box;
+
+use bevy::{
+    prelude::*,
+    reflect::{
+        serde::{TypedReflectDeserializer, TypedReflectSerializer},
+        TypeRegistry,
+    },
+};
+use bevy_simple_prefs::{deserialize, serialize, Prefs};
+use criterion::{criterion_group, criterion_main, Criterion};
+use ron::ser::{to_string_pretty, PrettyConfig};
+use serde::de::DeserializeSeed;
+
+macro_rules! flat_prefs {
+    ($($field:ident: $ty:ident($inner:ty)),* $(,)?) => {
+        #[derive(Prefs, Reflect, Default)]
+        struct FlatPrefs {
+            $($field: $ty,)*
+        }
+
+        $(
+            #[derive(Resource, Reflect, Clone, Default)]
+            struct $ty($inner);
+        )*
+    };
+}
+
+flat_prefs!(
+    f0: F0(u32), f1: F1(f32), f2: F2(bool), f3: F3(u32), f4: F4(f32),
+    f5: F5(bool), f6: F6(u32), f7: F7(f32), f8: F8(bool), f9: F9(u32),
+    f10: F10(f32), f11: F11(bool), f12: F12(u32), f13: F13(f32), f14: F14(bool),
+    f15: F15(u32), f16: F16(f32), f17: F17(bool), f18: F18(u32), f19: F19(f32),
+    f20: F20(bool), f21: F21(u32), f22: F22(f32), f23: F23(bool), f24: F24(u32),
+    f25: F25(f32), f26: F26(bool), f27: F27(u32), f28: F28(f32), f29: F29(bool),
+    f30: F30(u32), f31: F31(f32), f32: F32(bool), f33: F33(u32), f34: F34(f32),
+    f35: F35(bool), f36: F36(u32), f37: F37(f32), f38: F38(bool), f39: F39(u32),
+);
+
+fn flat_prefs(c: &mut Criterion) {
+    let prefs = FlatPrefs::default();
+    let serialized = serialize(&prefs).unwrap();
+
+    let mut registry = TypeRegistry::new();
+    registry.register::<FlatPrefs>();
+
+    let mut group = c.benchmark_group("serialize");
+    group.bench_function("reflect", |b| {
+        b.iter(|| {
+            let serializer = TypedReflectSerializer::new(black_box(&prefs), &registry);
+            black_box(to_string_pretty(&serializer, PrettyConfig::default()).unwrap())
+        });
+    });
+    group.bench_function("prefs", |b| {
+        b.iter(|| black_box(serialize(black_box(&prefs)).unwrap()));
+    });
+    group.finish();
+
+    let registration = registry.get(std::any::TypeId::of::<FlatPrefs>()).unwrap();
+    let mut group = c.benchmark_group("deserialize");
+    group.bench_function("reflect", |b| {
+        b.iter(|| {
+            let mut deserializer = ron::Deserializer::from_str(black_box(&serialized)).unwrap();
+            let value = TypedReflectDeserializer::new(registration, &registry)
+                .deserialize(&mut deserializer)
+                .unwrap();
+            let mut prefs = FlatPrefs::default();
+            prefs.apply(&*value);
+            black_box(prefs)
+        });
+    });
+    group.bench_function("prefs", |b| {
+        b.iter(|| black_box(deserialize::<FlatPrefs>(black_box(&serialized)).unwrap()));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, flat_prefs);
+criterion_main!(benches);
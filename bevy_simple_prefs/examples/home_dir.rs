@@ -1,7 +1,7 @@
 //! Example demonstrating how to store the preferences in the user's home directory.
 
 use bevy::{log::LogPlugin, prelude::*};
-use bevy_simple_prefs::{Prefs, PrefsPlugin};
+use bevy_simple_prefs::{prefs_just_loaded, Prefs, PrefsPlugin, PrefsSet};
 
 #[derive(Resource, Reflect, Default, Clone)]
 struct Launches(u32);
@@ -19,19 +19,22 @@ fn main() {
                 ..default()
             }),
             PrefsPlugin::<ExamplePrefs> {
-                // This value won't be used in WASM builds
+                // This field doesn't exist in WASM builds
                 path: home::home_dir().unwrap_or_default(),
                 // Setting this is optional. `(your_package_name)_prefs.ron` will be used by default.
                 filename: "custom_filename.ron".to_string(),
                 ..default()
             },
         ))
-        .add_systems(Update, print)
+        .add_systems(
+            Update,
+            print
+                .run_if(prefs_just_loaded::<ExamplePrefs>())
+                .after(PrefsSet::Load),
+        )
         .run();
 }
 
-fn print(launches: ResMut<Launches>) {
-    if launches.is_changed() && !launches.is_added() {
-        info!("Launches: {}", launches.0);
-    }
+fn print(launches: Res<Launches>) {
+    info!("Launches: {}", launches.0);
 }
@@ -1,7 +1,7 @@
 //! Example showing typical usage of `PrefsPlugin`.
 
 use bevy::{color::palettes::tailwind, ecs::system::EntityCommands, log::LogPlugin, prelude::*};
-use bevy_simple_prefs::{Prefs, PrefsPlugin};
+use bevy_simple_prefs::{prefs_just_loaded, Prefs, PrefsPlugin, PrefsSet};
 
 // All `Prefs` must also be `Reflect` and `Default`.
 #[derive(Prefs, Reflect, Default)]
@@ -38,11 +38,16 @@ fn main() {
             Update,
             (
                 volume_buttons,
-                volume_label.run_if(resource_changed::<Volume>),
+                // Loaded values don't trigger change detection, so update the labels
+                // when the prefs are loaded too.
+                volume_label
+                    .run_if(resource_changed::<Volume>.or(prefs_just_loaded::<ExamplePrefs>())),
                 difficulty_buttons,
-                difficulty_label.run_if(resource_changed::<Difficulty>),
+                difficulty_label
+                    .run_if(resource_changed::<Difficulty>.or(prefs_just_loaded::<ExamplePrefs>())),
                 button_style,
-            ),
+            )
+                .after(PrefsSet::Load),
         )
         .run();
 }
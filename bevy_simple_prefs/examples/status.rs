@@ -5,7 +5,7 @@
 //! before running systems that rely on them.
 
 use bevy::{log::LogPlugin, prelude::*};
-use bevy_simple_prefs::{Prefs, PrefsPlugin, PrefsStatus};
+use bevy_simple_prefs::{prefs_just_loaded, Prefs, PrefsPlugin, PrefsSet};
 
 #[derive(States, Default, Debug, Clone, PartialEq, Eq, Hash)]
 enum MyAppState {
@@ -32,15 +32,18 @@ fn main() {
             PrefsPlugin::<ExamplePrefs>::default(),
         ))
         .init_state::<MyAppState>()
-        .add_systems(Update, check_status.run_if(in_state(MyAppState::Loading)))
+        .add_systems(
+            Update,
+            start_playing
+                .run_if(prefs_just_loaded::<ExamplePrefs>())
+                .after(PrefsSet::Load),
+        )
         .add_systems(OnEnter(MyAppState::Playing), (print, increment).chain())
         .run();
 }
 
-fn check_status(status: Res<PrefsStatus<ExamplePrefs>>, mut next: ResMut<NextState<MyAppState>>) {
-    if status.loaded {
-        next.set(MyAppState::Playing);
-    }
+fn start_playing(mut next: ResMut<NextState<MyAppState>>) {
+    next.set(MyAppState::Playing);
 }
 
 fn print(launches: Res<Launches>) {
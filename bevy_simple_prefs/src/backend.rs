@@ -0,0 +1,431 @@
+//! Storage backends for serialized preferences.
+
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+use std::path::Path;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::log::warn;
+use bevy::utils::BoxedFuture;
+
+use crate::PrefsError;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{delete_str, load_str, load_str_from_any, save_str_with_mode};
+#[cfg(target_arch = "wasm32")]
+use crate::{load_str, local_storage, save_str};
+
+/// Storage for serialized preferences, which can be given to
+/// [`PrefsPlugin::with_backend`](crate::PrefsPlugin::with_backend).
+///
+/// Preferences are identified by a key, which is [`PrefsSettings::key`](crate::PrefsSettings::key).
+/// Without a backend, preferences are stored with a `FileBackend`, or a `LocalStorageBackend` in
+/// WASM builds, which can also be wrapped by other backends. [`MemoryBackend`] doesn't persist
+/// anything, which is useful for tests.
+///
+/// The returned futures are awaited with [`block_on`](bevy::tasks::block_on). Automatic saves and
+/// background loads await them from the [`IoTaskPool`](bevy::tasks::IoTaskPool), but blocking
+/// loads, and functions that act immediately, such as
+/// [`Prefs::save_current`](crate::Prefs::save_current) and [`clear_prefs`](crate::clear_prefs),
+/// await them on the calling thread. Backends may await IO, such as a platform's cloud save API,
+/// or block on it, but shouldn't take long. In WASM builds, there are no threads to block, so the
+/// futures must not wait for the browser.
+///
+/// ```rust
+/// use bevy::utils::BoxedFuture;
+/// use bevy_simple_prefs::{PrefsError, StorageBackend};
+///
+/// /// Refuses to store preferences larger than 4 KiB.
+/// struct Limited<B>(B);
+///
+/// impl<B: StorageBackend> StorageBackend for Limited<B> {
+///     fn load<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<Option<String>, PrefsError>> {
+///         self.0.load(key)
+///     }
+///
+///     fn save<'a>(&'a self, key: &'a str, data: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+///         Box::pin(async move {
+///             if data.len() > 4096 {
+///                 return Err(PrefsError::QuotaExceeded);
+///             }
+///             self.0.save(key, data).await
+///         })
+///     }
+///
+///     fn delete<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+///         self.0.delete(key)
+///     }
+/// }
+/// ```
+pub trait StorageBackend: Send + Sync + 'static {
+    /// Loads the preferences stored at `key`.
+    ///
+    /// Returns `Ok(None)` if nothing is stored there.
+    fn load<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<Option<String>, PrefsError>>;
+
+    /// Stores `data` at `key`, replacing any previous preferences.
+    fn save<'a>(&'a self, key: &'a str, data: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>>;
+
+    /// Deletes the preferences stored at `key`, if they exist.
+    fn delete<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>>;
+}
+
+impl<B: StorageBackend + ?Sized> StorageBackend for Arc<B> {
+    fn load<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<Option<String>, PrefsError>> {
+        (**self).load(key)
+    }
+
+    fn save<'a>(&'a self, key: &'a str, data: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        (**self).save(key, data)
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        (**self).delete(key)
+    }
+}
+
+/// A [`StorageBackend`] that stores preferences in files in `dir`, using the key as the
+/// filename.
+///
+/// This is how preferences are stored without a backend, where `dir` is
+/// [`PrefsSettings::dir`](crate::PrefsSettings::dir) and the key is the `filename`.
+///
+/// This is not supported in WASM builds.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+pub struct FileBackend {
+    /// Directory that preferences files are saved to.
+    pub dir: PathBuf,
+    /// Directories that preferences files are loaded from, in priority order. Directories that
+    /// can't be read are skipped.
+    ///
+    /// If this is empty, preferences files are loaded from `dir`.
+    pub load_dirs: Vec<PathBuf>,
+    /// Directories that preferences files are copied to after each successful save, and deleted
+    /// from along with the preferences file. Failures are logged rather than returned.
+    pub mirror_dirs: Vec<PathBuf>,
+    /// If `true`, `dir` and any missing parent directories are created before saving.
+    pub create_dirs: bool,
+    /// Optional Unix permissions for preferences files, as in
+    /// [`PrefsPlugin::file_mode`](crate::PrefsPlugin::file_mode).
+    pub file_mode: Option<u32>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileBackend {
+    /// Stores preferences files in `dir`, which is created when saving if it doesn't exist.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            load_dirs: Vec::new(),
+            mirror_dirs: Vec::new(),
+            create_dirs: true,
+            file_mode: None,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StorageBackend for FileBackend {
+    fn load<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<Option<String>, PrefsError>> {
+        Box::pin(async move {
+            match self.load_dirs.is_empty() {
+                true => load_str(&self.dir, key),
+                false => load_str_from_any(&self.load_dirs, key),
+            }
+        })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, data: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        Box::pin(async move {
+            save_str_with_mode(&self.dir, key, data, self.create_dirs, self.file_mode)?;
+
+            for dir in &self.mirror_dirs {
+                if let Err(e) = save_str_with_mode(dir, key, data, self.create_dirs, self.file_mode)
+                {
+                    warn!("Failed to mirror preferences to {:?}: {}", dir, e);
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        Box::pin(async move {
+            for dir in &self.mirror_dirs {
+                delete_str(dir, key);
+            }
+
+            match std::fs::remove_file(self.dir.join(key)) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+/// A [`StorageBackend`] that stores preferences in the browser's LocalStorage.
+///
+/// This is how preferences are stored without a backend, where the key is
+/// [`PrefsSettings::key`](crate::PrefsSettings::key).
+///
+/// This only exists in WASM builds.
+#[cfg(target_arch = "wasm32")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LocalStorageBackend;
+
+#[cfg(target_arch = "wasm32")]
+impl StorageBackend for LocalStorageBackend {
+    fn load<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<Option<String>, PrefsError>> {
+        Box::pin(async move { load_str(key) })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, data: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        Box::pin(async move { save_str(key, data) })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        Box::pin(async move {
+            local_storage()?
+                .remove_item(key)
+                .map_err(|e| PrefsError::other(format!("{:?}", e)))
+        })
+    }
+}
+
+/// A [`StorageBackend`] that stores preferences in memory.
+///
+/// Clones of the backend share the same storage, so preferences saved by one `App` can be loaded
+/// by another, which makes this useful for tests. Nothing is persisted once the last clone is
+/// dropped.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryBackend(Arc<Mutex<HashMap<String, String>>>);
+
+impl MemoryBackend {
+    fn map(&self) -> std::sync::MutexGuard<'_, HashMap<String, String>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn load<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<Option<String>, PrefsError>> {
+        Box::pin(async move { Ok(self.map().get(key).cloned()) })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, data: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        Box::pin(async move {
+            self.map().insert(key.to_string(), data.to_string());
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        Box::pin(async move {
+            self.map().remove(key);
+            Ok(())
+        })
+    }
+}
+
+/// A [`StorageBackend`] that stores preferences in a table of a SQLite database, with a row for
+/// each key.
+///
+/// The table has a `key` and a `value` column, and is created if it doesn't exist. Clones of the
+/// backend share the same connection, so several preferences types can be stored in the same
+/// table.
+///
+/// ```rust
+/// # use bevy::prelude::*;
+/// # use bevy_simple_prefs::{Prefs, PrefsPlugin, SqliteBackend, StorageBackend};
+/// # #[derive(Prefs, Reflect, Default)]
+/// # struct ExamplePrefs {
+/// #     volume: Volume,
+/// # }
+/// # #[derive(Resource, Reflect, Clone, Default)]
+/// # struct Volume(u32);
+/// let connection = rusqlite::Connection::open_in_memory().unwrap();
+/// let backend = SqliteBackend::new(connection, "prefs").unwrap();
+///
+/// let mut app = App::new();
+/// app.add_plugins(TaskPoolPlugin::default());
+/// app.add_plugins(
+///     PrefsPlugin::<ExamplePrefs> {
+///         filename: "example.ron".into(),
+///         ..default()
+///     }
+///     .with_backend(backend.clone()),
+/// );
+///
+/// app.world_mut().resource_mut::<Volume>().0 = 60;
+/// ExamplePrefs::save_current(app.world_mut()).unwrap();
+/// let stored = bevy::tasks::block_on(backend.load("example.ron")).unwrap();
+/// assert!(stored.unwrap().contains("60"));
+/// ```
+///
+/// This only exists with the `sqlite` feature, and not in WASM builds.
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+#[derive(Clone, Debug)]
+pub struct SqliteBackend {
+    connection: Arc<Mutex<rusqlite::Connection>>,
+    table: String,
+}
+
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+impl SqliteBackend {
+    /// Opens the SQLite database at `path`, creating it if it doesn't exist, and stores
+    /// preferences in `table`.
+    pub fn open(path: impl AsRef<Path>, table: &str) -> Result<Self, PrefsError> {
+        let connection = rusqlite::Connection::open(path).map_err(sqlite_error)?;
+        Self::new(connection, table)
+    }
+
+    /// Stores preferences in `table` of an existing connection, such as the one the app already
+    /// uses for its own data.
+    pub fn new(connection: rusqlite::Connection, table: &str) -> Result<Self, PrefsError> {
+        // Quote the table name, so it can't be mistaken for SQL.
+        let table = format!("\"{}\"", table.replace('"', "\"\""));
+        connection
+            .execute(
+                &format!(
+                    "CREATE TABLE IF NOT EXISTS {} (key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL)",
+                    table
+                ),
+                (),
+            )
+            .map_err(sqlite_error)?;
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            table,
+        })
+    }
+
+    fn connection(&self) -> std::sync::MutexGuard<'_, rusqlite::Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+impl StorageBackend for SqliteBackend {
+    fn load<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<Option<String>, PrefsError>> {
+        use rusqlite::OptionalExtension;
+
+        Box::pin(async move {
+            self.connection()
+                .query_row(
+                    &format!("SELECT value FROM {} WHERE key = ?1", self.table),
+                    [key],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(sqlite_error)
+        })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, data: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        Box::pin(async move {
+            self.connection()
+                .execute(
+                    &format!(
+                        "INSERT INTO {} (key, value) VALUES (?1, ?2) \
+                         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                        self.table
+                    ),
+                    [key, data],
+                )
+                .map(|_| ())
+                .map_err(sqlite_error)
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        Box::pin(async move {
+            self.connection()
+                .execute(&format!("DELETE FROM {} WHERE key = ?1", self.table), [key])
+                .map(|_| ())
+                .map_err(sqlite_error)
+        })
+    }
+}
+
+#[cfg(all(feature = "sqlite", not(target_arch = "wasm32")))]
+fn sqlite_error(e: rusqlite::Error) -> PrefsError {
+    match e {
+        rusqlite::Error::SqliteFailure(e, _) if e.code == rusqlite::ErrorCode::DiskFull => {
+            PrefsError::QuotaExceeded
+        }
+        e => PrefsError::other(e),
+    }
+}
+
+/// A [`StorageBackend`] that stores preferences in the OS keychain, using the key as the service
+/// name of each entry.
+///
+/// This is how preferences are stored when [`PrefsPlugin::keychain`](crate::PrefsPlugin::keychain)
+/// is set, where `user` is the type path of the preferences type. Keychains are intended for
+/// small secrets such as authentication tokens, and may limit the size of an entry to a few
+/// kilobytes.
+///
+/// This only exists with the `keychain` feature, and not in WASM builds.
+#[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+#[derive(Clone, Debug)]
+pub struct KeychainBackend {
+    /// User name of the keychain entries.
+    pub user: String,
+}
+
+#[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+impl KeychainBackend {
+    /// Stores preferences in keychain entries for `user`.
+    pub fn new(user: impl Into<String>) -> Self {
+        Self { user: user.into() }
+    }
+
+    fn entry(&self, key: &str) -> Result<keyring::Entry, PrefsError> {
+        keyring::Entry::new(key, &self.user).map_err(keyring_error)
+    }
+}
+
+#[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+impl StorageBackend for KeychainBackend {
+    fn load<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<Option<String>, PrefsError>> {
+        Box::pin(async move {
+            match self.entry(key)?.get_password() {
+                Ok(serialized) => Ok(Some(serialized)),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(e) => Err(keyring_error(e)),
+            }
+        })
+    }
+
+    fn save<'a>(&'a self, key: &'a str, data: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        Box::pin(async move { self.entry(key)?.set_password(data).map_err(keyring_error) })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<(), PrefsError>> {
+        Box::pin(async move {
+            match self.entry(key)?.delete_credential() {
+                Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+                Err(e) => Err(keyring_error(e)),
+            }
+        })
+    }
+}
+
+#[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+fn keyring_error(e: keyring::Error) -> PrefsError {
+    match e {
+        keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_) => {
+            PrefsError::StorageUnavailable
+        }
+        keyring::Error::TooLong(..) => PrefsError::QuotaExceeded,
+        e => PrefsError::other(e),
+    }
+}
@@ -2,39 +2,549 @@
 //!
 //! A small Bevy plugin for persisting multiple `Resource`s to a single file.
 
+use bevy::ecs::query::With;
 use std::{
     any::TypeId,
+    collections::HashMap,
+    future::Future,
     marker::PhantomData,
+    sync::{Arc, Mutex, OnceLock, PoisonError, RwLock, RwLockReadGuard},
+    time::Duration,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    io::Write,
     path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+pub use backend::*;
 use bevy::{
     app::{App, Plugin, Startup, Update},
     ecs::{
+        change_detection::DetectChangesMut,
         component::Component,
-        schedule::IntoSystemConfigs,
-        system::{Commands, Query, Resource},
+        entity::Entity,
+        event::Event,
+        schedule::{
+            common_conditions::resource_exists, InternedScheduleLabel, IntoSystemConfigs,
+            IntoSystemSetConfigs, ScheduleLabel, SystemSet,
+        },
+        system::{
+            BoxedSystem, Commands, IntoSystem, Local, Query, Res, ResMut, Resource, SystemId,
+        },
         world::{CommandQueue, World},
     },
     log::warn,
     reflect::{
-        serde::{TypedReflectDeserializer, TypedReflectSerializer},
-        GetTypeRegistration, Reflect, TypePath, TypeRegistry,
+        serde::{
+            ReflectDeserializerProcessor, SerializationData, TypedReflectDeserializer,
+            TypedReflectSerializer,
+        },
+        ArrayInfo, DynamicArray, DynamicStruct, DynamicTupleStruct, GetTypeRegistration,
+        PartialReflect, Reflect, ReflectDeserialize, ReflectMut, ReflectRef, ReflectSerialize,
+        Struct, StructInfo, TypeInfo, TypePath, TypeRegistration, TypeRegistry, TypeRegistryArc,
+        VariantInfo,
     },
     tasks::{block_on, futures_lite::future, Task},
+    time::{Real, Time},
 };
 pub use bevy_simple_prefs_derive::*;
-use ron::ser::{to_string_pretty, PrettyConfig};
-use serde::de::DeserializeSeed;
+pub use ron::extensions::Extensions;
+use ron::ser::{to_string, to_string_pretty, PrettyConfig};
+use serde::{
+    de::{DeserializeSeed, Deserializer, Error as _, IgnoredAny, MapAccess, SeqAccess, Visitor},
+    ser::{Error as _, SerializeStruct},
+    Deserialize, Serialize, Serializer,
+};
+
+mod backend;
+#[cfg(all(feature = "testing", not(target_arch = "wasm32")))]
+pub mod testing;
+#[cfg(test)]
+mod tests;
+
+// Lets the derive macro's `::bevy_simple_prefs` paths resolve in this crate's own tests.
+#[cfg(test)]
+extern crate self as bevy_simple_prefs;
+
+/// Returns the names whose flag is `true`, followed by the number of them.
+///
+/// Used by `bevy_simple_prefs_derive` to list the fields that are compiled.
+#[doc(hidden)]
+pub const fn __compiled_names<const N: usize>(
+    names: [(&'static str, bool); N],
+) -> ([&'static str; N], usize) {
+    let mut compiled = [""; N];
+    let mut len = 0;
+    let mut i = 0;
+    while i < N {
+        if names[i].1 {
+            compiled[len] = names[i].0;
+            len += 1;
+        }
+        i += 1;
+    }
+    (compiled, len)
+}
 
 /// A trait to be implemented by `bevy_simple_prefs_derive`.
+///
+/// Each field is stored as the individual preference `Resource` of its type, so no two fields
+/// may have the same type.
+///
+/// ```compile_fail
+/// # use bevy::prelude::*;
+/// # use bevy_simple_prefs::Prefs;
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     music_volume: Volume,
+///     effects_volume: Volume,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, Default)]
+/// struct Volume(u32);
+/// ```
+///
+/// This includes fields whose types are spelled differently, such as through a type alias.
+///
+/// ```compile_fail
+/// # use bevy::prelude::*;
+/// # use bevy_simple_prefs::Prefs;
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     music_volume: Volume,
+///     effects_volume: EffectsVolume,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, Default)]
+/// struct Volume(u32);
+///
+/// type EffectsVolume = Volume;
+/// ```
+///
+/// Fields annotated with `#[prefs(validate = "...")]` are corrected by the given function after
+/// they are loaded, and whenever they change, before they are saved.
+///
+/// ```rust
+/// # use bevy::prelude::*;
+/// # use bevy_simple_prefs::Prefs;
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     #[prefs(validate = "clamp_volume")]
+///     volume: Volume,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+/// struct Volume(u32);
+///
+/// fn clamp_volume(volume: &mut Volume) {
+///     volume.0 = volume.0.min(100);
+/// }
+/// ```
 pub trait Prefs {
     /// Runs when `PrefsPlugin` is built and initializes individual preference `Resource`s with default values.
     fn init(app: &mut App);
     /// Runs when individual preferences `Resources` are changed and persists preferences.
     fn save(world: &mut World);
     /// Loads preferences and updates individual preference `Resources`.
+    ///
+    /// Only one load runs at a time. If a load is already in progress, another one is started
+    /// once it has finished, and any further requests in the meantime are coalesced into that
+    /// one. Blocking loads discard the load in progress instead.
+    ///
+    /// Loaded values are applied without triggering change detection, in native and WASM builds
+    /// alike, so systems that react to changes of a preference `Resource` only see changes made
+    /// by the app. Use [`prefs_just_loaded`] to react to loaded values instead.
+    ///
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_simple_prefs::{LoadPrefsTask, Prefs, PrefsPlugin};
+    /// # #[derive(Prefs, Reflect, Default)]
+    /// # struct ExamplePrefs {
+    /// #     volume: Volume,
+    /// # }
+    /// # #[derive(Resource, Reflect, Clone, Default)]
+    /// # struct Volume(u32);
+    /// let mut app = App::new();
+    /// app.add_plugins(TaskPoolPlugin::default());
+    /// app.add_plugins(PrefsPlugin::<ExamplePrefs>::without_startup_load());
+    ///
+    /// for _ in 0..10 {
+    ///     ExamplePrefs::load(app.world_mut());
+    /// }
+    /// let mut load_tasks = app.world_mut().query::<&LoadPrefsTask<ExamplePrefs>>();
+    /// assert_eq!(load_tasks.iter(app.world()).count(), 1);
+    /// ```
     fn load(world: &mut World);
+    /// Resets individual preference `Resources` to their default values without saving them.
+    fn reset(world: &mut World);
+    /// Copies the individual preference `Resources` into [`Staged`] `Resources`, which can be
+    /// edited without affecting the live values until [`Prefs::commit_edit`].
+    ///
+    /// This is useful for settings menus with OK and Cancel buttons. Staged values are neither
+    /// saved nor replaced by loads. Beginning an edit while one is in progress discards it.
+    ///
+    /// ```rust
+    /// use bevy::prelude::*;
+    /// use bevy_simple_prefs::{Prefs, PrefsPlugin, Staged};
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// struct ExamplePrefs {
+    ///     volume: Volume,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+    /// struct Volume(u32);
+    ///
+    /// let mut app = App::new();
+    /// app.add_plugins(PrefsPlugin::<ExamplePrefs>::default());
+    ///
+    /// ExamplePrefs::begin_edit(app.world_mut());
+    /// app.world_mut().resource_mut::<Staged<Volume>>().0 = Volume(20);
+    /// assert_eq!(*app.world().resource::<Volume>(), Volume(0));
+    ///
+    /// // Cancel
+    /// ExamplePrefs::cancel_edit(app.world_mut());
+    /// assert!(!app.world().contains_resource::<Staged<Volume>>());
+    /// assert_eq!(*app.world().resource::<Volume>(), Volume(0));
+    ///
+    /// // OK
+    /// ExamplePrefs::begin_edit(app.world_mut());
+    /// app.world_mut().resource_mut::<Staged<Volume>>().0 = Volume(30);
+    /// ExamplePrefs::commit_edit(app.world_mut());
+    /// assert_eq!(*app.world().resource::<Volume>(), Volume(30));
+    /// ```
+    fn begin_edit(world: &mut World);
+    /// Replaces the individual preference `Resources` with the [`Staged`] values from
+    /// [`Prefs::begin_edit`], which are then saved as usual, and removes the staged values.
+    ///
+    /// Does nothing if no edit is in progress.
+    fn commit_edit(world: &mut World);
+    /// Removes the [`Staged`] values from [`Prefs::begin_edit`], leaving the individual
+    /// preference `Resources` untouched.
+    fn cancel_edit(world: &mut World);
+    /// Returns the current values of the individual preference `Resources`.
+    ///
+    /// For `Resources` being previewed, this is their [`Persisted`] value.
+    fn current(world: &World) -> Self
+    where
+        Self: Sized;
+    /// Inserts individual preference `Resources` from `self` without saving them.
+    fn insert(self, world: &mut World)
+    where
+        Self: Sized;
+    /// Applies loaded preferences to the individual preference `Resources` without saving them.
+    ///
+    /// Unlike [`Prefs::insert`], this doesn't trigger change detection, like loading.
+    fn insert_loaded(self, world: &mut World)
+    where
+        Self: Sized;
+    /// Immediately writes the current values of the individual preference `Resources`.
+    ///
+    /// Unlike the usual save, this happens regardless of whether the `Resources` have changed or
+    /// the preferences have been loaded, so it can be used to seed the preferences file from
+    /// values populated by other means. Any in-progress load is discarded, so that it doesn't
+    /// overwrite those values.
+    fn save_current(world: &mut World) -> Result<(), PrefsError>
+    where
+        Self: Reflect + TypePath + GetTypeRegistration + Default + Sized,
+    {
+        save_current::<Self>(world)
+    }
+    /// Immediately reads the stored preferences and inserts only the field of type `R`, leaving
+    /// the other individual preference `Resources` untouched.
+    ///
+    /// Returns `Ok(false)` if no preferences have been stored. This blocks the calling thread
+    /// while reading, and doesn't affect [`PrefsStatus::loaded`].
+    ///
+    /// Fails with [`PrefsError::UnknownField`] if `R` is not the type of exactly one of the
+    /// fields.
+    fn load_field<R: Resource + Reflect + Clone>(world: &mut World) -> Result<bool, PrefsError>
+    where
+        Self: Reflect + TypePath + GetTypeRegistration + Default + Sized,
+    {
+        load_field::<Self, R>(world)
+    }
+    /// Resets only the field of type `R` to its value in `Self::default()`, leaving the other
+    /// individual preference `Resources` untouched.
+    ///
+    /// Unlike [`Prefs::reset`], the change is saved as usual.
+    ///
+    /// Fails with [`PrefsError::UnknownField`] if `R` is not the type of exactly one of the
+    /// fields.
+    fn reset_field<R: Resource + Reflect + Clone>(world: &mut World) -> Result<(), PrefsError>
+    where
+        Self: Reflect + TypePath + GetTypeRegistration + Default + Sized,
+    {
+        reset_field::<Self, R>(world)
+    }
+    /// Immediately reads the stored preferences and returns the names of the fields whose
+    /// individual preference `Resources` differ from them.
+    ///
+    /// If no preferences have been stored, the fields are compared with the values that would be
+    /// loaded instead. Values are compared with [`PartialReflect::reflect_partial_eq`], and fields
+    /// whose values can't be compared are considered different. Transient fields are never
+    /// included. This blocks the calling thread while reading.
+    fn diff(world: &World) -> Result<Vec<&'static str>, PrefsError>
+    where
+        Self: Reflect + TypePath + GetTypeRegistration + Default + Sized,
+    {
+        diff::<Self>(world)
+    }
+    /// Returns a [`PrefsSnapshot`] of the current values of the individual preference
+    /// `Resources`, which can be applied later with [`Prefs::restore`].
+    ///
+    /// Snapshots are kept in memory rather than stored, which is useful for tests, undo systems,
+    /// and reproducing the preferences from a bug report.
+    fn snapshot(world: &World) -> Result<PrefsSnapshot<Self>, PrefsError>
+    where
+        Self: Reflect + TypePath + GetTypeRegistration + Default + Sized,
+    {
+        snapshot::<Self>(world)
+    }
+    /// Applies a [`PrefsSnapshot`] taken with [`Prefs::snapshot`] to the individual preference
+    /// `Resources`, and immediately writes them like [`Prefs::save_current`].
+    ///
+    /// Any in-progress load is discarded, so that it doesn't overwrite the restored values.
+    /// Nothing is changed if the snapshot can't be deserialized. This blocks the calling thread
+    /// while writing.
+    fn restore(world: &mut World, snapshot: &PrefsSnapshot<Self>) -> Result<(), PrefsError>
+    where
+        Self: Reflect + TypePath + GetTypeRegistration + Default + Sized,
+    {
+        restore::<Self>(world, snapshot)
+    }
+    /// Immediately rewrites the stored preferences in the current format, dropping any fields
+    /// that are no longer part of the preferences, including unrecognized fields of newer
+    /// versions. The stored values of transient fields are kept. See [`PreservedFields`].
+    ///
+    /// The stored preferences are read and deserialized as if they were being loaded, including
+    /// `post_read`, any `journal` entries and `post_load`, but without environment variable or
+    /// fragment overrides. The individual preference `Resources` are left untouched. Returns
+    /// `Ok(false)` if no preferences have been stored or the plugin is `read_only`. This blocks
+    /// the calling thread while reading and writing.
+    fn compact(world: &mut World) -> Result<bool, PrefsError>
+    where
+        Self: Reflect + TypePath + GetTypeRegistration + Default + Sized,
+    {
+        compact::<Self>(world)
+    }
+    /// Reads and deserializes the preferences file at `path` into a detached value, without
+    /// touching the `World` or any `PrefsPlugin`.
+    ///
+    /// This is useful for previewing other save slots, for example. Returns `None` if the file
+    /// doesn't exist or can't be deserialized. Fields missing from the file keep their default
+    /// values. This blocks the calling thread while reading.
+    ///
+    /// In WASM builds, this takes a LocalStorage key instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn peek(path: impl AsRef<Path>) -> Option<Self>
+    where
+        Self: Reflect + TypePath + GetTypeRegistration + Default + Sized,
+    {
+        peek_str(std::fs::read_to_string(path).ok()?)
+    }
+    /// Reads and deserializes the preferences stored in the LocalStorage item at `key` into a
+    /// detached value, without touching the `World` or any `PrefsPlugin`.
+    ///
+    /// Returns `None` if the item doesn't exist or can't be deserialized.
+    #[cfg(target_arch = "wasm32")]
+    fn peek(key: &str) -> Option<Self>
+    where
+        Self: Reflect + TypePath + GetTypeRegistration + Default + Sized,
+    {
+        let serialized = load_str(key).ok()??;
+        #[cfg(feature = "compact")]
+        let serialized = compact::decode(serialized).ok()?;
+        peek_str(serialized)
+    }
+    /// Reads the [`PrefsMetadata`] from the header of the preferences file at `path`, without
+    /// deserializing the preferences.
+    ///
+    /// Returns `None` if the file doesn't exist. This blocks the calling thread while reading.
+    /// See [`FormatOptions::timestamp`].
+    ///
+    /// In WASM builds, this takes a LocalStorage key instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn peek_metadata(path: impl AsRef<Path>) -> Option<PrefsMetadata> {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|serialized| read_metadata(&serialized))
+    }
+    /// Reads the [`PrefsMetadata`] from the header of the preferences stored in the LocalStorage
+    /// item at `key`, without deserializing the preferences.
+    ///
+    /// Returns `None` if the item doesn't exist.
+    #[cfg(target_arch = "wasm32")]
+    fn peek_metadata(key: &str) -> Option<PrefsMetadata> {
+        let serialized = load_str(key).ok()??;
+        #[cfg(feature = "compact")]
+        let serialized = compact::decode(serialized).ok()?;
+        Some(read_metadata(&serialized))
+    }
+    /// Immediately writes the current preferences to the file at `path`, such as one picked in a
+    /// file dialog, without changing where the preferences are stored.
+    ///
+    /// The file contains the output of [`export_prefs`], and can be read back with
+    /// [`Prefs::load_from`]. This blocks the calling thread while writing.
+    ///
+    /// This method does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn save_to(world: &World, path: impl AsRef<Path>) -> Result<(), PrefsError>
+    where
+        Self: Reflect + TypePath + GetTypeRegistration + Default + Sized,
+    {
+        std::fs::write(path, export_prefs::<Self>(world)?)?;
+        Ok(())
+    }
+    /// Immediately reads the preferences file at `path`, such as one written by
+    /// [`Prefs::save_to`], and applies it like [`import_prefs`], which also replaces the stored
+    /// preferences.
+    ///
+    /// Nothing is changed if the file can't be read or deserialized. This blocks the calling
+    /// thread while reading and writing.
+    ///
+    /// This method does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_from(world: &mut World, path: impl AsRef<Path>) -> Result<(), PrefsError>
+    where
+        Self: Reflect + TypePath + GetTypeRegistration + Default + Sized,
+    {
+        import_prefs::<Self>(world, &std::fs::read_to_string(path)?)
+    }
+    /// Names of all fields, in the order they are declared.
+    ///
+    /// This includes transient fields, which can be filtered out with [`Prefs::TRANSIENT_FIELDS`],
+    /// but not fields excluded by `#[cfg(...)]`.
+    ///
+    /// ```rust
+    /// use bevy::prelude::*;
+    /// use bevy_simple_prefs::Prefs;
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// struct ExamplePrefs {
+    ///     volume: Volume,
+    ///     recent: Recent<String>,
+    ///     #[cfg(target_os = "ios")]
+    ///     haptics: Haptics,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Volume(u32);
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Recent<T>(Vec<T>);
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Haptics(bool);
+    ///
+    /// # #[cfg(not(target_os = "ios"))]
+    /// # {
+    /// assert_eq!(ExamplePrefs::FIELD_NAMES, ["volume", "recent"]);
+    /// assert_eq!(ExamplePrefs::field_type_names(), ["Volume", "Recent<String>"]);
+    ///
+    /// // Fields that aren't compiled are ignored when loading.
+    /// let prefs: ExamplePrefs =
+    ///     bevy_simple_prefs::deserialize("(volume: (20), haptics: (true))").unwrap();
+    /// assert_eq!(prefs.volume.0, 20);
+    /// # }
+    /// ```
+    const FIELD_NAMES: &'static [&'static str] = &[];
+    /// [Short type paths](TypePath::short_type_path) of the types of all fields, in the same
+    /// order as [`Prefs::FIELD_NAMES`].
+    ///
+    /// These don't depend on how the types are spelled in the struct definition, such as through
+    /// a type alias or a qualified path.
+    fn field_type_names() -> &'static [&'static str] {
+        &[]
+    }
+    /// Doc comments of all fields, in the same order as [`Prefs::FIELD_NAMES`], or empty strings
+    /// for fields without them.
+    ///
+    /// These are written as comments above each field with [`FormatOptions::field_comments`].
+    const FIELD_DOCS: &'static [&'static str] = &[];
+    /// Names of the fields marked with `#[prefs(secret)]`, which are encrypted when saved.
+    const SECRET_FIELDS: &'static [&'static str] = &[];
+    /// Names of the fields marked with `#[prefs(transient)]`, which are loaded but never saved.
+    const TRANSIENT_FIELDS: &'static [&'static str] = &[];
+    /// Names of the fields marked with `#[prefs(restart_required)]`, whose changes only take
+    /// effect after the app restarts.
+    ///
+    /// This is only informational, such as for annotating these preferences in a settings menu.
+    /// They are saved and loaded like any other field.
+    ///
+    /// ```rust
+    /// use bevy::prelude::*;
+    /// use bevy_simple_prefs::Prefs;
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// struct GraphicsPrefs {
+    ///     vsync: Vsync,
+    ///     #[prefs(restart_required)]
+    ///     backend: Backend,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Vsync(bool);
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// enum Backend {
+    ///     #[default]
+    ///     Auto,
+    ///     Vulkan,
+    ///     Dx12,
+    /// }
+    ///
+    /// assert_eq!(GraphicsPrefs::RESTART_REQUIRED_FIELDS, ["backend"]);
+    /// ```
+    const RESTART_REQUIRED_FIELDS: &'static [&'static str] = &[];
+    /// Names of the fields with `#[cfg(...)]` attributes that are compiled in this build.
+    ///
+    /// These are left out of the [`schema_hash`], so that it is the same on every platform.
+    const CFG_FIELDS: &'static [&'static str] = &[];
+    /// Keys that the fields are written under in the preferences file, in the same order as
+    /// [`Prefs::FIELD_NAMES`], or empty if they are written under their own names.
+    ///
+    /// These are set with `#[prefs(rename_all = "...")]`, which supports `"snake_case"`,
+    /// `"kebab-case"`, `"camelCase"`, `"PascalCase"` and `"SCREAMING_SNAKE_CASE"`. Only the
+    /// top-level fields are renamed. Fields written under their own names, such as those in
+    /// files written before the attribute was added, are still loaded.
+    ///
+    /// Note that RON requires keys containing `-` to be written as raw identifiers, such as
+    /// `r#master-volume`. This is done automatically when saving.
+    ///
+    /// ```rust
+    /// use bevy::prelude::*;
+    /// use bevy_simple_prefs::{deserialize, serialize, Prefs};
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// #[prefs(rename_all = "camelCase")]
+    /// struct ExamplePrefs {
+    ///     master_volume: MasterVolume,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct MasterVolume(u32);
+    ///
+    /// assert_eq!(ExamplePrefs::FIELD_KEYS, ["masterVolume"]);
+    ///
+    /// let serialized = serialize(&ExamplePrefs {
+    ///     master_volume: MasterVolume(20),
+    /// })
+    /// .unwrap();
+    /// assert!(serialized.contains("masterVolume: (20)"));
+    ///
+    /// let prefs: ExamplePrefs = deserialize(&serialized).unwrap();
+    /// assert_eq!(prefs.master_volume.0, 20);
+    /// ```
+    const FIELD_KEYS: &'static [&'static str] = &[];
+    /// The version of the preferences, set with `#[prefs(version = 1)]`.
+    ///
+    /// If this is not `0`, it is recorded in a header at the top of the preferences file. When a
+    /// file written by a newer version is loaded, its unrecognized fields are preserved and written
+    /// back on the next save. See [`PreservedFields`].
+    const VERSION: u32 = 0;
 }
 
 /// The Bevy plugin responsible for persisting `T`.
@@ -59,177 +569,6972 @@ pub trait Prefs {
 /// App::new().add_plugins(PrefsPlugin::<ExamplePrefs>::default());
 /// ```
 pub struct PrefsPlugin<T: Reflect + TypePath> {
-    /// Filename (or LocalStorage key) for the preferences file.
+    /// Filename for the preferences file, which is [`PrefsPlugin::default_filename`] by default.
+    ///
+    /// In WASM builds, this is used as the LocalStorage key.
     pub filename: String,
     /// Path to the directory where the preferences file will be stored.
     ///
-    /// This value is not used in WASM builds.
+    /// A relative path is relative to the working directory. To store the preferences file next
+    /// to the executable instead, use [`PrefsPlugin::next_to_executable`].
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
     pub path: PathBuf,
+    /// A prefix used to keep the preferences of different environments, such as development and
+    /// production builds, apart.
+    ///
+    /// In WASM builds, this is prepended to the LocalStorage key. Otherwise, the preferences file
+    /// is stored in a subdirectory of `path` with this name.
+    ///
+    /// If this is empty and a [`PrefsPrefix`] resource exists when the plugin is built, its
+    /// prefix is used instead.
+    pub prefix: String,
+    /// Additional directories to load the preferences file from, in priority order, if it
+    /// doesn't exist in `path`.
+    ///
+    /// Preferences are never saved to these directories.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub load_paths: Vec<PathBuf>,
+    /// An optional directory to save the preferences file to if saving to `path` fails with
+    /// [`PrefsError::ReadOnly`], because permission was denied or `path` is on a read-only
+    /// filesystem. Other failures, such as a full disk, are returned as usual.
+    ///
+    /// After a successful save to this directory, [`PrefsSettings::path`] is updated to point to
+    /// it. Because it's likely to be more recent, a preferences file in this directory is loaded in
+    /// preference to one in `path`. Like `path`, it is followed by `prefix`.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fallback_path: Option<PathBuf>,
+    /// Additional directories that the preferences file is copied to after each successful save,
+    /// such as a network share, so that preferences survive the loss of `path`. Like `path`, each
+    /// is followed by `prefix`.
+    ///
+    /// Writing to mirrors is best-effort: failures are logged, but don't fail the save. The
+    /// preferences file in `path` remains authoritative, and mirrors are only loaded from, in
+    /// order, if it is missing or can't be deserialized.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub mirror_paths: Vec<PathBuf>,
+    /// An optional directory containing a preferences file with the same filename, which
+    /// provides defaults that the preferences file in `path` is layered on top of.
+    ///
+    /// This allows system-wide defaults to be overridden by each user. Fields that are missing
+    /// from the preferences file in `path` keep the values from this file, and only fields that
+    /// differ from it are saved. Preferences are never saved to this directory, and `prefix` isn't
+    /// applied to it.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub base_path: Option<PathBuf>,
+    /// If `true`, `path` and any missing parent directories are created before saving.
+    ///
+    /// If `false`, saving to a directory that doesn't exist fails with a [`PrefsSaveError`].
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub create_dirs: bool,
+    /// Optional Unix permissions for the preferences file, such as `0o600` for a file that only
+    /// its owner can read and write.
+    ///
+    /// The permissions are applied when the file is created, and to an existing file before it is
+    /// overwritten. They also apply to `sidecar` files, the `journal` and the `history` log. If
+    /// this is `None`, new files get the default permissions. This is ignored on platforms other
+    /// than Unix.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub file_mode: Option<u32>,
+    /// How failed automatic saves are retried.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub save_retry: SaveRetry,
+    /// Optional log of changes to individual preferences, stored alongside the preferences file.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub history: Option<PrefsHistory>,
+    /// Optional journal of changes, which is appended to instead of rewriting the whole
+    /// preferences file when only some preferences have changed.
+    ///
+    /// The journal isn't used when a [`PrefsPlugin::backend`] is set.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub journal: Option<PrefsJournal>,
+    /// Options controlling how the preferences file is written and read.
+    pub format: FormatOptions,
+    /// If `true`, preferences are loaded synchronously in `load_schedule`, blocking until the
+    /// preferences file has been read.
+    ///
+    /// By default, preferences are loaded in the background, and systems may observe default
+    /// values for a few frames. With this enabled, systems in `Update` only ever observe the
+    /// loaded values, on all platforms.
+    pub blocking_load: bool,
+    /// The schedule that [`Prefs::load`] runs in, which is `Startup` by default.
+    ///
+    /// If this is `None`, loading isn't scheduled at all, and [`Prefs::load`] can be added to a
+    /// schedule or run manually. Changes aren't saved until the preferences have been loaded.
+    ///
+    /// ```rust
+    /// use bevy::{ecs::schedule::ScheduleLabel, prelude::*};
+    /// use bevy_simple_prefs::{Prefs, PrefsPlugin};
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// struct ExamplePrefs {
+    ///     volume: Volume,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Volume(u32);
+    ///
+    /// #[derive(States, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+    /// enum AppState {
+    ///     #[default]
+    ///     Splash,
+    ///     LoadSettings,
+    /// }
+    ///
+    /// App::new()
+    ///     .add_plugins(PrefsPlugin::<ExamplePrefs> {
+    ///         load_schedule: Some(OnEnter(AppState::LoadSettings).intern()),
+    ///         ..default()
+    ///     });
+    /// ```
+    pub load_schedule: Option<InternedScheduleLabel>,
+    /// If `true`, preferences are loaded as usual but never written.
+    ///
+    /// Changes to individual preferences still take effect for the rest of the session. This is
+    /// useful for demo builds, for example.
+    pub read_only: bool,
+    /// Optional serialized preferences that are loaded when no preferences have been stored, such
+    /// as on the first run.
+    ///
+    /// This is typically embedded with `include_str!`. Fields that are missing from it keep their
+    /// default values, and `T::default()` is used if it can't be deserialized.
+    pub embedded_default: Option<&'static str>,
+    /// An optional path of an asset containing serialized preferences that are loaded when no
+    /// preferences have been stored, in preference to `embedded_default`.
+    ///
+    /// This allows defaults to be tuned without recompiling. The asset is loaded with the
+    /// `AssetServer`, so `AssetPlugin` must be added before this plugin. Loading the preferences
+    /// waits for the asset, even with `blocking_load`, and [`PrefsStatus::loaded`] is only set
+    /// once both have been resolved. If the asset fails to load, `embedded_default` is used
+    /// instead.
+    ///
+    /// This field only exists with the `asset` feature.
+    #[cfg(feature = "asset")]
+    pub default_asset: Option<String>,
+    /// Optional default preferences that are fetched from a URL each time the preferences are
+    /// loaded, which the stored preferences are layered on top of like `base_path`.
+    ///
+    /// This allows defaults to be tuned for every player without releasing a new build. Only
+    /// preferences that differ from the fetched defaults are stored, so later changes to the
+    /// defaults apply to all preferences that haven't been changed locally. Loading the
+    /// preferences waits for the response, even with `blocking_load`, and
+    /// [`PrefsStatus::loaded`] is only set once it has been received. If the request fails or
+    /// times out, `embedded_default` is used as the defaults instead.
+    ///
+    /// This field only exists with the `http` feature.
+    #[cfg(feature = "http")]
+    pub http_defaults: Option<HttpDefaults>,
+    /// If set, the preferences are saved at this interval even if they haven't changed.
+    ///
+    /// This bounds how much is lost if the app crashes or is killed, for preferences that change
+    /// continuously. The interval restarts after any save, and is measured in real time.
+    /// Requires `TimePlugin`.
+    pub periodic_save: Option<Duration>,
+    /// If `true`, the preferences are serialized on the main thread directly from the individual
+    /// preference `Resources`, rather than from a clone of them in the
+    /// [`IoTaskPool`](bevy::tasks::IoTaskPool).
+    ///
+    /// This avoids holding a second copy of large preferences, such as long lists, in memory
+    /// while saving, at the cost of blocking the frame while they are serialized. The values are
+    /// moved out of the `Resources` while serializing and moved back afterwards, without being
+    /// marked as changed. They are still written in the task pool.
+    pub serialize_in_place: bool,
+    /// If set, a warning is logged in debug builds the first time a preference changes in this
+    /// many consecutive frames.
+    ///
+    /// This usually means that a system is dereferencing `ResMut` every frame without changing
+    /// the value, which causes the preferences to be saved every frame. See
+    /// [`PrefsChangeStreaks`].
+    pub warn_constant_changes: Option<u32>,
+    /// If `true`, the preferences are stored in a section of a file that is shared with other
+    /// `PrefsPlugin`s using the same `filename`, rather than in a file of their own.
+    ///
+    /// The shared file is a map from the short type path of each prefs type to its preferences,
+    /// such as `{ "ExamplePrefs": (...), "ExampleStats": (...) }`. Each type only reads and
+    /// writes its own section, and sections of other types are kept as they are. Saves of
+    /// different types are serialized, so types saving in the same frame don't overwrite each
+    /// other's sections.
+    ///
+    /// `pre_write`, `post_read`, and `sidecar` apply to the section rather than to the whole
+    /// file. A shared file that can't be split into sections is left as it is, and saving fails
+    /// with an error. This has no effect when using the OS keychain.
+    pub shared_file: bool,
+    /// An optional function that transforms the serialized preferences before they are written.
+    ///
+    /// This can be used to add a header comment, for example. Use `post_read` to undo any
+    /// changes that would prevent the preferences from being deserialized.
+    pub pre_write: Option<fn(String) -> String>,
+    /// An optional function that transforms the stored preferences after they are read, before
+    /// they are deserialized.
+    pub post_read: Option<fn(String) -> String>,
+    /// An optional function that is given the loaded preferences as a whole, before the individual
+    /// preference `Resource`s are inserted.
+    ///
+    /// This can be used to validate fields against each other, or to derive some preferences from
+    /// others. The `Resource`s are inserted with the corrected values without triggering a save.
+    /// If the function returns `true`, the corrected preferences are also saved.
+    ///
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_simple_prefs::{Prefs, PrefsPlugin};
+    /// # #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+    /// # struct MinVolume(u32);
+    /// # #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+    /// # struct MaxVolume(u32);
+    /// #[derive(Prefs, Reflect, Default)]
+    /// struct ExamplePrefs {
+    ///     min_volume: MinVolume,
+    ///     max_volume: MaxVolume,
+    /// }
+    ///
+    /// fn fix_volume_range(prefs: &mut ExamplePrefs) -> bool {
+    ///     if prefs.min_volume.0 <= prefs.max_volume.0 {
+    ///         return false;
+    ///     }
+    ///     std::mem::swap(&mut prefs.min_volume.0, &mut prefs.max_volume.0);
+    ///     true
+    /// }
+    ///
+    /// App::new().add_plugins(PrefsPlugin::<ExamplePrefs> {
+    ///     post_load: Some(fix_volume_range),
+    ///     ..default()
+    /// });
+    /// ```
+    pub post_load: Option<fn(&mut T) -> bool>,
+    /// If `true`, the value of each field is logged at debug level once the preferences have been
+    /// loaded, which can help diagnose problems reported by players.
+    ///
+    /// Values are formatted with their reflected `Debug` representation, after `post_load` has
+    /// been applied. The values of `#[prefs(secret)]` fields are left out.
+    pub log_loaded: bool,
+    /// An optional function that decides whether changed preferences are saved automatically.
+    ///
+    /// While it returns `false`, saves are skipped and changes are kept pending, to be saved once
+    /// it returns `true` again. This can be used to avoid persisting changes made in a tutorial or
+    /// by a guest, for example. Explicit saves, such as [`Prefs::save_current`], aren't affected.
+    pub save_if: Option<fn(&World) -> bool>,
+    /// The number of frames after the plugin is added during which changed preferences aren't
+    /// saved.
+    ///
+    /// This keeps preferences that the app changes while it initializes from being written before
+    /// they are final. As with `save_if`, which can be used to wait for a state instead, changes
+    /// made during these frames are kept pending, and saved once the frames have passed.
+    pub warm_up_frames: u32,
+    /// An optional function that is given the exact contents of the preferences file before it is
+    /// written, after `pre_write`, and returns the filename and contents of a sidecar file to
+    /// write alongside it.
+    ///
+    /// This can be used to write a detached signature of the preferences file, for example. The
+    /// sidecar file is written to the same directory, only after the preferences file has been
+    /// written successfully, so an existing sidecar file is left untouched if that fails. Sidecar
+    /// files aren't removed by [`clear_prefs`], and aren't written when using the OS keychain.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub sidecar: Option<fn(&str) -> Option<(String, String)>>,
+    /// An optional prefix of environment variables that override the loaded preferences, such as
+    /// `MYGAME_` for a dedicated server configured with `MYGAME_MAX_PLAYERS=64`.
+    ///
+    /// After the preferences are loaded, each field is overridden by the environment variable
+    /// named by this prefix followed by the field's key in uppercase, with `-` replaced by `_`.
+    /// The value is parsed as RON, with newtypes unwrapped, or as a string if that fails. Values
+    /// that can't be parsed are ignored with a warning.
+    ///
+    /// Overrides aren't saved. While an overridden preference keeps the value it was overridden
+    /// with, its stored value is written to the preferences file instead.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub env_prefix: Option<String>,
+    /// An optional directory of fragment files that override the loaded preferences, such as
+    /// `prefs.d`. Relative paths are relative to `path` followed by `prefix`.
+    ///
+    /// After the preferences are loaded, each file in the directory with the same extension as
+    /// `filename` is deserialized and applied on top of them in lexicographic order of their
+    /// filenames, so later fragments take precedence. Fragments only need to contain the fields they override. Fragments that can't
+    /// be read or deserialized are ignored with a warning. `env_prefix` is applied afterwards.
+    ///
+    /// This allows mods to adjust preferences by adding a file. Fragments are never written, and
+    /// neither are their overrides. While an overridden preference keeps the value it was
+    /// overridden with, its stored value is written to the preferences file instead.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fragment_dir: Option<PathBuf>,
+    /// The number of save slots, each with its own preferences file, or `0` for a single
+    /// preferences file.
+    ///
+    /// Slot `n` is stored in `filename` with `_slot{n}` appended to its stem, such as
+    /// `prefs_slot0.ron`. Slot `0` is active initially, and the [`PrefsSlot`] resource selects
+    /// the active slot. [`PrefsStatus`] describes the active slot, and [`slot_status`] describes
+    /// each slot.
+    ///
+    /// This field does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub slots: u32,
+    /// If `true`, preferences are stored in the OS keychain rather than in a file.
+    ///
+    /// The preferences are stored with a [`KeychainBackend`], whose entry is identified by
+    /// `prefix` followed by `filename`, and the type path of `T`. Keychains are intended for small
+    /// secrets such as authentication tokens, and may limit the size of an entry to a few
+    /// kilobytes.
+    ///
+    /// This field only exists with the `keychain` feature, and not in WASM builds.
+    #[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+    pub keychain: bool,
+    /// If `true`, preferences are compressed and base64-encoded before being stored in
+    /// LocalStorage, which takes up less of its quota at the cost of readability.
+    ///
+    /// Stored preferences are decompressed when loading regardless of this setting, so it can be
+    /// changed at any time.
+    ///
+    /// This field only exists with the `compact` feature, and only in WASM builds.
+    #[cfg(all(feature = "compact", target_arch = "wasm32"))]
+    pub compact_storage: bool,
+    /// If `true`, changes that haven't been saved yet, such as those made while
+    /// [`PrefsStatus::autosave_paused`] is set, are written to LocalStorage when the page is
+    /// hidden or closed, which browsers may do without the app exiting cleanly.
+    ///
+    /// This field only exists in WASM builds.
+    #[cfg(target_arch = "wasm32")]
+    pub save_on_hide: bool,
+    /// An optional [`StorageBackend`] that stores the preferences instead of the filesystem or
+    /// LocalStorage, which is set with [`PrefsPlugin::with_backend`].
+    pub backend: Option<Arc<dyn StorageBackend>>,
+    /// Systems that run once after the preferences are first loaded, which are added with
+    /// [`PrefsPlugin::on_loaded`].
+    pub on_loaded: OnLoadedSystems,
     /// PhantomData
     pub _phantom: PhantomData<T>,
 }
 impl<T: Reflect + TypePath> Default for PrefsPlugin<T> {
     fn default() -> Self {
-        // For wasm, we want to provide a unique name for a project by default
-        // to avoid collisions when doing local development or deploying multiple
-        // apps to the same web server (for example, itch.io).
-        let package_name = T::crate_name().unwrap_or("bevy_simple");
-
         Self {
-            filename: format!("{}_prefs.ron", package_name),
+            filename: Self::default_filename(),
+            #[cfg(not(target_arch = "wasm32"))]
             path: Default::default(),
+            prefix: String::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            load_paths: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            fallback_path: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            mirror_paths: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            base_path: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            create_dirs: true,
+            #[cfg(not(target_arch = "wasm32"))]
+            file_mode: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            save_retry: Default::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            history: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            journal: None,
+            format: Default::default(),
+            blocking_load: false,
+            load_schedule: Some(Startup.intern()),
+            read_only: false,
+            embedded_default: None,
+            #[cfg(feature = "asset")]
+            default_asset: None,
+            #[cfg(feature = "http")]
+            http_defaults: None,
+            periodic_save: None,
+            serialize_in_place: false,
+            warn_constant_changes: None,
+            shared_file: false,
+            pre_write: None,
+            post_read: None,
+            post_load: None,
+            log_loaded: false,
+            save_if: None,
+            warm_up_frames: 0,
+            #[cfg(not(target_arch = "wasm32"))]
+            sidecar: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            env_prefix: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            fragment_dir: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            slots: 0,
+            #[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+            keychain: false,
+            #[cfg(all(feature = "compact", target_arch = "wasm32"))]
+            compact_storage: false,
+            #[cfg(target_arch = "wasm32")]
+            save_on_hide: true,
+            backend: None,
+            on_loaded: Default::default(),
             _phantom: Default::default(),
         }
     }
 }
 
-/// Settings for `PrefsPlugin`.
-#[derive(Resource)]
-pub struct PrefsSettings<T> {
-    /// Filename (or LocalStorage key) for the preferences file.
-    pub filename: String,
-    /// Path to the directory where the preferences file will be stored.
-    pub path: PathBuf,
-    /// PhantomData
-    pub _phantom: PhantomData<T>,
-}
+impl<T: Reflect + TypePath> PrefsPlugin<T> {
+    /// Returns the default [`PrefsPlugin::filename`], which is `{crate}_prefs.ron`.
+    ///
+    /// `{crate}` is the name of the crate that `T` is defined in, according to
+    /// [`TypePath::crate_name`]. In a workspace, this may be a library crate rather than the
+    /// app's own crate.
+    ///
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_simple_prefs::{Prefs, PrefsPlugin};
+    /// # #[derive(Prefs, Reflect, Default)]
+    /// # struct ExamplePrefs {}
+    /// let filename = PrefsPlugin::<ExamplePrefs>::default_filename();
+    /// assert!(filename.ends_with("_prefs.ron"));
+    ///
+    /// App::new().add_plugins(PrefsPlugin::<ExamplePrefs> {
+    ///     filename: filename.replace(".ron", ".cfg"),
+    ///     ..default()
+    /// });
+    /// ```
+    pub fn default_filename() -> String {
+        // For wasm, we want to provide a unique name for a project by default
+        // to avoid collisions when doing local development or deploying multiple
+        // apps to the same web server (for example, itch.io).
+        let package_name = T::crate_name().unwrap_or("bevy_simple");
 
-/// Current status of the `PrefsPlugin`.
-#[derive(Resource)]
-pub struct PrefsStatus<T> {
-    /// `true` if the preferences have been
-    pub loaded: bool,
-    _phantom: PhantomData<T>,
-}
+        format!("{}_prefs.ron", package_name)
+    }
 
-impl<T> Default for PrefsStatus<T> {
-    fn default() -> Self {
+    /// Creates a `PrefsPlugin` that never loads the preferences on its own.
+    ///
+    /// The plugin's resources are still inserted and the preference resources are initialized
+    /// with their default values, but [`Prefs::load`] is left for the caller to run, which is
+    /// useful for crates building on top of this one. This is the same as setting
+    /// [`PrefsPlugin::load_schedule`] to `None`.
+    ///
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_simple_prefs::{Prefs, PrefsPlugin, PrefsStatus};
+    /// # #[derive(Prefs, Reflect, Default)]
+    /// # struct ExamplePrefs {
+    /// #     volume: Volume,
+    /// # }
+    /// # #[derive(Resource, Reflect, Clone, Default)]
+    /// # struct Volume(u32);
+    /// let mut app = App::new();
+    /// app.add_plugins(TaskPoolPlugin::default());
+    /// app.add_plugins(PrefsPlugin::<ExamplePrefs> {
+    ///     blocking_load: true,
+    ///     ..PrefsPlugin::without_startup_load()
+    /// });
+    /// app.update();
+    /// assert!(!app.world().resource::<PrefsStatus<ExamplePrefs>>().loaded);
+    ///
+    /// ExamplePrefs::load(app.world_mut());
+    /// assert!(app.world().resource::<PrefsStatus<ExamplePrefs>>().loaded);
+    /// ```
+    pub fn without_startup_load() -> Self {
         Self {
-            loaded: false,
-            _phantom: Default::default(),
+            load_schedule: None,
+            ..Default::default()
         }
     }
-}
 
-/// A component that holds the task responsible for updating individual preference `Resource`s after they have been loaded.
-#[derive(Component)]
-pub struct LoadPrefsTask(pub Task<CommandQueue>);
+    /// Returns a `PrefsPlugin` that stores the preferences file in the directory containing the
+    /// executable, for portable installs.
+    ///
+    /// Unlike the default `path`, this doesn't depend on the working directory, which can differ
+    /// when the app is launched from a shortcut. Symlinks to the executable are resolved, so the
+    /// preferences file is stored next to the actual binary.
+    ///
+    /// Returns [`PrefsError::Io`] if the location of the executable can't be determined, or if
+    /// files can't be created in its directory, such as when the app is installed system-wide.
+    /// Consider setting `fallback_path` for the latter.
+    ///
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_simple_prefs::{Prefs, PrefsPlugin};
+    /// # #[derive(Prefs, Reflect, Default)]
+    /// # struct ExamplePrefs {}
+    /// let plugin = PrefsPlugin::<ExamplePrefs>::next_to_executable().unwrap();
+    ///
+    /// let exe = std::env::current_exe().unwrap().canonicalize().unwrap();
+    /// assert_eq!(Some(plugin.path.as_path()), exe.parent());
+    /// ```
+    ///
+    /// This function does not exist in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn next_to_executable() -> Result<Self, PrefsError> {
+        let io_error = |e: std::io::Error, context: String| {
+            PrefsError::Io(Arc::new(std::io::Error::new(
+                e.kind(),
+                format!("{}: {}", context, e),
+            )))
+        };
 
-impl<T: Prefs + Reflect + TypePath> Plugin for PrefsPlugin<T> {
-    fn build(&self, app: &mut bevy::prelude::App) {
-        app.insert_resource::<PrefsSettings<T>>(PrefsSettings {
-            filename: self.filename.clone(),
-            path: self.path.clone(),
-            _phantom: Default::default(),
-        });
-        app.init_resource::<PrefsStatus<T>>();
+        let exe = std::env::current_exe()
+            .and_then(|exe| exe.canonicalize())
+            .map_err(|e| io_error(e, "failed to locate executable".into()))?;
+        let dir = exe.parent().ok_or_else(|| {
+            PrefsError::other(format!("executable {:?} has no parent directory", exe))
+        })?;
 
-        <T>::init(app);
+        // Permissions don't reliably tell whether files can be created, such as with ACLs or a
+        // read-only filesystem, so a file is created and removed to find out.
+        let probe = dir.join(format!(".prefs_probe_{}", std::process::id()));
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&probe)
+            .map_err(|e| io_error(e, format!("executable directory {:?} isn't writable", dir)))?;
+        let _ = std::fs::remove_file(&probe);
 
-        // `save` checks load status and needs to run in the same frame after `handle_tasks`.
-        app.add_systems(Update, (handle_tasks, <T>::save).chain());
-        app.add_systems(Startup, <T>::load);
+        Ok(Self {
+            path: dir.to_path_buf(),
+            ..Default::default()
+        })
+    }
+
+    /// Adds a system that runs a single time, right after the loaded preferences have been
+    /// applied.
+    ///
+    /// This is useful for applying preferences to other parts of the app, such as setting the
+    /// volume of an audio engine. The system runs after the first load only, and is removed
+    /// afterwards. Systems run in the order they were added.
+    ///
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_simple_prefs::{Prefs, PrefsPlugin};
+    /// # #[derive(Prefs, Reflect, Default)]
+    /// # struct ExamplePrefs {
+    /// #     volume: Volume,
+    /// # }
+    /// # #[derive(Resource, Reflect, Clone, Default)]
+    /// # struct Volume(u32);
+    /// #[derive(Resource, Default)]
+    /// struct AudioEngine {
+    ///     volume: u32,
+    ///     configured: u32,
+    /// }
+    ///
+    /// fn configure_audio(volume: Res<Volume>, mut engine: ResMut<AudioEngine>) {
+    ///     engine.volume = volume.0;
+    ///     engine.configured += 1;
+    /// }
+    ///
+    /// App::new()
+    ///     .init_resource::<AudioEngine>()
+    ///     .add_plugins(PrefsPlugin::<ExamplePrefs>::default().on_loaded(configure_audio));
+    /// ```
+    pub fn on_loaded<M>(self, system: impl IntoSystem<(), (), M>) -> Self {
+        self.on_loaded
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Box::new(IntoSystem::into_system(system)));
+        self
+    }
+
+    /// Stores the preferences with `backend` rather than in a file or in LocalStorage.
+    ///
+    /// The preferences are loaded, saved and deleted under [`PrefsSettings::key`], which is
+    /// `prefix` followed by `filename`. Features that work on the preferences file directly, such
+    /// as `fallback_path`, `mirror_paths`, `journal` and `sidecar`, are not used.
+    ///
+    /// ```rust
+    /// # use bevy::prelude::*;
+    /// # use bevy_simple_prefs::{MemoryBackend, Prefs, PrefsPlugin};
+    /// # #[derive(Prefs, Reflect, Default)]
+    /// # struct ExamplePrefs {
+    /// #     volume: Volume,
+    /// # }
+    /// # #[derive(Resource, Reflect, Clone, Default)]
+    /// # struct Volume(u32);
+    /// App::new().add_plugins(PrefsPlugin::<ExamplePrefs>::default().with_backend(MemoryBackend::default()));
+    /// ```
+    pub fn with_backend(mut self, backend: impl StorageBackend) -> Self {
+        self.backend = Some(Arc::new(backend));
+        self
     }
 }
 
-fn handle_tasks(mut commands: Commands, mut transform_tasks: Query<&mut LoadPrefsTask>) {
-    for mut task in &mut transform_tasks {
-        if let Some(mut commands_queue) = block_on(future::poll_once(&mut task.0)) {
-            bevy::log::debug!("adding pref resource update commands");
-            commands.append(&mut commands_queue);
-        }
+/// Systems added with [`PrefsPlugin::on_loaded`].
+#[derive(Default)]
+pub struct OnLoadedSystems(Mutex<Vec<BoxedSystem>>);
+
+/// The [`OnLoadedSystems`] of `T` that haven't run yet.
+#[derive(Resource)]
+struct PrefsOnLoaded<T> {
+    systems: Vec<SystemId>,
+    _phantom: PhantomData<T>,
+}
+
+fn run_on_loaded<T: Send + Sync + 'static>(
+    mut commands: Commands,
+    status: Res<PrefsStatus<T>>,
+    mut on_loaded: ResMut<PrefsOnLoaded<T>>,
+) {
+    if !status.loaded || on_loaded.systems.is_empty() {
+        return;
+    }
+
+    for system in on_loaded.systems.drain(..) {
+        commands.run_system(system);
+        commands.unregister_system(system);
     }
 }
 
-/// Loads preferences from persisted data.
-pub fn load_str(dir: &Path, filename: &str) -> Option<String> {
+/// Settings for `PrefsPlugin`.
+#[derive(Resource)]
+pub struct PrefsSettings<T> {
+    /// Filename for the preferences file.
+    ///
+    /// In WASM builds, this is used as the LocalStorage key.
+    pub filename: String,
+    /// Path to the directory where the preferences file will be stored.
     #[cfg(not(target_arch = "wasm32"))]
-    {
-        let path = dir.join(filename);
+    pub path: PathBuf,
+    /// A prefix used to keep the preferences of different environments apart.
+    pub prefix: String,
+    /// Additional directories to load the preferences file from, in priority order.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub load_paths: Vec<PathBuf>,
+    /// An optional directory to save the preferences file to if saving to `path` fails with
+    /// [`PrefsError::ReadOnly`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fallback_path: Option<PathBuf>,
+    /// Additional directories that the preferences file is copied to after each successful save.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub mirror_paths: Vec<PathBuf>,
+    /// An optional directory containing defaults that the preferences file is layered on top of.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub base_path: Option<PathBuf>,
+    /// If `true`, `path` and any missing parent directories are created before saving.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub create_dirs: bool,
+    /// Optional Unix permissions for the preferences file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub file_mode: Option<u32>,
+    /// How failed automatic saves are retried.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub save_retry: SaveRetry,
+    /// Optional log of changes to individual preferences.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub history: Option<PrefsHistory>,
+    /// Optional journal of changes, which is appended to instead of rewriting the whole
+    /// preferences file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub journal: Option<PrefsJournal>,
+    /// Options controlling how the preferences file is written and read.
+    pub format: FormatOptions,
+    /// If `true`, preferences are loaded synchronously rather than in the background.
+    pub blocking_load: bool,
+    /// If `true`, preferences are loaded as usual but never written.
+    pub read_only: bool,
+    /// Optional serialized preferences that are loaded when no preferences have been stored.
+    pub embedded_default: Option<&'static str>,
+    /// Serialized preferences from [`PrefsPlugin::default_asset`], once it has loaded, which are
+    /// loaded when no preferences have been stored in preference to `embedded_default`.
+    #[cfg(feature = "asset")]
+    pub asset_default: Option<String>,
+    /// Optional default preferences that are fetched from a URL.
+    #[cfg(feature = "http")]
+    pub http_defaults: Option<HttpDefaults>,
+    /// The defaults most recently fetched from `http_defaults`, or `embedded_default` if fetching
+    /// them failed.
+    #[cfg(feature = "http")]
+    pub http_default: Option<String>,
+    /// If set, the preferences are saved at this interval even if they haven't changed.
+    pub periodic_save: Option<Duration>,
+    /// If `true`, the preferences are serialized on the main thread directly from the individual
+    /// preference `Resources`, rather than from a clone of them.
+    pub serialize_in_place: bool,
+    /// If set, a warning is logged in debug builds the first time a preference changes in this
+    /// many consecutive frames.
+    pub warn_constant_changes: Option<u32>,
+    /// If `true`, the preferences are stored in a section of a file that is shared with other
+    /// prefs types.
+    pub shared_file: bool,
+    /// An optional function that transforms the serialized preferences before they are written.
+    pub pre_write: Option<fn(String) -> String>,
+    /// An optional function that transforms the stored preferences after they are read.
+    pub post_read: Option<fn(String) -> String>,
+    /// An optional function that is given the loaded preferences as a whole, before they are
+    /// inserted, and returns `true` if they should be saved.
+    pub post_load: Option<fn(&mut T) -> bool>,
+    /// If `true`, the value of each field is logged at debug level once loaded.
+    pub log_loaded: bool,
+    /// An optional function that returns `false` while changed preferences shouldn't be saved.
+    pub save_if: Option<fn(&World) -> bool>,
+    /// An optional function that returns a sidecar file to write alongside the preferences file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub sidecar: Option<fn(&str) -> Option<(String, String)>>,
+    /// An optional prefix of environment variables that override the loaded preferences.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub env_prefix: Option<String>,
+    /// An optional directory of fragment files that override the loaded preferences.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fragment_dir: Option<PathBuf>,
+    /// The number of save slots, or `0` for a single preferences file.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub slots: u32,
+    /// If `true`, preferences are stored in the OS keychain rather than in a file.
+    #[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+    pub keychain: bool,
+    /// If `true`, preferences are compressed before being stored in LocalStorage.
+    #[cfg(all(feature = "compact", target_arch = "wasm32"))]
+    pub compact_storage: bool,
+    /// An optional [`StorageBackend`] that stores the preferences instead of the filesystem or
+    /// LocalStorage.
+    pub backend: Option<Arc<dyn StorageBackend>>,
+    /// PhantomData
+    pub _phantom: PhantomData<T>,
+}
+
+impl<T> Clone for PrefsSettings<T> {
+    fn clone(&self) -> Self {
+        Self {
+            filename: self.filename.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            path: self.path.clone(),
+            prefix: self.prefix.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            load_paths: self.load_paths.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            fallback_path: self.fallback_path.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            mirror_paths: self.mirror_paths.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            base_path: self.base_path.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            create_dirs: self.create_dirs,
+            #[cfg(not(target_arch = "wasm32"))]
+            file_mode: self.file_mode,
+            #[cfg(not(target_arch = "wasm32"))]
+            save_retry: self.save_retry.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            history: self.history.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            journal: self.journal.clone(),
+            format: self.format.clone(),
+            blocking_load: self.blocking_load,
+            read_only: self.read_only,
+            embedded_default: self.embedded_default,
+            #[cfg(feature = "asset")]
+            asset_default: self.asset_default.clone(),
+            #[cfg(feature = "http")]
+            http_defaults: self.http_defaults.clone(),
+            #[cfg(feature = "http")]
+            http_default: self.http_default.clone(),
+            periodic_save: self.periodic_save,
+            serialize_in_place: self.serialize_in_place,
+            warn_constant_changes: self.warn_constant_changes,
+            shared_file: self.shared_file,
+            pre_write: self.pre_write,
+            post_read: self.post_read,
+            post_load: self.post_load,
+            log_loaded: self.log_loaded,
+            save_if: self.save_if,
+            #[cfg(not(target_arch = "wasm32"))]
+            sidecar: self.sidecar,
+            #[cfg(not(target_arch = "wasm32"))]
+            env_prefix: self.env_prefix.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            fragment_dir: self.fragment_dir.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            slots: self.slots,
+            #[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+            keychain: self.keychain,
+            #[cfg(all(feature = "compact", target_arch = "wasm32"))]
+            compact_storage: self.compact_storage,
+            backend: self.backend.clone(),
+            _phantom: Default::default(),
+        }
+    }
+}
 
-        std::fs::read_to_string(path).ok()
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> PrefsSettings<T> {
+    /// Returns the directory that preferences are saved to, which is `path` followed by `prefix`.
+    pub fn dir(&self) -> PathBuf {
+        self.path.join(&self.prefix)
     }
 
-    #[cfg(target_arch = "wasm32")]
-    {
-        let Some(window) = web_sys::window() else {
-            warn!("Failed to load save file: no window.");
-            return None;
+    /// Returns the directory that preferences are saved to if saving to [`Self::dir`] fails,
+    /// which is `fallback_path` followed by `prefix`.
+    pub fn fallback_dir(&self) -> Option<PathBuf> {
+        self.fallback_path
+            .as_ref()
+            .map(|fallback_path| fallback_path.join(&self.prefix))
+    }
+
+    /// Returns the directories that the preferences file is mirrored to, which are each of
+    /// `mirror_paths` followed by `prefix`.
+    pub fn mirror_dirs(&self) -> Vec<PathBuf> {
+        self.mirror_paths
+            .iter()
+            .map(|mirror_path| mirror_path.join(&self.prefix))
+            .collect()
+    }
+
+    /// Returns the directories that preferences are loaded from, in priority order.
+    pub fn load_dirs(&self) -> Vec<PathBuf> {
+        self.fallback_dir()
+            .into_iter()
+            .chain(std::iter::once(self.dir()))
+            .chain(self.load_paths.iter().cloned())
+            .chain(self.mirror_dirs())
+            .collect()
+    }
+}
+
+impl<T: TypePath> PrefsSettings<T> {
+    /// Returns a readable summary of where and how the preferences are stored, such as for
+    /// including in a bug report.
+    ///
+    /// See [`describe_prefs`] for a summary that also includes the [`PrefsStatus`].
+    pub fn describe(&self) -> String {
+        use std::fmt::Write as _;
+
+        let mut out = T::type_path().to_string();
+        let mut line = |label: &str, value: &dyn std::fmt::Display| {
+            let _ = write!(out, "\n  {}: {}", label, value);
         };
 
-        let Ok(Some(storage)) = window.local_storage() else {
-            warn!("Failed to load save file: no storage.");
-            return None;
+        #[allow(unused_mut)]
+        let mut storage = match &self.backend {
+            Some(_) => format!("custom backend, key `{}`", self.key()),
+            #[cfg(not(target_arch = "wasm32"))]
+            None => format!("file {:?}", self.dir().join(&self.filename)),
+            #[cfg(target_arch = "wasm32")]
+            None => format!("LocalStorage, key `{}`", self.key()),
         };
+        #[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+        if self.keychain {
+            storage = format!("OS keychain, key `{}`", self.key());
+        }
+        #[cfg(all(feature = "compact", target_arch = "wasm32"))]
+        if self.compact_storage {
+            storage.push_str(", compressed");
+        }
+        if self.is_shared() {
+            storage.push_str(", shared with other preferences");
+        }
+        line("storage", &storage);
 
-        let Ok(maybe_item) = storage.get_item(filename) else {
-            warn!("Failed to load save file: failed to get item.");
-            return None;
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let dirs = |dirs: &[PathBuf]| format!("{:?}", dirs);
+            if !self.load_paths.is_empty() {
+                line("load paths", &dirs(&self.load_paths));
+            }
+            if let Some(fallback_dir) = self.fallback_dir() {
+                line("fallback", &format!("{:?}", fallback_dir));
+            }
+            if !self.mirror_paths.is_empty() {
+                line("mirrors", &dirs(&self.mirror_dirs()));
+            }
+            if let Some(base_path) = &self.base_path {
+                line("base", &format!("{:?}", base_path));
+            }
+            if let Some(fragment_dir) = &self.fragment_dir {
+                line("fragments", &format!("{:?}", self.dir().join(fragment_dir)));
+            }
+            if self.slots > 0 {
+                line("slots", &self.slots.to_string());
+            }
+            if let Some(env_prefix) = &self.env_prefix {
+                line("environment overrides", &format!("{}*", env_prefix));
+            }
+            if let Some(journal) = &self.journal {
+                line("journal", &journal.resolve_filename(&self.filename));
+            }
+            if let Some(history) = &self.history {
+                line("history", &format!("{:?}", history));
+            }
+        }
+
+        #[cfg(feature = "http")]
+        if let Some(http_defaults) = &self.http_defaults {
+            line("defaults URL", &http_defaults.url);
+        }
+
+        let mut format = vec!["RON"];
+        let flags = [
+            (self.format.sort_keys, "sorted keys"),
+            (self.format.strict, "strict"),
+            (self.format.schema_hash, "schema hash"),
+            (self.format.field_comments, "field comments"),
+            (self.format.timestamp, "timestamp"),
+            (self.format.limits.is_some(), "limits"),
+        ];
+        format.extend(flags.iter().filter(|(on, _)| *on).map(|(_, name)| *name));
+        line("format", &format.join(", "));
+        if !self.format.extensions.is_empty() {
+            line("extensions", &format!("{:?}", self.format.extensions));
+        }
+        if let Some(build_version) = self.format.build_version {
+            line("build version", &build_version);
+        }
+        #[cfg(feature = "secret")]
+        line(
+            "secret key",
+            &match self.format.secret_key {
+                Some(_) => "set",
+                None => "not set",
+            },
+        );
+
+        let mut saving = vec![match self.read_only {
+            true => "read only".to_string(),
+            false => "on change".to_string(),
+        }];
+        if let Some(periodic_save) = self.periodic_save {
+            saving.push(format!("every {:?}", periodic_save));
+        }
+        if self.save_if.is_some() {
+            saving.push("conditional".to_string());
+        }
+        line("saving", &saving.join(", "));
+        line(
+            "loading",
+            &match self.blocking_load {
+                true => "blocking",
+                false => "in the background",
+            },
+        );
+
+        out
+    }
+
+    /// Returns the serialized preferences that are loaded when no preferences have been stored,
+    /// which are the contents of the `default_asset` if it has loaded, or `embedded_default`.
+    pub fn first_run_default(&self) -> Option<String> {
+        #[cfg(feature = "asset")]
+        if let Some(asset_default) = &self.asset_default {
+            return Some(asset_default.clone());
+        }
+
+        self.embedded_default.map(str::to_string)
+    }
+
+    /// Returns `prefix` followed by `filename`, which is used as the LocalStorage key in WASM
+    /// builds.
+    pub fn key(&self) -> String {
+        format!("{}{}", self.prefix, self.filename)
+    }
+
+    /// Reads the stored preferences and applies `post_read`.
+    ///
+    /// Returns `Ok(None)` if no preferences have been stored. This is [`Self::read_raw`] followed
+    /// by [`Self::finish_read`].
+    pub fn read(&self) -> Result<Option<String>, PrefsError> {
+        self.finish_read(self.read_raw())
+    }
+
+    /// Reads the stored preferences without decompressing them, extracting them from a shared
+    /// file, or applying `post_read`, which is left to [`Self::finish_read`].
+    ///
+    /// LocalStorage can only be read synchronously, so WASM builds call this when a load starts,
+    /// and leave the rest to the load task.
+    pub fn read_raw(&self) -> Result<Option<String>, PrefsError> {
+        let _lock = self.is_shared().then(sections::lock);
+        self.read_stored_raw()
+    }
+
+    /// Finishes reading preferences returned by [`Self::read_raw`], decompressing them,
+    /// extracting this type's section of a shared file, and applying `post_read`.
+    pub fn finish_read(
+        &self,
+        raw: Result<Option<String>, PrefsError>,
+    ) -> Result<Option<String>, PrefsError> {
+        let serialized = raw.and_then(|stored| self.decode_stored(stored));
+        let serialized = match self.is_shared() {
+            true => serialized.and_then(|shared| match shared {
+                Some(shared) => sections::get(&shared, T::short_type_path()),
+                None => Ok(None),
+            }),
+            false => serialized,
         };
 
-        maybe_item
+        match self.post_read {
+            Some(post_read) => serialized.map(|s| s.map(post_read)),
+            None => serialized,
+        }
+    }
+
+    /// Reads the stored preferences file, which may be shared with other prefs types.
+    fn read_stored(&self) -> Result<Option<String>, PrefsError> {
+        self.read_stored_raw()
+            .and_then(|stored| self.decode_stored(stored))
     }
-}
 
-/// Persists preferences.
-pub fn save_str(dir: &Path, filename: &str, data: &str) {
+    /// Reads the stored preferences file without decompressing it.
+    fn read_stored_raw(&self) -> Result<Option<String>, PrefsError> {
+        let (storage, key) = self.storage();
+        block_on(storage.load(&key))
+    }
+
+    /// Returns the storage that the preferences are read from and written to, along with the key
+    /// that they are stored under.
+    ///
+    /// This is a `KeychainBackend` with [`Self::key`] if `keychain` is set, or `backend` with
+    /// [`Self::key`] if it is set. Otherwise, it is a [`FileBackend`] for [`Self::dir`] with
+    /// `filename`, or in WASM builds, a `LocalStorageBackend` with [`Self::key`].
+    pub fn storage(&self) -> (Box<dyn StorageBackend>, String) {
+        #[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+        if self.keychain {
+            return (Box::new(KeychainBackend::new(T::type_path())), self.key());
+        }
+
+        if let Some(backend) = &self.backend {
+            return (Box::new(backend.clone()), self.key());
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        return (
+            Box::new(self.file_backend(self.dir())),
+            self.filename.clone(),
+        );
+        #[cfg(target_arch = "wasm32")]
+        (Box::new(LocalStorageBackend), self.key())
+    }
+
+    /// Returns a [`FileBackend`] that saves to `dir`, and loads from and mirrors to the
+    /// directories in these settings.
     #[cfg(not(target_arch = "wasm32"))]
-    {
-        let path = dir.join(filename);
+    fn file_backend(&self, dir: PathBuf) -> FileBackend {
+        FileBackend {
+            dir,
+            load_dirs: self.load_dirs(),
+            mirror_dirs: self.mirror_dirs(),
+            create_dirs: self.create_dirs,
+            file_mode: self.file_mode,
+        }
+    }
+
+    /// Returns `true` if the preferences are stored in files, rather than with a `backend` or in
+    /// the OS keychain.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn stores_files(&self) -> bool {
+        #[cfg(feature = "keychain")]
+        if self.keychain {
+            return false;
+        }
+
+        self.backend.is_none()
+    }
 
-        if let Err(e) = std::fs::write(path, data) {
-            warn!("Failed to store save file: {:?}", e);
+    /// Returns `true` if the preferences are a section of a file shared with other prefs types,
+    /// which `shared_file` has no effect on in the OS keychain.
+    fn is_shared(&self) -> bool {
+        #[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+        if self.keychain {
+            return false;
         }
+
+        self.shared_file
     }
 
-    #[cfg(target_arch = "wasm32")]
-    {
-        let window = match web_sys::window() {
-            Some(w) => w,
-            None => {
-                warn!("Failed to store save file: no window.");
-                return;
+    /// Compresses serialized preferences before they are stored in LocalStorage, if
+    /// `compact_storage` is set.
+    #[cfg(all(feature = "compact", target_arch = "wasm32"))]
+    fn encode_stored(&self, serialized: String) -> String {
+        match self.compact_storage && self.backend.is_none() {
+            true => compact::encode(&serialized),
+            false => serialized,
+        }
+    }
+
+    /// Decompresses a stored preferences file read with [`Self::read_stored_raw`].
+    fn decode_stored(&self, stored: Option<String>) -> Result<Option<String>, PrefsError> {
+        #[cfg(all(feature = "compact", target_arch = "wasm32"))]
+        let stored = stored.map(compact::decode).transpose()?;
+        Ok(stored)
+    }
+
+    /// Applies `pre_write` and stores the serialized preferences, followed by any `sidecar` file.
+    ///
+    /// If `shared_file` is set, only this type's section of the shared file is replaced. If a
+    /// `backend` is set, the preferences are saved to it under [`Self::key`] instead, without a
+    /// `sidecar` file.
+    ///
+    /// If writing the preferences file fails with [`PrefsError::ReadOnly`], the preferences are
+    /// written to `fallback_path` instead, and `path` is updated to match. Once the preferences
+    /// file has been written, it is copied to any `mirror_paths`. Failed writes aren't retried
+    /// here; automatic saves are retried according to `save_retry`.
+    pub fn write(&mut self, serialized: String) -> Result<(), PrefsError> {
+        let serialized = match self.pre_write {
+            Some(pre_write) => pre_write(serialized),
+            None => serialized,
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let sidecar = self
+            .sidecar
+            .filter(|_| self.stores_files())
+            .and_then(|sidecar| sidecar(&serialized));
+
+        let _lock = self.is_shared().then(sections::lock);
+        let serialized = match self.is_shared() {
+            true => {
+                let shared = self.read_stored()?;
+                sections::set(shared.as_deref(), T::short_type_path(), Some(&serialized))?
+                    .unwrap_or_default()
             }
+            false => serialized,
         };
 
-        let storage = match window.local_storage() {
-            Ok(Some(s)) => s,
-            _ => {
-                warn!("Failed to store save file: no storage.");
+        #[cfg(all(feature = "compact", target_arch = "wasm32"))]
+        let serialized = self.encode_stored(serialized);
+
+        let (storage, key) = self.storage();
+        #[allow(unused_mut)]
+        let mut result = block_on(storage.save(&key, &serialized));
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.stores_files() {
+            let mut dir = self.dir();
+
+            if let (Err(PrefsError::ReadOnly(_)), Some(fallback_dir)) =
+                (&result, self.fallback_dir())
+            {
+                if fallback_dir != dir {
+                    let fallback = FileBackend {
+                        create_dirs: true,
+                        ..self.file_backend(fallback_dir.clone())
+                    };
+                    result = block_on(fallback.save(&key, &serialized));
+                    if result.is_ok() {
+                        warn!("Saved preferences to fallback path {:?}", fallback_dir);
+                        self.path = self.fallback_path.clone().unwrap_or_default();
+                        dir = fallback_dir;
+                    }
+                }
+            }
+
+            if let (Ok(()), Some(journal)) = (&result, &self.journal) {
+                delete_str(&dir, &journal.resolve_filename(&self.filename));
+            }
+
+            result = result.and_then(|()| match sidecar {
+                Some((filename, contents)) => {
+                    save_str_with_mode(&dir, &filename, &contents, self.create_dirs, self.file_mode)
+                }
+                None => Ok(()),
+            });
+        }
+
+        result
+    }
+
+    /// Deletes the stored preferences, if they exist.
+    ///
+    /// If `shared_file` is set, only this type's section of the shared file is removed, and the
+    /// file is deleted once no sections remain.
+    pub fn delete(&self) {
+        let _lock = self.is_shared().then(sections::lock);
+        if self.is_shared() {
+            let remaining = match self.read_stored() {
+                Ok(Some(shared)) => sections::set(Some(&shared), T::short_type_path(), None),
+                Ok(None) => return,
+                Err(e) => Err(e),
+            };
+            let remaining = match remaining {
+                Ok(remaining) => remaining,
+                Err(e) => {
+                    warn!("Failed to update shared preferences file: {:?}", e);
+                    return;
+                }
+            };
+            if let Some(remaining) = remaining {
+                #[cfg(all(feature = "compact", target_arch = "wasm32"))]
+                let remaining = self.encode_stored(remaining);
+                let (storage, key) = self.storage();
+                if let Err(e) = block_on(storage.save(&key, &remaining)) {
+                    warn!("Failed to store save file: {:?}", e);
+                }
                 return;
             }
-        };
+        }
 
-        if let Err(e) = storage.set_item(filename, data) {
-            warn!("Failed to store save file: {:?}", e);
+        let (storage, key) = self.storage();
+        if let Err(e) = block_on(storage.delete(&key)) {
+            warn!("Failed to delete save file: {:?}", e);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let (true, Some(journal)) = (self.stores_files(), &self.journal) {
+            delete_str(&self.dir(), &journal.resolve_filename(&self.filename));
         }
     }
 }
 
-/// Deserializes preferences
-pub fn deserialize<T: Reflect + GetTypeRegistration + Default>(
-    serialized: &str,
-) -> Result<T, ron::de::Error> {
-    let mut registry = TypeRegistry::new();
-    registry.register::<T>();
-    let registration = registry.get(TypeId::of::<T>()).unwrap();
+impl<T: Prefs + Reflect + TypePath + GetTypeRegistration + Default> PrefsSettings<T> {
+    /// Returns the preferences that the stored preferences are layered on top of, which are
+    /// the fetched `http_defaults` or read from `base_path` if either is set, or `T::default()`
+    /// otherwise.
+    pub fn base(&self) -> Result<T, PrefsError> {
+        #[cfg(feature = "http")]
+        if let Some(http_default) = &self.http_default {
+            return deserialize_with_options(http_default, &self.format);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(base_path) = &self.base_path {
+            let serialized = match load_str(base_path, &self.filename)? {
+                Some(shared) if self.shared_file => sections::get(&shared, T::short_type_path())?,
+                serialized => serialized,
+            };
+            if let Some(serialized) = serialized {
+                return deserialize_with_options(&serialized, &self.format);
+            }
+        }
+
+        Ok(T::default())
+    }
+
+    /// Returns `true` if `http_defaults` or `base_path` is set.
+    fn is_layered(&self) -> bool {
+        #[cfg(feature = "http")]
+        if self.http_defaults.is_some() {
+            return true;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.base_path.is_some() {
+            return true;
+        }
+
+        false
+    }
+
+    /// Serializes preferences as they would be stored, leaving out fields that are identical to
+    /// [`Self::base`] if `http_defaults` or `base_path` is set.
+    ///
+    /// Fields that still have the values they were overridden with when they were loaded are
+    /// written with their stored values instead.
+    pub fn serialize(
+        &self,
+        to_save: &T,
+        preserved: &PreservedFields,
+    ) -> Result<String, PrefsError> {
+        let ReflectRef::Struct(value) = to_save.reflect_ref() else {
+            unreachable!("Prefs can only be derived for structs");
+        };
+
+        let keys = FieldKeys::of::<T>();
+        let overridden: Vec<_> = preserved
+            .overridden
+            .iter()
+            .filter(|field| {
+                value
+                    .field(keys.name(&field.key))
+                    .and_then(|value| value.reflect_partial_eq(&*field.value))
+                    .unwrap_or(false)
+            })
+            .collect();
+
+        let mut skip = T::TRANSIENT_FIELDS.to_vec();
+        skip.extend(overridden.iter().map(|field| keys.name(&field.key)));
+
+        // Overridden fields are written like transient fields, from their stored values.
+        let substituted;
+        let preserved = match overridden.is_empty() {
+            true => preserved,
+            false => {
+                let stored = overridden
+                    .iter()
+                    .filter_map(|field| Some((field.key.clone(), field.stored.clone()?)));
+                substituted = PreservedFields {
+                    transient: preserved.transient.iter().cloned().chain(stored).collect(),
+                    ..preserved.clone()
+                };
+                &substituted
+            }
+        };
+
+        if self.is_layered() {
+            let base = self.base()?;
+            let ReflectRef::Struct(base) = base.reflect_ref() else {
+                unreachable!("Prefs can only be derived for structs");
+            };
+
+            skip.extend((0..value.field_len()).filter_map(|index| {
+                let unchanged = value
+                    .field_at(index)?
+                    .reflect_partial_eq(base.field_at(index)?)?;
+                unchanged.then(|| value.name_at(index)).flatten()
+            }));
+        }
+
+        serialize_preserving_skipping(to_save, &self.format, preserved, &skip)
+    }
+
+    /// Reads and deserializes the preferences from the first of [`Self::mirror_dirs`] that
+    /// contains valid preferences, ignoring the preferences file in `path`.
+    ///
+    /// Returns `None` if no mirror contains valid preferences.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_mirrors(&self) -> Option<(T, PreservedFields)> {
+        self.mirror_dirs().into_iter().find_map(|dir| {
+            let mirror = Self {
+                path: dir.clone(),
+                prefix: String::new(),
+                load_paths: Vec::new(),
+                fallback_path: None,
+                mirror_paths: Vec::new(),
+                journal: None,
+                backend: None,
+                ..self.clone()
+            };
+            let result = mirror
+                .read()
+                .and_then(|serialized| serialized.map(|s| mirror.deserialize(&s)).transpose());
+            match result {
+                Ok(Some(value)) => {
+                    warn!("Loaded preferences from mirror {:?}", dir);
+                    Some(value)
+                }
+                Ok(None) => None,
+                Err(e) => {
+                    warn!("Failed to load preferences from mirror {:?}: {}", dir, e);
+                    None
+                }
+            }
+        })
+    }
+
+    /// Deserializes stored preferences, applying them on top of [`Self::base`], followed by any
+    /// entries in the `journal`.
+    pub fn deserialize(&self, serialized: &str) -> Result<(T, PreservedFields), PrefsError> {
+        let (value, preserved) =
+            deserialize_preserving_onto(self.base()?, serialized, &self.format)?;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let (Some(journal), true) = (&self.journal, self.stores_files()) {
+            return Ok((self.replay_journal(journal, value)?, preserved));
+        }
+
+        Ok((value, preserved))
+    }
 
-    let mut deserializer = ron::Deserializer::from_str(serialized).unwrap();
+    /// Stores serialized preferences like [`Self::write`], or, if `journal` is set and only
+    /// some preferences changed, appends `changes` to the journal instead.
+    ///
+    /// `changes` are the names of the changed fields and their values, serialized with
+    /// [`serialize_compact`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write_journaled(
+        &mut self,
+        serialized: String,
+        changes: &[(&'static str, String)],
+    ) -> Result<(), PrefsError> {
+        let (Some(journal), true) = (&self.journal, self.stores_files()) else {
+            return self.write(serialized);
+        };
+
+        let dir = self.dir();
+        let journal_path = dir.join(journal.resolve_filename(&self.filename));
+        let appendable = !changes.is_empty()
+            && !changes
+                .iter()
+                .any(|(field, _)| T::SECRET_FIELDS.contains(field))
+            && dir.join(&self.filename).exists()
+            && std::fs::metadata(&journal_path).map_or(0, |m| m.len()) < journal.max_bytes;
+
+        if appendable {
+            let keys = FieldKeys::of::<T>();
+            let fields: Vec<_> = changes
+                .iter()
+                .map(|(field, value)| {
+                    let key = keys.key(field);
+                    let raw = key.contains(['.', '+', '-']).then_some("r#");
+                    format!("{}{}:{}", raw.unwrap_or_default(), key, value)
+                })
+                .collect();
+            let entry = format!("({})\n", fields.join(","));
+
+            let result = open_with_mode(&journal_path, true, self.file_mode)
+                .and_then(|mut file| file.write_all(entry.as_bytes()));
+            match result {
+                Ok(()) => return Ok(()),
+                Err(e) => warn!("Failed to append to journal, writing a snapshot: {:?}", e),
+            }
+        }
+
+        self.write(serialized)
+    }
+
+    /// Applies `fragment_dir` and `env_prefix` to loaded preferences, recording the fields that
+    /// they override in `preserved` so that their stored values are saved rather than the
+    /// overrides.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn apply_overrides(
+        &self,
+        value: T,
+        preserved: &mut PreservedFields,
+    ) -> Result<T, PrefsError> {
+        if self.fragment_dir.is_none() && self.env_prefix.is_none() {
+            return Ok(value);
+        }
+
+        let ReflectRef::Struct(fields) = value.reflect_ref() else {
+            unreachable!("Prefs can only be derived for structs");
+        };
+        let loaded: Vec<_> = T::FIELD_NAMES
+            .iter()
+            .filter_map(|name| Some((*name, fields.field(name)?.clone_value())))
+            .collect();
+
+        // Serialize the loaded values rather than reading them from the file, so that fields that
+        // were only set by the journal or the base preferences are covered too.
+        let serialized =
+            serialize_struct(&value, &self.format, &[]).map_err(PrefsError::Serialize)?;
+        let stored = top_level_fields(&serialized).unwrap_or_default();
+        let base = self.is_layered().then(|| self.base()).transpose()?;
+
+        let value = self.apply_env(self.apply_fragments(value));
+
+        let keys = FieldKeys::of::<T>();
+        let ReflectRef::Struct(fields) = value.reflect_ref() else {
+            unreachable!("Prefs can only be derived for structs");
+        };
+        for (name, loaded) in &loaded {
+            let Some(field) = fields.field(name) else {
+                continue;
+            };
+            if field.reflect_partial_eq(&**loaded) == Some(true) {
+                continue;
+            }
+
+            // Values that came from the base preferences are left out of the file as usual.
+            let from_base = base.as_ref().is_some_and(|base| {
+                let ReflectRef::Struct(base) = base.reflect_ref() else {
+                    return false;
+                };
+                base.field(name)
+                    .and_then(|base| base.reflect_partial_eq(&**loaded))
+                    .unwrap_or(false)
+            });
+            let key = keys.key(name);
+            preserved.overridden.push(OverriddenField {
+                key: key.to_string(),
+                value: field.clone_value(),
+                stored: stored
+                    .iter()
+                    .find(|(stored_key, _)| stored_key.trim_start_matches("r#") == key)
+                    .filter(|_| !from_base)
+                    .map(|(_, stored)| stored.to_string()),
+            });
+        }
+
+        Ok(value)
+    }
+
+    /// Overrides fields of `value` with the environment variables selected by `env_prefix`.
+    ///
+    /// Environment variables that can't be parsed are ignored with a warning.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn apply_env(&self, mut value: T) -> T {
+        let Some(env_prefix) = &self.env_prefix else {
+            return value;
+        };
+
+        let keys = FieldKeys::of::<T>();
+        for field in T::FIELD_NAMES {
+            let key = keys.key(field);
+            let name = format!("{}{}", env_prefix, key.to_uppercase().replace('-', "_"));
+            let Ok(raw) = std::env::var(&name) else {
+                continue;
+            };
+
+            // Try the value as RON first, falling back to a string so that string preferences
+            // don't need to be quoted.
+            let applied = [raw.clone(), format!("{:?}", raw)].iter().any(|raw| {
+                let raw_key = key.contains(['.', '+', '-']).then_some("r#");
+                let field = format!(
+                    "#![enable(unwrap_newtypes)]\n({}{}:{})",
+                    raw_key.unwrap_or_default(),
+                    key,
+                    raw
+                );
+                deserialize_onto(&mut value, &field, &self.format).is_ok()
+            });
+            if !applied {
+                warn!("Ignoring invalid value of {}: {:?}", name, raw);
+            }
+        }
 
-    let de = TypedReflectDeserializer::new(registration, &registry);
-    let dynamic_struct = de.deserialize(&mut deserializer)?;
+        value
+    }
+
+    /// Overrides fields of `value` with the files in `fragment_dir` that have the same extension
+    /// as `filename`, in lexicographic order of their filenames.
+    ///
+    /// Fragments that can't be read or deserialized are ignored with a warning.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn apply_fragments(&self, mut value: T) -> T {
+        let Some(fragment_dir) = &self.fragment_dir else {
+            return value;
+        };
+
+        let dir = self.dir().join(fragment_dir);
+        let mut paths: Vec<_> = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.is_file() && path.extension() == Path::new(&self.filename).extension()
+                })
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return value,
+            Err(e) => {
+                warn!("Failed to read fragment directory {:?}: {:?}", dir, e);
+                return value;
+            }
+        };
+        paths.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+        for path in paths {
+            let result = std::fs::read_to_string(&path)
+                .map_err(PrefsError::from)
+                .and_then(|fragment| deserialize_onto(&mut value, &fragment, &self.format));
+            if let Err(e) = result {
+                warn!("Ignoring invalid fragment {:?}: {}", path, e);
+            }
+        }
+
+        value
+    }
+
+    /// Applies the entries of the `journal` to preferences deserialized from the snapshot.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn replay_journal(&self, journal: &PrefsJournal, value: T) -> Result<T, PrefsError> {
+        let entries = match load_str(&self.dir(), &journal.resolve_filename(&self.filename)) {
+            Ok(Some(entries)) => entries,
+            Ok(None) => return Ok(value),
+            Err(e) => {
+                warn!("Failed to read journal: {}", e);
+                return Ok(value);
+            }
+        };
+
+        // Later entries take precedence, so combine them into a single set of fields.
+        let mut fields: Vec<(&str, &str)> = Vec::new();
+        for entry in entries.lines().filter(|entry| !entry.trim().is_empty()) {
+            let changes = ron::from_str::<IgnoredAny>(entry)
+                .ok()
+                .and_then(|_| top_level_fields(entry));
+            let Some(changes) = changes else {
+                warn!("Ignoring invalid journal entries for {}", T::type_path());
+                break;
+            };
+
+            for (name, value) in changes {
+                fields.retain(|(field, _)| *field != name);
+                fields.push((name, value));
+            }
+        }
+
+        if fields.is_empty() {
+            return Ok(value);
+        }
 
-    let mut val = T::default();
-    val.apply(&*dynamic_struct);
-    Ok(val)
+        let fields: Vec<_> = fields
+            .into_iter()
+            .map(|(name, value)| {
+                let raw = name.contains(['.', '+', '-']).then_some("r#");
+                format!("{}{}:{}", raw.unwrap_or_default(), name, value)
+            })
+            .collect();
+        deserialize_struct(value, &format!("({})", fields.join(",")), &self.format)
+    }
 }
 
-/// Serialize preferences
-pub fn serialize<T: Reflect + GetTypeRegistration>(to_save: &T) -> Result<String, ron::Error> {
-    let mut registry = TypeRegistry::new();
-    registry.register::<T>();
+/// A default [`PrefsPlugin::prefix`] for every `PrefsPlugin` added to the app after this
+/// resource is inserted.
+///
+/// This allows choosing a prefix at runtime, for example from an environment variable or a query
+/// parameter, without configuring each plugin.
+///
+/// ```rust
+/// # use bevy::prelude::*;
+/// # use bevy_simple_prefs::{Prefs, PrefsPlugin, PrefsPrefix};
+/// # #[derive(Prefs, Reflect, Default)]
+/// # struct ExamplePrefs {}
+/// App::new()
+///     .insert_resource(PrefsPrefix("staging_".to_string()))
+///     .add_plugins(PrefsPlugin::<ExamplePrefs>::default());
+/// ```
+#[derive(Resource, Clone, Debug, Default)]
+pub struct PrefsPrefix(pub String);
 
-    let config = PrettyConfig::default();
-    let reflect_serializer = TypedReflectSerializer::new(to_save, &registry);
-    to_string_pretty(&reflect_serializer, config)
+/// Options controlling how preferences are serialized and deserialized.
+#[derive(Clone, Debug, Default)]
+pub struct FormatOptions {
+    /// If `true`, the fields of the preferences struct are written in alphabetical order rather
+    /// than in the order they are declared.
+    ///
+    /// Note that this only applies to the top-level fields. For deterministic output, use a
+    /// `BTreeMap` rather than a `HashMap` for any map-like preferences.
+    pub sort_keys: bool,
+    /// RON extensions to enable when writing the preferences file.
+    ///
+    /// For example, [`Extensions::UNWRAP_NEWTYPES`] writes `volume: 50` rather than
+    /// `volume: (50)`, and [`Extensions::IMPLICIT_SOME`] writes `5` rather than `Some(5)` for
+    /// `Option` values. Enabled extensions are recorded in a header at the top of the file, so
+    /// files written with and without them can always be read back.
+    pub extensions: Extensions,
+    /// If `true`, loading fails on fields that don't exist in the preferences, rather than
+    /// ignoring them.
+    ///
+    /// This is useful to catch typos in hand-edited preferences files during development. Fields
+    /// of preferences files written by a newer [`Prefs::VERSION`] are still preserved.
+    ///
+    /// ```rust
+    /// use bevy::prelude::*;
+    /// use bevy_simple_prefs::{deserialize_with_options, FormatOptions, Prefs};
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// struct ExamplePrefs {
+    ///     volume: Volume,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Volume(u32);
+    ///
+    /// let options = FormatOptions {
+    ///     strict: true,
+    ///     ..default()
+    /// };
+    /// assert!(deserialize_with_options::<ExamplePrefs>("(volume: (20))", &options).is_ok());
+    /// assert!(deserialize_with_options::<ExamplePrefs>("(volumme: (20))", &options).is_err());
+    /// ```
+    pub strict: bool,
+    /// If `true`, the [`schema_hash`] of the preferences is recorded in a header at the top of
+    /// the file, and loading fails with [`PrefsError::SchemaMismatch`] if it doesn't match the
+    /// current shape of the preferences.
+    ///
+    /// This catches incompatible changes that weren't accompanied by a [`Prefs::VERSION`] bump.
+    /// Files without the header, and files written by a different version, are loaded as usual.
+    /// Mismatched files can still be migrated with [`PrefsPlugin::post_read`].
+    ///
+    /// ```rust
+    /// use bevy::prelude::*;
+    /// use bevy_simple_prefs::{
+    ///     deserialize_with_options, serialize_with_options, FormatOptions, Prefs, PrefsError,
+    /// };
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// struct ExamplePrefs {
+    ///     volume: Volume,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Volume(u32);
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// struct ChangedPrefs {
+    ///     volume: ChangedVolume,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct ChangedVolume(f32);
+    ///
+    /// let options = FormatOptions {
+    ///     schema_hash: true,
+    ///     ..default()
+    /// };
+    /// let saved = serialize_with_options(&ExamplePrefs::default(), &options).unwrap();
+    ///
+    /// assert!(deserialize_with_options::<ExamplePrefs>(&saved, &options).is_ok());
+    /// assert!(matches!(
+    ///     deserialize_with_options::<ChangedPrefs>(&saved, &options),
+    ///     Err(PrefsError::SchemaMismatch { .. })
+    /// ));
+    /// ```
+    pub schema_hash: bool,
+    /// If `true`, the doc comments of the fields are written as comments above them, so that
+    /// the preferences file documents itself for players editing it by hand.
+    ///
+    /// Comments are ignored when loading, and rewritten on every save.
+    ///
+    /// ```rust
+    /// use bevy::prelude::*;
+    /// use bevy_simple_prefs::{serialize_with_options, FormatOptions, Prefs};
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// struct ExamplePrefs {
+    ///     /// Master volume, from 0 to 100.
+    ///     volume: Volume,
+    ///     /// Horizontal field of view in degrees.
+    ///     ///
+    ///     /// Values above 120 may cause distortion.
+    ///     fov: Fov,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Volume(u32);
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Fov(u32);
+    ///
+    /// let options = FormatOptions {
+    ///     field_comments: true,
+    ///     ..default()
+    /// };
+    /// let saved = serialize_with_options(&ExamplePrefs::default(), &options).unwrap();
+    /// assert_eq!(
+    ///     saved,
+    ///     "(
+    ///     // Master volume, from 0 to 100.
+    ///     volume: (0),
+    ///     // Horizontal field of view in degrees.
+    ///     //
+    ///     // Values above 120 may cause distortion.
+    ///     fov: (0),
+    /// )"
+    /// );
+    /// ```
+    pub field_comments: bool,
+    /// A type registry to use instead of one containing only the preferences and the types they
+    /// depend on, such as the app's [`AppTypeRegistry`](bevy::ecs::reflect::AppTypeRegistry).
+    ///
+    /// This allows reusing registrations made elsewhere in the app, such as type data added with
+    /// `register_type_data`. The preferences are registered in it if they aren't already.
+    ///
+    /// ```rust
+    /// use bevy::{ecs::reflect::AppTypeRegistry, prelude::*};
+    /// use bevy_simple_prefs::{
+    ///     deserialize_with_options, serialize_with_options, FormatOptions, Prefs, PrefsPlugin,
+    /// };
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// struct ExamplePrefs {
+    ///     volume: Volume,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Volume(u32);
+    ///
+    /// let mut app = App::new();
+    /// app.register_type::<Volume>();
+    ///
+    /// let format = FormatOptions {
+    ///     type_registry: Some(app.world().resource::<AppTypeRegistry>().0.clone()),
+    ///     ..default()
+    /// };
+    ///
+    /// let prefs = ExamplePrefs { volume: Volume(20) };
+    /// let serialized = serialize_with_options(&prefs, &format).unwrap();
+    /// let prefs: ExamplePrefs = deserialize_with_options(&serialized, &format).unwrap();
+    /// assert_eq!(prefs.volume.0, 20);
+    ///
+    /// app.add_plugins(PrefsPlugin::<ExamplePrefs> {
+    ///     format,
+    ///     ..default()
+    /// });
+    /// ```
+    pub type_registry: Option<TypeRegistryArc>,
+    /// If set, a metadata header is written at the top of the file, recording this version of
+    /// the app and the time of the save.
+    ///
+    /// This is typically `env!("CARGO_PKG_VERSION")`. The header is made of comments, so files
+    /// with and without it can always be read back, and it doesn't prevent saves that wouldn't
+    /// change the preferences from being skipped. The metadata of loaded preferences is available
+    /// in [`PrefsStatus::metadata`].
+    ///
+    /// ```rust
+    /// use bevy::prelude::*;
+    /// use bevy_simple_prefs::{read_metadata, serialize_with_options, FormatOptions, Prefs};
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// #[prefs(version = 2)]
+    /// struct ExamplePrefs {
+    ///     volume: Volume,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Volume(u32);
+    ///
+    /// let options = FormatOptions {
+    ///     build_version: Some("1.4.0"),
+    ///     ..default()
+    /// };
+    /// let saved = serialize_with_options(&ExamplePrefs::default(), &options).unwrap();
+    ///
+    /// let metadata = read_metadata(&saved);
+    /// assert_eq!(metadata.version, 2);
+    /// assert_eq!(metadata.build_version.as_deref(), Some("1.4.0"));
+    /// assert!(metadata.saved_at.is_some());
+    /// ```
+    pub build_version: Option<&'static str>,
+    /// If `true`, a metadata header is written at the top of the file, recording the time of the
+    /// save, which is also recorded whenever `build_version` is set.
+    ///
+    /// This is useful for showing when save slots were last played without adding a field for
+    /// it. The time is available as [`PrefsMetadata::saved_at`] in [`PrefsStatus::metadata`], or
+    /// without loading the preferences with [`Prefs::peek_metadata`].
+    pub timestamp: bool,
+    /// Optional limits on the size of preferences files, which are checked before they are
+    /// deserialized.
+    ///
+    /// Files that exceed them fail to load with [`PrefsError::LimitExceeded`]. This protects
+    /// against crafted files, such as those shared between players, that would otherwise use an
+    /// excessive amount of memory or time to deserialize.
+    ///
+    /// ```rust
+    /// use bevy::prelude::*;
+    /// use bevy_simple_prefs::{
+    ///     deserialize_with_options, DeserializeLimits, FormatOptions, Prefs, PrefsError,
+    /// };
+    ///
+    /// #[derive(Prefs, Reflect, Default)]
+    /// struct ExamplePrefs {
+    ///     bindings: Bindings,
+    /// }
+    ///
+    /// #[derive(Resource, Reflect, Clone, Default)]
+    /// struct Bindings(Vec<Vec<String>>);
+    ///
+    /// let options = FormatOptions {
+    ///     limits: Some(DeserializeLimits {
+    ///         max_depth: 3,
+    ///         max_fields: 8,
+    ///     }),
+    ///     ..default()
+    /// };
+    /// let load = |serialized| deserialize_with_options::<ExamplePrefs>(serialized, &options);
+    ///
+    /// assert!(load(r#"(bindings: ([["W"], ["Up"]]))"#).is_ok());
+    /// assert!(matches!(
+    ///     load(r#"(bindings: ([[["W"]]]))"#),
+    ///     Err(PrefsError::LimitExceeded(_))
+    /// ));
+    /// assert!(matches!(
+    ///     load(r#"(bindings: ([["1", "2", "3", "4", "5", "6", "7", "8"]]))"#),
+    ///     Err(PrefsError::LimitExceeded(_))
+    /// ));
+    /// ```
+    pub limits: Option<DeserializeLimits>,
+    /// Key used to encrypt and decrypt fields marked with `#[prefs(secret)]`.
+    ///
+    /// Saving or loading preferences with secret fields fails if this is not set.
+    #[cfg(feature = "secret")]
+    pub secret_key: Option<SecretKey>,
+}
+
+/// Limits on the size of preferences files, set with [`FormatOptions::limits`].
+#[derive(Clone, Debug)]
+pub struct DeserializeLimits {
+    /// The maximum nesting depth of values, where the values of the fields of the preferences are
+    /// at depth `1`.
+    pub max_depth: usize,
+    /// The maximum total number of struct fields, map entries and sequence elements, at any
+    /// depth.
+    pub max_fields: usize,
+}
+
+impl Default for DeserializeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 32,
+            max_fields: 10_000,
+        }
+    }
+}
+
+/// A 256-bit key used to encrypt fields marked with `#[prefs(secret)]`.
+///
+/// Secret fields are encrypted with XChaCha20-Poly1305 and stored inline as base64 strings,
+/// leaving the rest of the preferences file readable.
+#[cfg(feature = "secret")]
+#[derive(Clone)]
+pub struct SecretKey(pub [u8; 32]);
+
+#[cfg(feature = "secret")]
+impl std::fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretKey(..)")
+    }
+}
+
+/// Settings for retrying failed saves, which may be caused by another process, such as a virus
+/// scanner or a cloud sync client, briefly holding the preferences file.
+///
+/// A failed automatic save is retried once the delay has passed, by saving the current
+/// preferences again from the main world, so neither the main thread nor the IO task pool waits
+/// in the meantime. A [`PrefsSaveError`] is only recorded, and a warning logged, after the last
+/// attempt fails. Saves made directly, such as with [`Prefs::save_current`], aren't retried.
+///
+/// This is not supported in WASM builds.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+pub struct SaveRetry {
+    /// The number of times to retry a failed write.
+    pub retries: u32,
+    /// The delay before the first retry, which doubles with each subsequent retry.
+    pub delay: Duration,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for SaveRetry {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            delay: Duration::from_millis(50),
+        }
+    }
+}
+
+/// Settings for the optional log of preference changes.
+///
+/// Each time preferences are saved, a line is appended to the log for every field that changed.
+/// Lines are JSON objects of the form `{"time":1700000000,"field":"volume","old":"(50)","new":"(60)"}`,
+/// where `time` is seconds since the Unix epoch and `old` and `new` are compact RON. `old` is
+/// `null` if the previous value is unknown. Changes to secret fields are logged, but their values
+/// are written as `"<secret>"`.
+///
+/// This is not supported in WASM builds.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+pub struct PrefsHistory {
+    /// Filename for the log, which is stored in the same directory as the preferences file.
+    ///
+    /// If this is `None`, the log is named after the preferences file, such as
+    /// `prefs.ron.history`, so that each `PrefsPlugin` and save slot has its own log.
+    pub filename: Option<String>,
+    /// When the log grows beyond this size, it is moved to `{filename}.old` and a new log is started.
+    pub max_bytes: u64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for PrefsHistory {
+    fn default() -> Self {
+        Self {
+            filename: None,
+            max_bytes: 1024 * 1024,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl PrefsHistory {
+    /// Returns the filename of the log for the preferences file `prefs_filename`.
+    pub fn resolve_filename(&self, prefs_filename: &str) -> String {
+        self.filename
+            .clone()
+            .unwrap_or_else(|| format!("{}.history", prefs_filename))
+    }
+}
+
+/// Settings for the optional journal of preference changes.
+///
+/// When only some preferences have changed, they are appended to the journal as a single line
+/// of compact RON, such as `(volume:(60))`, rather than rewriting the whole preferences file.
+/// When the journal grows beyond `max_bytes`, or when all preferences are saved at once, such as
+/// with [`Prefs::save_current`], a full snapshot is written to the preferences file and the
+/// journal is started over.
+///
+/// When loading, the journal is replayed on top of the snapshot. An entry that was cut short by a
+/// crash while appending is ignored, along with any entries after it. Changes to secret fields
+/// are never journaled, and entries aren't passed through `pre_write` and `post_read`.
+///
+/// This is not supported in WASM builds.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+pub struct PrefsJournal {
+    /// Filename for the journal, which is stored in the same directory as the preferences file.
+    ///
+    /// If this is `None`, the journal is named after the preferences file, such as
+    /// `prefs.ron.journal`, so that each `PrefsPlugin` and save slot has its own journal.
+    pub filename: Option<String>,
+    /// When the journal grows beyond this size, a full snapshot is written on the next save.
+    pub max_bytes: u64,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for PrefsJournal {
+    fn default() -> Self {
+        Self {
+            filename: None,
+            max_bytes: 64 * 1024,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl PrefsJournal {
+    /// Returns the filename of the journal for the preferences file `prefs_filename`.
+    pub fn resolve_filename(&self, prefs_filename: &str) -> String {
+        self.filename
+            .clone()
+            .unwrap_or_else(|| format!("{}.journal", prefs_filename))
+    }
+}
+
+/// A single change recorded in the [`PrefsHistory`] log.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+pub struct PrefsHistoryEntry {
+    /// Key of the field that changed, as written in the preferences file. This is the name of the
+    /// field, unless it has been renamed with [`Prefs::FIELD_KEYS`].
+    pub field: &'static str,
+    /// Previous value, serialized as compact RON.
+    pub old: Option<String>,
+    /// New value, serialized as compact RON.
+    pub new: String,
+}
+
+/// The most recently loaded or saved value of each field, used to fill in [`PrefsHistoryEntry::old`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource)]
+pub struct PrefsHistoryState<T> {
+    /// Field values serialized as compact RON, keyed by field name.
+    pub values: HashMap<&'static str, String>,
+    _phantom: PhantomData<T>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> Default for PrefsHistoryState<T> {
+    fn default() -> Self {
+        Self {
+            values: HashMap::new(),
+            _phantom: Default::default(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T: Prefs> PrefsHistoryState<T> {
+    /// Records new field values, keyed by field name, returning entries containing both the old
+    /// and new values.
+    pub fn record(&mut self, changes: Vec<(&'static str, String)>) -> Vec<PrefsHistoryEntry> {
+        let keys = FieldKeys::of::<T>();
+        changes
+            .into_iter()
+            .map(|(field, new)| PrefsHistoryEntry {
+                field: keys.key(field),
+                old: self.values.insert(field, new.clone()),
+                new,
+            })
+            .collect()
+    }
+}
+
+/// Progress of the most recent load, which determines whether changes can be saved.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PrefsLoadState {
+    /// No load has been started.
+    #[default]
+    NotStarted,
+    /// A load has been started, but its values haven't been applied yet.
+    InFlight,
+    /// The most recent load has been applied, or was superseded by explicitly written values.
+    Applied,
+}
+
+/// Current status of the `PrefsPlugin`.
+#[derive(Resource)]
+pub struct PrefsStatus<T> {
+    /// `true` if the preferences have been
+    pub loaded: bool,
+    /// Progress of the most recent load.
+    ///
+    /// Changes are only saved once this is [`PrefsLoadState::Applied`], so that a save can never
+    /// write values that a load in flight is about to replace, including when reloading.
+    pub load_state: PrefsLoadState,
+    /// A coarse estimate of load progress, from `0.0` to `1.0`.
+    ///
+    /// In WASM builds, this is `0.5` after the preferences have been read from LocalStorage, which
+    /// can only happen synchronously when the load starts. They are decompressed and deserialized
+    /// by the load task in a later frame, which still runs on the main thread, because WASM builds
+    /// have no other threads. Native builds go directly from `0.0` to `1.0`.
+    pub load_progress: f32,
+    /// The number of times a save has been initiated since the app started.
+    ///
+    /// This is incremented without triggering change detection. Multiple changes within a single
+    /// frame result in a single save.
+    pub save_count: u64,
+    /// The number of times loaded preferences have been applied since the app started, including
+    /// reloaded, imported and restored preferences.
+    ///
+    /// Loaded values don't trigger change detection, so this can be used to notice them instead.
+    /// See [`prefs_just_loaded`].
+    pub load_count: u64,
+    /// If `true`, the preferences are saved in the next [`PrefsSet::Save`], even if they haven't
+    /// changed.
+    ///
+    /// This is reset when the save is initiated. Because changes to this resource delay saving by
+    /// a frame, it can be set with `bypass_change_detection` to save sooner.
+    pub save_requested: bool,
+    /// The [`content_hash`] of the stored preferences, as they were last loaded or saved.
+    ///
+    /// Saves that wouldn't change the stored preferences are skipped. This is `None` if no
+    /// preferences have been stored, or if they couldn't be read.
+    pub content_hash: Option<u64>,
+    /// The size in bytes of the most recently saved preferences, before `pre_write` and any
+    /// compression, or `None` if they haven't been saved since the plugin was added.
+    ///
+    /// This is recorded even if the save was skipped because the stored preferences were already
+    /// identical. It can be used to notice preferences that keep growing, such as before they
+    /// exceed the LocalStorage quota in WASM builds.
+    pub last_save_bytes: Option<usize>,
+    /// If `true`, changes aren't saved until this is set back to `false`, at which point a single
+    /// save happens if anything changed in the meantime.
+    ///
+    /// This can be set with [`PrefsCommandsExt::pause_prefs_autosave`] and
+    /// [`PrefsCommandsExt::resume_prefs_autosave`]. Explicit saves, such as
+    /// [`Prefs::save_current`], still happen while autosave is paused.
+    pub autosave_paused: bool,
+    /// The error from the most recent failed load or save, for apps that don't need to tell them
+    /// apart.
+    ///
+    /// This is cleared by the next successful operation of the same kind as the one that failed.
+    /// See [`PrefsLoadError`] and [`PrefsSaveError`] for the errors of each kind.
+    pub last_error: Option<PrefsError>,
+    /// The metadata from the header of the most recently loaded preferences file, or `None` if no
+    /// preferences were stored.
+    ///
+    /// See [`FormatOptions::build_version`].
+    pub metadata: Option<PrefsMetadata>,
+    /// The [`Prefs::VERSION`] that the most recently loaded preferences file was written with, or
+    /// `None` if it didn't record one, such as when no preferences were stored or the file
+    /// predates versioning.
+    ///
+    /// This can be compared with [`Prefs::VERSION`] to warn that the preferences were written by a
+    /// newer version of the app.
+    pub loaded_version: Option<u32>,
+    last_error_from_save: bool,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> PrefsStatus<T> {
+    /// Marks a load as started, which holds back saves until it has been applied.
+    ///
+    /// This is used by the derive macro, and is not part of the public API.
+    #[doc(hidden)]
+    pub fn __begin_load(&mut self) {
+        self.load_state = PrefsLoadState::InFlight;
+    }
+
+    /// Marks the preferences as loaded, allowing changes to be saved.
+    ///
+    /// This is used by the derive macro, and is not part of the public API.
+    #[doc(hidden)]
+    pub fn __finish_load(&mut self) {
+        self.loaded = true;
+        self.load_progress = 1.0;
+        self.load_state = PrefsLoadState::Applied;
+    }
+
+    /// Records the outcome of a load in [`Self::last_error`].
+    ///
+    /// This is used by the derive macro, and is not part of the public API.
+    #[doc(hidden)]
+    pub fn __record_load(&mut self, error: Option<PrefsError>) {
+        self.record(error, false);
+    }
+
+    /// Records the outcome of a save in [`Self::last_error`].
+    ///
+    /// This is used by the derive macro, and is not part of the public API.
+    #[doc(hidden)]
+    pub fn __record_save(&mut self, error: Option<PrefsError>) {
+        self.record(error, true);
+    }
+
+    fn record(&mut self, error: Option<PrefsError>, from_save: bool) {
+        match error {
+            Some(error) => {
+                self.last_error = Some(error);
+                self.last_error_from_save = from_save;
+            }
+            None if self.last_error_from_save == from_save => self.last_error = None,
+            None => {}
+        }
+    }
+}
+
+impl<T> Default for PrefsStatus<T> {
+    fn default() -> Self {
+        Self {
+            loaded: false,
+            load_state: PrefsLoadState::NotStarted,
+            load_progress: 0.0,
+            save_count: 0,
+            load_count: 0,
+            save_requested: false,
+            content_hash: None,
+            last_save_bytes: None,
+            autosave_paused: false,
+            last_error: None,
+            metadata: None,
+            loaded_version: None,
+            last_error_from_save: false,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+/// The number of consecutive frames each field of `T` has changed in, used for
+/// [`PrefsPlugin::warn_constant_changes`].
+#[derive(Resource)]
+pub struct PrefsChangeStreaks<T> {
+    /// Consecutive frames with changes, keyed by field name.
+    pub streaks: HashMap<&'static str, u32>,
+    warned: Vec<&'static str>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> Default for PrefsChangeStreaks<T> {
+    fn default() -> Self {
+        Self {
+            streaks: HashMap::new(),
+            warned: Vec::new(),
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<T: TypePath> PrefsChangeStreaks<T> {
+    /// Records the fields that changed this frame, warning once about each field that has changed
+    /// in `frames` consecutive frames.
+    pub fn record(&mut self, changed: &[&'static str], frames: u32) {
+        self.streaks.retain(|field, _| changed.contains(field));
+        for field in changed {
+            let streak = self.streaks.entry(field).or_default();
+            *streak += 1;
+            if *streak >= frames && !self.warned.contains(field) {
+                self.warned.push(field);
+                warn!(
+                    "{}::{} changed in {} consecutive frames, which may cause excessive saving. \
+                    Use `set_if_neq` to avoid triggering change detection when the value is the same.",
+                    T::type_path(),
+                    field,
+                    frames
+                );
+            }
+        }
+    }
+}
+
+/// The number of frames left before `T` is saved, for [`PrefsPlugin::warm_up_frames`].
+#[derive(Resource)]
+struct PrefsWarmUp<T> {
+    frames_left: u32,
+    _phantom: PhantomData<T>,
+}
+
+/// Returns `true` while saving `T` is suppressed by [`PrefsPlugin::warm_up_frames`], counting
+/// down one frame each time it's called.
+///
+/// This is used by the derive macro, and is not part of the public API.
+#[doc(hidden)]
+pub fn __warming_up<T: Send + Sync + 'static>(world: &mut World) -> bool {
+    let Some(mut warm_up) = world.get_resource_mut::<PrefsWarmUp<T>>() else {
+        return false;
+    };
+    warm_up.frames_left -= 1;
+    if warm_up.frames_left == 0 {
+        world.remove_resource::<PrefsWarmUp<T>>();
+    }
+    true
+}
+
+/// A failed save of `T` waiting to be retried, for [`PrefsPlugin::save_retry`].
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource)]
+struct PrefsSaveRetry<T> {
+    attempts: u32,
+    due: std::time::Instant,
+    _phantom: PhantomData<T>,
+}
+
+/// Returns `true` while a failed save of `T` is waiting to be retried, for
+/// [`PrefsPlugin::save_retry`].
+///
+/// This is used by the derive macro, and is not part of the public API.
+#[doc(hidden)]
+pub fn __retry_pending<T: Send + Sync + 'static>(world: &World) -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(retry) = world.get_resource::<PrefsSaveRetry<T>>() {
+        return std::time::Instant::now() < retry.due;
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    let _ = world;
+    false
+}
+
+/// Records the outcome of a save of `T`, returning `true` if it failed and another attempt has
+/// been scheduled according to [`PrefsPlugin::save_retry`].
+///
+/// This is used by the derive macro, and is not part of the public API.
+#[doc(hidden)]
+pub fn __retry_save<T: Send + Sync + 'static>(
+    world: &mut World,
+    error: Option<&PrefsError>,
+) -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let attempts = world
+            .remove_resource::<PrefsSaveRetry<T>>()
+            .map_or(0, |retry| retry.attempts);
+        if !matches!(error, Some(PrefsError::Io(_) | PrefsError::ReadOnly(_))) {
+            return false;
+        }
+
+        let retry = &world.resource::<PrefsSettings<T>>().save_retry;
+        if attempts >= retry.retries {
+            return false;
+        }
+
+        let delay = retry.delay.saturating_mul(2u32.saturating_pow(attempts));
+        bevy::log::debug!("Retrying save in {:?}", delay);
+        world.insert_resource(PrefsSaveRetry::<T> {
+            attempts: attempts + 1,
+            due: std::time::Instant::now() + delay,
+            _phantom: PhantomData,
+        });
+        world
+            .resource_mut::<PrefsStatus<T>>()
+            .bypass_change_detection()
+            .save_requested = true;
+        true
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        let _ = (world, error);
+        false
+    }
+}
+
+/// Top-level fields of a preferences file that are written back as they were read, rather than
+/// from the preferences.
+///
+/// Unrecognized fields of a file written by a newer version of the preferences are preserved so
+/// that round-tripping a preferences file through an older build doesn't lose settings that it
+/// doesn't understand. Unrecognized fields of files written by the same or an older version, such
+/// as preferences that have since been removed, are dropped as usual.
+///
+/// The stored values of transient fields are preserved too, so that they stay in the file even
+/// though they are never written from the preferences, as are the stored values of fields that
+/// were overridden when they were loaded.
+#[derive(Clone, Debug, Default)]
+pub struct PreservedFields {
+    /// The version of the preferences file that the fields were read from.
+    pub version: u32,
+    /// Field names and their values, as raw RON.
+    pub fields: Vec<(String, String)>,
+    /// Keys of the transient fields and their stored values, as raw RON.
+    pub transient: Vec<(String, String)>,
+    /// Fields that were overridden by `fragment_dir` or `env_prefix` when they were loaded.
+    pub overridden: Vec<OverriddenField>,
+}
+
+/// A field of the preferences that was overridden by `fragment_dir` or `env_prefix` when it was
+/// loaded.
+///
+/// While the field still has the value that it was overridden with, its stored value is saved
+/// instead, so that overrides aren't persisted.
+#[derive(Debug)]
+pub struct OverriddenField {
+    /// The key of the field.
+    pub key: String,
+    /// The value that the field was overridden with.
+    pub value: Box<dyn PartialReflect>,
+    /// The value of the field before it was overridden, as raw RON, or `None` if it was
+    /// identical to the base preferences and shouldn't be stored.
+    pub stored: Option<String>,
+}
+
+impl Clone for OverriddenField {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            value: self.value.clone_value(),
+            stored: self.stored.clone(),
+        }
+    }
+}
+
+/// The [`PreservedFields`] of the most recently loaded preferences file of `T`.
+#[derive(Resource)]
+pub struct PrefsPreservedFields<T> {
+    /// The preserved fields.
+    pub preserved: PreservedFields,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> PrefsPreservedFields<T> {
+    /// Creates a new `PrefsPreservedFields`.
+    pub fn new(preserved: PreservedFields) -> Self {
+        Self {
+            preserved,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<T> Default for PrefsPreservedFields<T> {
+    fn default() -> Self {
+        Self::new(PreservedFields::default())
+    }
+}
+
+/// Senders for the futures returned by [`wait_for_prefs`] that are waiting for `T` to load.
+#[derive(Resource)]
+struct PrefsLoadWaiters<T> {
+    senders: Vec<async_channel::Sender<()>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> Default for PrefsLoadWaiters<T> {
+    fn default() -> Self {
+        Self {
+            senders: Vec::new(),
+            _phantom: Default::default(),
+        }
+    }
+}
+
+/// Returns a future that resolves once `T` has finished loading.
+///
+/// If `T` has already been loaded, the future resolves immediately. Otherwise, it resolves during
+/// the first [`PrefsSet::Load`] after loading finishes. The future's output is `false` if the app
+/// was dropped before then.
+///
+/// This allows awaiting preferences from an async context, such as a task on one of Bevy's task
+/// pools, rather than polling [`PrefsStatus`] every frame.
+pub fn wait_for_prefs<T: Send + Sync + 'static>(
+    world: &mut World,
+) -> impl Future<Output = bool> + Send + 'static {
+    let (sender, receiver) = async_channel::bounded(1);
+
+    if world.resource::<PrefsStatus<T>>().loaded {
+        let _ = sender.try_send(());
+    } else {
+        world
+            .resource_mut::<PrefsLoadWaiters<T>>()
+            .senders
+            .push(sender);
+    }
+
+    async move { receiver.recv().await.is_ok() }
+}
+
+fn notify_load_waiters<T: Send + Sync + 'static>(
+    status: Res<PrefsStatus<T>>,
+    mut waiters: ResMut<PrefsLoadWaiters<T>>,
+) {
+    if !status.loaded || waiters.senders.is_empty() {
+        return;
+    }
+
+    for sender in waiters.senders.drain(..) {
+        let _ = sender.try_send(());
+    }
+}
+
+/// An error that occurred while loading or saving preferences.
+#[derive(Debug, Clone)]
+pub enum PrefsError {
+    /// Reading or writing the underlying storage failed.
+    ///
+    /// Failures of storage that doesn't report a [`std::io::Error`], such as LocalStorage or a
+    /// [`StorageBackend`], are wrapped in one of kind [`std::io::ErrorKind::Other`].
+    Io(Arc<std::io::Error>),
+    /// The storage couldn't be written to, because permission was denied or it is read-only.
+    ReadOnly(Arc<std::io::Error>),
+    /// The preferences couldn't be serialized.
+    Serialize(ron::Error),
+    /// The stored preferences couldn't be deserialized, in whichever format they were stored.
+    Deserialize(String),
+    /// No storage is available, such as when LocalStorage is disabled in the browser.
+    StorageUnavailable,
+    /// The storage is full, or the preferences are larger than it allows.
+    QuotaExceeded,
+    /// The stored preferences were written with a different [`schema_hash`].
+    ///
+    /// See [`FormatOptions::schema_hash`].
+    SchemaMismatch {
+        /// The schema hash of the current preferences.
+        expected: u64,
+        /// The schema hash recorded in the stored preferences.
+        found: u64,
+    },
+    /// The stored preferences exceeded one of the [`DeserializeLimits`].
+    ///
+    /// See [`FormatOptions::limits`].
+    LimitExceeded(String),
+    /// A type that was expected to be the type of exactly one of the fields of the preferences
+    /// wasn't.
+    ///
+    /// See [`Prefs::load_field`].
+    UnknownField(String),
+}
+
+impl std::fmt::Display for PrefsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "storage error: {}", e),
+            Self::ReadOnly(e) => write!(f, "storage is read-only: {}", e),
+            Self::Serialize(e) => write!(f, "serialization error: {}", e),
+            Self::Deserialize(e) => write!(f, "deserialization error: {}", e),
+            Self::StorageUnavailable => write!(f, "storage is unavailable"),
+            Self::QuotaExceeded => write!(f, "storage quota exceeded"),
+            Self::SchemaMismatch { expected, found } => write!(
+                f,
+                "schema mismatch: expected {:016x}, found {:016x}",
+                expected, found
+            ),
+            Self::LimitExceeded(e) => write!(f, "limit exceeded: {}", e),
+            Self::UnknownField(e) => write!(f, "unknown field: {}", e),
+        }
+    }
+}
+
+/// IO errors are equal if they have the same kind and message.
+impl PartialEq for PrefsError {
+    fn eq(&self, other: &Self) -> bool {
+        let io_eq = |a: &std::io::Error, b: &std::io::Error| {
+            a.kind() == b.kind() && a.to_string() == b.to_string()
+        };
+
+        match (self, other) {
+            (Self::Io(a), Self::Io(b)) | (Self::ReadOnly(a), Self::ReadOnly(b)) => io_eq(a, b),
+            (Self::Serialize(a), Self::Serialize(b)) => a == b,
+            (Self::Deserialize(a), Self::Deserialize(b))
+            | (Self::LimitExceeded(a), Self::LimitExceeded(b))
+            | (Self::UnknownField(a), Self::UnknownField(b)) => a == b,
+            (Self::StorageUnavailable, Self::StorageUnavailable)
+            | (Self::QuotaExceeded, Self::QuotaExceeded) => true,
+            (
+                Self::SchemaMismatch { expected, found },
+                Self::SchemaMismatch {
+                    expected: other_expected,
+                    found: other_found,
+                },
+            ) => expected == other_expected && found == other_found,
+            _ => false,
+        }
+    }
+}
+
+impl std::error::Error for PrefsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) | Self::ReadOnly(e) => Some(&**e),
+            Self::Serialize(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for PrefsError {
+    fn from(e: std::io::Error) -> Self {
+        // `ErrorKind::ReadOnlyFilesystem` needs Rust 1.83, so the OS error code is checked
+        // instead. `EROFS` is 30 on Linux, macOS and the BSDs.
+        let read_only = e.kind() == std::io::ErrorKind::PermissionDenied
+            || (cfg!(unix) && e.raw_os_error() == Some(30));
+
+        match read_only {
+            true => Self::ReadOnly(Arc::new(e)),
+            false => Self::Io(Arc::new(e)),
+        }
+    }
+}
+
+impl PrefsError {
+    /// Returns a [`PrefsError::Io`] for a failure of storage that doesn't report a
+    /// [`std::io::Error`].
+    pub(crate) fn other(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self::Io(Arc::new(std::io::Error::other(error)))
+    }
+}
+
+/// The error from the most recent failed save of `T`.
+///
+/// This resource is inserted when a save fails and removed after the next successful save.
+#[derive(Resource, Debug)]
+pub struct PrefsSaveError<T> {
+    /// The underlying serialization or storage error.
+    pub error: PrefsError,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> PrefsSaveError<T> {
+    /// Creates a new `PrefsSaveError`.
+    pub fn new(error: PrefsError) -> Self {
+        Self {
+            error,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<T: TypePath> std::fmt::Display for PrefsSaveError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to save {}: {}", T::type_path(), self.error)
+    }
+}
+
+/// The error from the most recent failed load of `T`.
+///
+/// This resource is inserted when the stored preferences can't be read or deserialized, in which
+/// case the individual preference `Resources` keep their default values. It is removed after the
+/// next successful load.
+#[derive(Resource, Debug)]
+pub struct PrefsLoadError<T> {
+    /// The underlying deserialization or storage error.
+    pub error: PrefsError,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> PrefsLoadError<T> {
+    /// Creates a new `PrefsLoadError`.
+    pub fn new(error: PrefsError) -> Self {
+        Self {
+            error,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<T: TypePath> std::fmt::Display for PrefsLoadError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed to load {}: {}", T::type_path(), self.error)
+    }
+}
+
+/// An event sent when a save of `T` completes successfully.
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_simple_prefs::{Prefs, PrefsPlugin, PrefsSaved};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     volume: Volume,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, Default)]
+/// struct Volume(u32);
+///
+/// fn update_indicator(mut events: EventReader<PrefsSaved<ExamplePrefs>>) {
+///     for event in events.read() {
+///         if event.changed {
+///             info!("Preferences saved");
+///         }
+///     }
+/// }
+/// ```
+#[derive(Event, Debug)]
+pub struct PrefsSaved<T> {
+    /// `true` if the stored preferences were written, or `false` if writing them was skipped
+    /// because they were already identical.
+    ///
+    /// The serialized preferences are compared with those last loaded or saved, so preferences
+    /// that are changed and then changed back count as unchanged, without the preference
+    /// resources having to implement `PartialEq`.
+    pub changed: bool,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> PrefsSaved<T> {
+    /// Creates a new `PrefsSaved`.
+    pub fn new(changed: bool) -> Self {
+        Self {
+            changed,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+/// Returns a hash of serialized preferences, as recorded in [`PrefsStatus::content_hash`].
+///
+/// The time of the save recorded in the metadata header is ignored. The hash is only stable
+/// within a single run of the app.
+pub fn content_hash(serialized: &str) -> u64 {
+    use std::hash::{BuildHasher, Hasher, RandomState};
+
+    // A fixed `RandomState` keeps hashes consistent across threads.
+    static STATE: std::sync::OnceLock<RandomState> = std::sync::OnceLock::new();
+    let mut hasher = STATE.get_or_init(RandomState::new).build_hasher();
+    for line in content_lines(serialized) {
+        hasher.write(line.as_bytes());
+    }
+    hasher.finish()
+}
+
+/// The lines of serialized preferences, other than the time of the save.
+fn content_lines(serialized: &str) -> impl Iterator<Item = &str> {
+    serialized
+        .split_inclusive('\n')
+        .filter(|line| !line.starts_with(SAVED_AT_HEADER))
+}
+
+/// A run condition that is `true` if `T` has been loaded.
+///
+/// Loaded preferences are applied in [`PrefsSet::Load`], so systems using this condition should
+/// be ordered after that set to observe the loaded values in the same frame.
+///
+/// ```rust
+/// # use bevy::prelude::*;
+/// # use bevy_simple_prefs::{prefs_loaded, Prefs, PrefsSet};
+/// # #[derive(Prefs, Reflect, Default)]
+/// # struct ExamplePrefs {}
+/// # fn apply_volume() {}
+/// App::new().add_systems(
+///     Update,
+///     apply_volume
+///         .run_if(prefs_loaded::<ExamplePrefs>())
+///         .after(PrefsSet::Load),
+/// );
+/// ```
+pub fn prefs_loaded<T: Send + Sync + 'static>(
+) -> impl FnMut(Option<Res<PrefsStatus<T>>>) -> bool + Clone {
+    |status: Option<Res<PrefsStatus<T>>>| status.is_some_and(|status| status.loaded)
+}
+
+/// A run condition that is `true` the first time it is evaluated after `T` has been loaded.
+///
+/// If the preferences are loaded again, for example after
+/// [`switch_prefs_path`](PrefsCommandsExt::switch_prefs_path), or applied by [`import_prefs`],
+/// [`Prefs::load_field`] or restoring a [`PrefsSnapshot`], it is `true` once more. As with
+/// [`prefs_loaded`], systems using this condition should be ordered after [`PrefsSet::Load`].
+///
+/// Loaded values don't trigger change detection, so systems that react to a preference
+/// `Resource` changing should also run when this is `true`.
+///
+/// ```rust
+/// # use bevy::prelude::*;
+/// # use bevy_simple_prefs::{prefs_just_loaded, Prefs, PrefsSet};
+/// # #[derive(Prefs, Reflect, Default)]
+/// # struct ExamplePrefs {}
+/// # fn apply_volume() {}
+/// App::new().add_systems(
+///     Update,
+///     apply_volume
+///         .run_if(prefs_just_loaded::<ExamplePrefs>())
+///         .after(PrefsSet::Load),
+/// );
+/// ```
+pub fn prefs_just_loaded<T: Send + Sync + 'static>(
+) -> impl FnMut(Option<Res<PrefsStatus<T>>>, Local<u64>) -> bool + Clone {
+    |status: Option<Res<PrefsStatus<T>>>, mut last_load_count: Local<u64>| {
+        let load_count = status.map_or(0, |status| status.load_count);
+        let just_loaded = load_count > 0 && load_count != *last_load_count;
+        *last_load_count = load_count;
+        just_loaded
+    }
+}
+
+/// System sets used by `PrefsPlugin` in the `Update` schedule.
+///
+/// Systems that must observe freshly loaded preferences in the same frame that they are applied
+/// should be ordered after [`PrefsSet::Load`]. Commands from the load task are applied before any
+/// system ordered after that set runs.
+///
+/// ```rust
+/// # use bevy::prelude::*;
+/// # use bevy_simple_prefs::PrefsSet;
+/// # fn apply_volume() {}
+/// App::new().add_systems(Update, apply_volume.after(PrefsSet::Load));
+/// ```
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PrefsSet {
+    /// Applies preferences that have finished loading.
+    Load,
+    /// Saves preferences that have changed.
+    Save,
+}
+
+/// A copy of an individual preference `Resource` being edited, inserted by [`Prefs::begin_edit`].
+///
+/// Changes to it don't affect the live value of `R` until [`Prefs::commit_edit`].
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Staged<R>(pub R);
+
+/// The persisted value of an individual preference `Resource` being previewed, inserted by
+/// [`PrefsCommandsExt::preview_pref`].
+///
+/// While it exists, this value is saved in place of the live value of `R`, and changes to `R`
+/// don't trigger saves. Loading or resetting the preferences ends the preview.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct Persisted<R>(pub R);
+
+/// Type-erased operations for every preferences type added to the app with a [`PrefsPlugin`].
+///
+/// This allows operating on all preferences at once, without knowing their types.
+#[derive(Resource, Default)]
+pub struct PrefsRegistry {
+    registrations: Vec<PrefsRegistration>,
+}
+
+impl PrefsRegistry {
+    /// Returns the registration for each preferences type, in the order their plugins were added.
+    pub fn iter(&self) -> impl Iterator<Item = &PrefsRegistration> {
+        self.registrations.iter()
+    }
+
+    /// Returns the registration for `T`, if it has been added with a [`PrefsPlugin`].
+    pub fn get<T: 'static>(&self) -> Option<&PrefsRegistration> {
+        self.get_by_id(TypeId::of::<T>())
+    }
+
+    /// Returns the registration for the preferences type with the given `TypeId`, if it has been
+    /// added with a [`PrefsPlugin`].
+    pub fn get_by_id(&self, type_id: TypeId) -> Option<&PrefsRegistration> {
+        self.registrations.iter().find(|r| r.type_id == type_id)
+    }
+
+    fn unregister<T: 'static>(&mut self) {
+        self.registrations
+            .retain(|r| r.type_id != TypeId::of::<T>());
+    }
+
+    fn register<T: Prefs + Reflect + TypePath + GetTypeRegistration + Default>(&mut self) {
+        if self.get::<T>().is_some() {
+            return;
+        }
+
+        self.registrations.push(PrefsRegistration {
+            type_id: TypeId::of::<T>(),
+            type_path: T::type_path(),
+            save: T::save,
+            load: T::load,
+            reset: T::reset,
+            save_current: T::save_current,
+            clear: clear_prefs::<T>,
+            export: export_prefs::<T>,
+            import: import_prefs::<T>,
+            prepare_import: prepare_import::<T>,
+        });
+    }
+}
+
+/// Type-erased operations for a single preferences type, stored in the [`PrefsRegistry`].
+#[derive(Clone, Debug)]
+pub struct PrefsRegistration {
+    /// The `TypeId` of the preferences type.
+    pub type_id: TypeId,
+    /// The type path of the preferences type.
+    pub type_path: &'static str,
+    /// Persists the preferences if they have changed.
+    ///
+    /// See [`Prefs::save`].
+    pub save: fn(&mut World),
+    /// Loads the preferences and updates the individual preference `Resource`s.
+    ///
+    /// See [`Prefs::load`].
+    pub load: fn(&mut World),
+    /// Resets the individual preference `Resource`s to their default values without saving them.
+    ///
+    /// See [`Prefs::reset`].
+    pub reset: fn(&mut World),
+    /// Immediately writes the current values of the individual preference `Resource`s.
+    ///
+    /// See [`Prefs::save_current`].
+    pub save_current: fn(&mut World) -> Result<(), PrefsError>,
+    /// Deletes the stored preferences and resets them to their default values.
+    ///
+    /// See [`clear_prefs`].
+    pub clear: fn(&mut World),
+    /// Serializes the current preferences.
+    ///
+    /// See [`export_prefs`].
+    pub export: fn(&World) -> Result<String, PrefsError>,
+    /// Replaces the stored preferences with previously exported preferences and applies them.
+    ///
+    /// See [`import_prefs`].
+    pub import: fn(&mut World, &str) -> Result<(), PrefsError>,
+    /// Deserializes previously exported preferences, returning a [`PreparedImport`] that applies
+    /// them like [`import_prefs`].
+    ///
+    /// See [`prepare_import`].
+    pub prepare_import: fn(&World, &str) -> Result<PreparedImport, PrefsError>,
+}
+
+/// Deserialized preferences waiting to be applied, returned by [`prepare_import`].
+pub struct PreparedImport(Box<dyn FnOnce(&mut World) -> Result<(), PrefsError>>);
+
+impl PreparedImport {
+    /// Replaces the stored preferences with the deserialized preferences and applies them.
+    pub fn apply(self, world: &mut World) -> Result<(), PrefsError> {
+        (self.0)(world)
+    }
+}
+
+/// A component that holds the task responsible for updating individual preference `Resource`s after they have been loaded.
+#[derive(Component)]
+pub struct LoadPrefsTask<T> {
+    /// The load task, which produces commands that insert the loaded `Resource`s.
+    pub task: Task<CommandQueue>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> LoadPrefsTask<T> {
+    /// Creates a new `LoadPrefsTask`.
+    pub fn new(task: Task<CommandQueue>) -> Self {
+        Self {
+            task,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+/// A component that holds the task responsible for writing preferences after they have changed.
+#[derive(Component)]
+pub struct SavePrefsTask<T> {
+    /// The save task, which produces commands to run after the preferences have been written.
+    pub task: Task<CommandQueue>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> SavePrefsTask<T> {
+    /// Creates a new `SavePrefsTask`.
+    pub fn new(task: Task<CommandQueue>) -> Self {
+        Self {
+            task,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+/// Extension trait for `Commands` providing preference-related commands.
+pub trait PrefsCommandsExt {
+    /// Points `PrefsPlugin<T>` at a new directory and loads the preferences stored there.
+    ///
+    /// Any pending save to the old directory is completed first, and any in-progress load is
+    /// discarded. [`PrefsStatus::loaded`] is `false` until the new preferences have been loaded,
+    /// and no saves happen in the meantime.
+    ///
+    /// This is not available in WASM builds.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn switch_prefs_path<T: Prefs + Send + Sync + 'static>(&mut self, path: impl Into<PathBuf>);
+
+    /// Deletes the stored preferences of every type in the [`PrefsRegistry`] and resets them to
+    /// their default values.
+    ///
+    /// See [`clear_all_prefs`].
+    fn clear_all_prefs(&mut self);
+
+    /// Tears down `PrefsPlugin<T>`, discarding any in-progress load.
+    ///
+    /// See [`remove_prefs`].
+    fn remove_prefs<T: Send + Sync + 'static>(&mut self);
+
+    /// Stops saving changes to the preferences of `T` until
+    /// [`PrefsCommandsExt::resume_prefs_autosave`].
+    ///
+    /// See [`PrefsStatus::autosave_paused`].
+    fn pause_prefs_autosave<T: Send + Sync + 'static>(&mut self);
+
+    /// Resumes saving changes to the preferences of `T`, saving once if anything changed while
+    /// autosave was paused.
+    ///
+    /// See [`PrefsStatus::autosave_paused`].
+    fn resume_prefs_autosave<T: Send + Sync + 'static>(&mut self);
+
+    /// Copies the preferences of `T` into [`Staged`] resources to be edited.
+    ///
+    /// See [`Prefs::begin_edit`].
+    fn begin_prefs_edit<T: Prefs + 'static>(&mut self);
+
+    /// Applies the [`Staged`] preferences of `T`, which are then saved as usual.
+    ///
+    /// See [`Prefs::commit_edit`].
+    fn commit_prefs_edit<T: Prefs + 'static>(&mut self);
+
+    /// Discards the [`Staged`] preferences of `T`.
+    ///
+    /// See [`Prefs::cancel_edit`].
+    fn cancel_prefs_edit<T: Prefs + 'static>(&mut self);
+
+    /// Sets an individual preference `Resource` to `value` without saving it, for example while
+    /// the player hovers over an option.
+    ///
+    /// The persisted value is kept in a [`Persisted`] resource until the preview is ended with
+    /// [`revert_pref`](Self::revert_pref) or [`commit_pref`](Self::commit_pref). Previewing a
+    /// `Resource` that is already being previewed only replaces the previewed value.
+    fn preview_pref<R: Resource + Clone>(&mut self, value: R);
+
+    /// Ends the preview of `R`, restoring its [`Persisted`] value.
+    ///
+    /// Does nothing if `R` isn't being previewed.
+    fn revert_pref<R: Resource>(&mut self);
+
+    /// Ends the preview of `R`, keeping its previewed value, which is then saved as usual.
+    ///
+    /// Does nothing if `R` isn't being previewed.
+    fn commit_pref<R: Resource>(&mut self);
+}
+
+impl PrefsCommandsExt for Commands<'_, '_> {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn switch_prefs_path<T: Prefs + Send + Sync + 'static>(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        self.queue(move |world: &mut World| switch_prefs_path::<T>(world, path));
+    }
+
+    fn clear_all_prefs(&mut self) {
+        self.queue(clear_all_prefs);
+    }
+
+    fn remove_prefs<T: Send + Sync + 'static>(&mut self) {
+        self.queue(remove_prefs::<T>);
+    }
+
+    fn pause_prefs_autosave<T: Send + Sync + 'static>(&mut self) {
+        self.queue(|world: &mut World| set_autosave_paused::<T>(world, true));
+    }
+
+    fn resume_prefs_autosave<T: Send + Sync + 'static>(&mut self) {
+        self.queue(|world: &mut World| set_autosave_paused::<T>(world, false));
+    }
+
+    fn begin_prefs_edit<T: Prefs + 'static>(&mut self) {
+        self.queue(T::begin_edit);
+    }
+
+    fn commit_prefs_edit<T: Prefs + 'static>(&mut self) {
+        self.queue(T::commit_edit);
+    }
+
+    fn cancel_prefs_edit<T: Prefs + 'static>(&mut self) {
+        self.queue(T::cancel_edit);
+    }
+
+    fn preview_pref<R: Resource + Clone>(&mut self, value: R) {
+        self.queue(move |world: &mut World| {
+            if !world.contains_resource::<Persisted<R>>() {
+                if let Some(persisted) = world.get_resource::<R>().cloned() {
+                    world.insert_resource(Persisted(persisted));
+                }
+            }
+            world.insert_resource(value);
+        });
+    }
+
+    fn revert_pref<R: Resource>(&mut self) {
+        self.queue(|world: &mut World| {
+            if let Some(Persisted(value)) = world.remove_resource::<Persisted<R>>() {
+                world.insert_resource(value);
+            }
+        });
+    }
+
+    fn commit_pref<R: Resource>(&mut self) {
+        self.queue(|world: &mut World| {
+            if world.remove_resource::<Persisted<R>>().is_some() {
+                world.resource_mut::<R>().set_changed();
+            }
+        });
+    }
+}
+
+fn set_autosave_paused<T: Send + Sync + 'static>(world: &mut World, paused: bool) {
+    // Bypass change detection, which would otherwise delay the save after resuming.
+    if let Some(mut status) = world.get_resource_mut::<PrefsStatus<T>>() {
+        status.bypass_change_detection().autosave_paused = paused;
+    }
+}
+
+/// Deletes the stored preferences of every type in the [`PrefsRegistry`] and resets them to
+/// their default values.
+///
+/// See [`clear_prefs`].
+pub fn clear_all_prefs(world: &mut World) {
+    let Some(registry) = world.get_resource::<PrefsRegistry>() else {
+        return;
+    };
+
+    let clear_fns: Vec<fn(&mut World)> = registry.iter().map(|r| r.clear).collect();
+    for clear in clear_fns {
+        clear(world);
+    }
+}
+
+/// Deletes the stored preferences of `T` and resets them to their default values.
+///
+/// Any pending save is completed first, and any in-progress load is discarded. The defaults are
+/// not saved, so the preferences file is only written again once a preference changes. Files in
+/// [`PrefsPlugin::load_paths`] are left in place, as are the stored preferences of read-only
+/// plugins.
+///
+/// In WASM builds, a save that is already in progress may still complete after the
+/// preferences have been cleared.
+pub fn clear_prefs<T: Prefs + TypePath + Send + Sync + 'static>(world: &mut World) {
+    #[cfg(not(target_arch = "wasm32"))]
+    finish_save_tasks::<T>(world);
+    cancel_load_tasks::<T>(world);
+
+    let settings = world.resource::<PrefsSettings<T>>();
+    if !settings.read_only {
+        settings.delete();
+    }
+
+    T::reset(world);
+    world.insert_resource(PrefsPreservedFields::<T>::default());
+
+    let mut status = world.resource_mut::<PrefsStatus<T>>();
+    status.__finish_load();
+    status.content_hash = None;
+}
+
+/// Tears down `PrefsPlugin<T>`.
+///
+/// Any pending save is completed first, and any in-progress load is dropped along with its
+/// entity, so it can no longer insert resources once the plugin is gone. The plugin's own
+/// resources are then removed and `T` is removed from the [`PrefsRegistry`], after which the
+/// plugin's systems stop running. The preference resources themselves are left in place.
+///
+/// In WASM builds, a save that is already in progress is dropped as well.
+pub fn remove_prefs<T: Send + Sync + 'static>(world: &mut World) {
+    #[cfg(not(target_arch = "wasm32"))]
+    finish_save_tasks::<T>(world);
+    cancel_load_tasks::<T>(world);
+    #[cfg(target_arch = "wasm32")]
+    {
+        let save_tasks: Vec<Entity> = world
+            .query_filtered::<Entity, With<SavePrefsTask<T>>>()
+            .iter(world)
+            .collect();
+        for entity in save_tasks {
+            world.despawn(entity);
+        }
+    }
+
+    world.remove_resource::<PrefsSettings<T>>();
+    world.remove_resource::<PrefsStatus<T>>();
+    world.remove_resource::<PrefsLoadWaiters<T>>();
+    world.remove_resource::<PrefsPreservedFields<T>>();
+    world.remove_resource::<PrefsChangeStreaks<T>>();
+    world.remove_resource::<PrefsWarmUp<T>>();
+    world.remove_resource::<PrefsLoadQueued<T>>();
+    #[cfg(not(target_arch = "wasm32"))]
+    world.remove_resource::<PrefsSlot<T>>();
+    #[cfg(not(target_arch = "wasm32"))]
+    world.remove_resource::<PrefsHistoryState<T>>();
+    world.remove_resource::<PrefsSaveError<T>>();
+    world.remove_resource::<PrefsLoadError<T>>();
+    #[cfg(feature = "asset")]
+    world.remove_resource::<PrefsDefaultAsset<T>>();
+    #[cfg(feature = "http")]
+    world.remove_resource::<PrefsHttpFetch<T>>();
+    #[cfg(target_arch = "wasm32")]
+    if let Some(flush) = world.remove_resource::<PrefsFlush<T>>() {
+        // The page listeners outlive the plugin, so make sure they have nothing left to write.
+        flush
+            .pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+    }
+    if let Some(on_loaded) = world.remove_resource::<PrefsOnLoaded<T>>() {
+        for system in on_loaded.systems {
+            let _ = world.unregister_system(system);
+        }
+    }
+
+    if let Some(mut registry) = world.get_resource_mut::<PrefsRegistry>() {
+        registry.unregister::<T>();
+    }
+}
+
+fn peek_str<T>(serialized: String) -> Option<T>
+where
+    T: Prefs + Reflect + TypePath + GetTypeRegistration + Default,
+{
+    deserialize(&serialized)
+        .inspect_err(|e| warn!("Failed to peek at {}: {}", T::type_path(), e))
+        .ok()
+}
+
+fn diff<T>(world: &World) -> Result<Vec<&'static str>, PrefsError>
+where
+    T: Prefs + Reflect + TypePath + GetTypeRegistration + Default,
+{
+    let settings = world.resource::<PrefsSettings<T>>();
+    let stored = settings.read()?.or_else(|| settings.first_run_default());
+    let stored = match stored {
+        Some(serialized) => settings.deserialize(&serialized)?.0,
+        None => settings.base()?,
+    };
+    let current = T::current(world);
+
+    let (ReflectRef::Struct(stored), ReflectRef::Struct(current)) =
+        (stored.reflect_ref(), current.reflect_ref())
+    else {
+        unreachable!("Prefs can only be derived for structs");
+    };
+
+    Ok(T::FIELD_NAMES
+        .iter()
+        .copied()
+        .filter(|name| !T::TRANSIENT_FIELDS.contains(name))
+        .filter(|name| {
+            let (Some(stored), Some(current)) = (stored.field(name), current.field(name)) else {
+                return true;
+            };
+            current.reflect_partial_eq(stored) != Some(true)
+        })
+        .collect())
+}
+
+fn load_field<T, R>(world: &mut World) -> Result<bool, PrefsError>
+where
+    T: Prefs + Reflect + TypePath + GetTypeRegistration + Default,
+    R: Resource + Reflect + Clone,
+{
+    let settings = world.resource::<PrefsSettings<T>>();
+    let Some(serialized) = settings.read()? else {
+        return Ok(false);
+    };
+    let (value, _) = settings.deserialize(&serialized)?;
+
+    // Like loading, this doesn't trigger change detection.
+    let value = field_of::<T, R>(&value)?.clone();
+    world.remove_resource::<Persisted<R>>();
+    match world.get_resource_mut::<R>() {
+        Some(mut current) => *current.bypass_change_detection() = value,
+        None => world.insert_resource(value),
+    }
+    world
+        .resource_mut::<PrefsStatus<T>>()
+        .bypass_change_detection()
+        .load_count += 1;
+
+    Ok(true)
+}
+
+fn reset_field<T, R>(world: &mut World) -> Result<(), PrefsError>
+where
+    T: Prefs + Reflect + TypePath + GetTypeRegistration + Default,
+    R: Resource + Reflect + Clone,
+{
+    world.insert_resource(field_of::<T, R>(&T::default())?.clone());
+    Ok(())
+}
+
+/// Returns the field of type `R` of `value`, which must be the type of exactly one of the fields.
+fn field_of<T, R>(value: &T) -> Result<&R, PrefsError>
+where
+    T: Prefs + Reflect + TypePath + GetTypeRegistration,
+    R: Reflect,
+{
+    let TypeInfo::Struct(info) = T::get_type_registration().type_info() else {
+        unreachable!("Prefs can only be derived for structs");
+    };
+    let ReflectRef::Struct(value) = value.reflect_ref() else {
+        unreachable!("Prefs can only be derived for structs");
+    };
+
+    let mut names = T::FIELD_NAMES.iter().filter(|name| {
+        info.field(name)
+            .is_some_and(|field| field.type_id() == TypeId::of::<R>())
+    });
+    let (Some(name), None) = (names.next(), names.next()) else {
+        return Err(PrefsError::UnknownField(format!(
+            "{} is not the type of exactly one field of {}",
+            std::any::type_name::<R>(),
+            T::type_path()
+        )));
+    };
+
+    value
+        .field(name)
+        .and_then(|field| field.try_downcast_ref::<R>())
+        .ok_or_else(|| {
+            PrefsError::UnknownField(format!("{} has no field {}", T::type_path(), name))
+        })
+}
+
+fn save_current<T: Prefs + Reflect + TypePath + GetTypeRegistration + Default>(
+    world: &mut World,
+) -> Result<(), PrefsError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    finish_save_tasks::<T>(world);
+    cancel_load_tasks::<T>(world);
+
+    let mut status = world.resource_mut::<PrefsStatus<T>>();
+    if status.load_state != PrefsLoadState::Applied {
+        status.__finish_load();
+    }
+
+    let settings = world.resource::<PrefsSettings<T>>();
+    if settings.read_only {
+        return Ok(());
+    }
+
+    let last_hash = world.resource::<PrefsStatus<T>>().content_hash;
+    let result = export_prefs::<T>(world).and_then(|serialized| {
+        let hash = content_hash(&serialized);
+        let bytes = serialized.len();
+        world
+            .resource_mut::<PrefsSettings<T>>()
+            .write(serialized)
+            .map(|()| (hash, bytes, last_hash != Some(hash)))
+    });
+
+    let mut status = world.resource_mut::<PrefsStatus<T>>();
+    let status = status.bypass_change_detection();
+    status.save_count += 1;
+
+    match result {
+        Ok((hash, bytes, changed)) => {
+            status.content_hash = Some(hash);
+            status.last_save_bytes = Some(bytes);
+            status.__record_save(None);
+            world.remove_resource::<PrefsSaveError<T>>();
+            world.send_event(PrefsSaved::<T>::new(changed));
+            Ok(())
+        }
+        Err(e) => {
+            status.__record_save(Some(e.clone()));
+            world.insert_resource(PrefsSaveError::<T>::new(e.clone()));
+            Err(e)
+        }
+    }
+}
+
+/// Serializes the current preferences of `T`, as they would be written to the preferences file.
+pub fn export_prefs<T: Prefs + Reflect + TypePath + GetTypeRegistration + Default>(
+    world: &World,
+) -> Result<String, PrefsError> {
+    world.resource::<PrefsSettings<T>>().serialize(
+        &T::current(world),
+        &world.resource::<PrefsPreservedFields<T>>().preserved,
+    )
+}
+
+/// Returns a readable summary of the configuration and status of the preferences of `T`, such as
+/// for including in a bug report.
+///
+/// This is [`PrefsSettings::describe`] followed by the [`PrefsStatus`].
+///
+/// ```rust
+/// # use bevy::prelude::*;
+/// # use bevy_simple_prefs::{describe_prefs, Prefs, PrefsPlugin};
+/// # #[derive(Prefs, Reflect, Default)]
+/// # struct ExamplePrefs {
+/// #     volume: Volume,
+/// # }
+/// # #[derive(Resource, Reflect, Clone, Default)]
+/// # struct Volume(u32);
+/// let mut app = App::new();
+/// app.add_plugins(PrefsPlugin::<ExamplePrefs> {
+///     filename: "prefs.ron".into(),
+///     ..default()
+/// });
+///
+/// let description = describe_prefs::<ExamplePrefs>(app.world());
+/// assert!(description.contains("prefs.ron"));
+/// assert!(description.contains("load state: NotStarted"));
+/// ```
+pub fn describe_prefs<T: Prefs + TypePath + Send + Sync + 'static>(world: &World) -> String {
+    use std::fmt::Write as _;
+
+    let mut out = world.resource::<PrefsSettings<T>>().describe();
+    let status = world.resource::<PrefsStatus<T>>();
+    let _ = write!(out, "\n  load state: {:?}", status.load_state);
+    if let Some(loaded_version) = status.loaded_version {
+        let _ = write!(out, "\n  loaded version: {}", loaded_version);
+    }
+    let _ = write!(out, "\n  saves: {}", status.save_count);
+    if let Some(last_save_bytes) = status.last_save_bytes {
+        let _ = write!(out, "\n  last save: {} bytes", last_save_bytes);
+    }
+    if status.autosave_paused {
+        let _ = write!(out, "\n  autosave: paused");
+    }
+    if let Some(last_error) = &status.last_error {
+        let _ = write!(out, "\n  last error: {}", last_error);
+    }
+    out
+}
+
+/// The complete preferences of `T` at some point in time, taken with [`Prefs::snapshot`].
+///
+/// Unlike the stored preferences, snapshots include the values of transient fields.
+///
+/// The snapshot holds the serialized preferences, which can be shared with [`Self::as_str`] and
+/// turned back into a snapshot with [`Self::from_serialized`].
+pub struct PrefsSnapshot<T> {
+    serialized: String,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> PrefsSnapshot<T> {
+    /// Creates a snapshot from serialized preferences, such as those returned by
+    /// [`Self::as_str`].
+    pub fn from_serialized(serialized: impl Into<String>) -> Self {
+        Self {
+            serialized: serialized.into(),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns the serialized preferences.
+    pub fn as_str(&self) -> &str {
+        &self.serialized
+    }
+}
+
+impl<T> Clone for PrefsSnapshot<T> {
+    fn clone(&self) -> Self {
+        Self::from_serialized(self.serialized.clone())
+    }
+}
+
+impl<T> std::fmt::Debug for PrefsSnapshot<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PrefsSnapshot")
+            .field(&self.serialized)
+            .finish()
+    }
+}
+
+fn snapshot<T: Prefs + Reflect + TypePath + GetTypeRegistration + Default>(
+    world: &World,
+) -> Result<PrefsSnapshot<T>, PrefsError> {
+    // Unlike `export_prefs`, every field is included even if it matches the base preferences or
+    // is transient.
+    serialize_preserving_skipping(
+        &T::current(world),
+        &world.resource::<PrefsSettings<T>>().format,
+        &world.resource::<PrefsPreservedFields<T>>().preserved,
+        &[],
+    )
+    .map(PrefsSnapshot::from_serialized)
+}
+
+fn restore<T: Prefs + Reflect + TypePath + GetTypeRegistration + Default>(
+    world: &mut World,
+    snapshot: &PrefsSnapshot<T>,
+) -> Result<(), PrefsError> {
+    let (val, mut preserved) = deserialize_preserving_onto(
+        T::default(),
+        &snapshot.serialized,
+        &world.resource::<PrefsSettings<T>>().format,
+    )?;
+    // Transient fields are restored, but their stored values stay as they are, and so do those
+    // of overridden fields.
+    let current = &world.resource::<PrefsPreservedFields<T>>().preserved;
+    preserved.transient = current.transient.clone();
+    preserved.overridden = current.overridden.clone();
+
+    val.insert_loaded(world);
+    world.insert_resource(PrefsPreservedFields::<T>::new(preserved));
+    world
+        .resource_mut::<PrefsStatus<T>>()
+        .bypass_change_detection()
+        .load_count += 1;
+
+    save_current::<T>(world)
+}
+
+fn compact<T: Prefs + Reflect + TypePath + GetTypeRegistration + Default>(
+    world: &mut World,
+) -> Result<bool, PrefsError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    finish_save_tasks::<T>(world);
+
+    let settings = world.resource::<PrefsSettings<T>>();
+    if settings.read_only {
+        return Ok(false);
+    }
+    let Some(serialized) = settings.read()? else {
+        return Ok(false);
+    };
+    let (mut value, preserved) = settings.deserialize(&serialized)?;
+    if let Some(post_load) = settings.post_load {
+        post_load(&mut value);
+    }
+    // Stored values of transient fields are still part of the preferences, and the current
+    // preferences are still overridden.
+    let preserved = PreservedFields {
+        transient: preserved.transient,
+        overridden: world
+            .resource::<PrefsPreservedFields<T>>()
+            .preserved
+            .overridden
+            .clone(),
+        ..Default::default()
+    };
+    let compacted = settings.serialize(&value, &preserved)?;
+
+    let hash = content_hash(&compacted);
+    let bytes = compacted.len();
+    world.resource_mut::<PrefsSettings<T>>().write(compacted)?;
+    world.insert_resource(PrefsPreservedFields::<T>::new(preserved));
+
+    let mut status = world.resource_mut::<PrefsStatus<T>>();
+    let status = status.bypass_change_detection();
+    status.content_hash = Some(hash);
+    status.last_save_bytes = Some(bytes);
+
+    Ok(true)
+}
+
+/// Replaces the stored preferences of `T` with `serialized` and applies them.
+///
+/// `serialized` is typically the output of [`export_prefs`]. Any pending save is completed
+/// first, and any in-progress load is discarded. Nothing is changed if `serialized` can't be
+/// deserialized. The stored preferences of read-only plugins are left in place.
+pub fn import_prefs<T: Prefs + Reflect + TypePath + GetTypeRegistration + Default>(
+    world: &mut World,
+    serialized: &str,
+) -> Result<(), PrefsError> {
+    prepare_import::<T>(world, serialized)?.apply(world)
+}
+
+/// Deserializes `serialized` without changing anything, returning a [`PreparedImport`] that
+/// applies it like [`import_prefs`].
+///
+/// This allows checking that several preferences types can all be imported before applying any
+/// of them.
+pub fn prepare_import<T: Prefs + Reflect + TypePath + GetTypeRegistration + Default>(
+    world: &World,
+    serialized: &str,
+) -> Result<PreparedImport, PrefsError> {
+    let (val, preserved) = world
+        .resource::<PrefsSettings<T>>()
+        .deserialize(serialized)?;
+    let serialized = serialized.to_string();
+
+    Ok(PreparedImport(Box::new(move |world| {
+        apply_import::<T>(world, val, preserved, serialized)
+    })))
+}
+
+fn apply_import<T: Prefs + Reflect + TypePath + GetTypeRegistration + Default>(
+    world: &mut World,
+    val: T,
+    preserved: PreservedFields,
+    serialized: String,
+) -> Result<(), PrefsError> {
+    #[cfg(not(target_arch = "wasm32"))]
+    finish_save_tasks::<T>(world);
+    cancel_load_tasks::<T>(world);
+
+    if !world.resource::<PrefsSettings<T>>().read_only {
+        let hash = content_hash(&serialized);
+        let bytes = serialized.len();
+        world.resource_mut::<PrefsSettings<T>>().write(serialized)?;
+        let mut status = world.resource_mut::<PrefsStatus<T>>();
+        status.content_hash = Some(hash);
+        status.last_save_bytes = Some(bytes);
+    }
+
+    val.insert_loaded(world);
+    world.insert_resource(PrefsPreservedFields::<T>::new(preserved));
+
+    let mut status = world.resource_mut::<PrefsStatus<T>>();
+    status.__finish_load();
+    status.load_count += 1;
+
+    Ok(())
+}
+
+/// Serializes the current preferences of every type in the [`PrefsRegistry`] into a single
+/// archive, keyed by type path.
+///
+/// With the `compact` feature, the archive is compressed and base64-encoded. Otherwise, it is
+/// plain RON. The archive can be restored with [`import_all_prefs`].
+pub fn export_all_prefs(world: &World) -> Result<String, PrefsError> {
+    let mut archive = std::collections::BTreeMap::new();
+    if let Some(registry) = world.get_resource::<PrefsRegistry>() {
+        for registration in registry.iter() {
+            archive.insert(registration.type_path, (registration.export)(world)?);
+        }
+    }
+
+    let archive =
+        to_string_pretty(&archive, PrettyConfig::default()).map_err(PrefsError::Serialize)?;
+
+    #[cfg(feature = "compact")]
+    let archive = compact::encode(&archive);
+
+    Ok(archive)
+}
+
+/// Restores an archive created by [`export_all_prefs`], replacing the stored preferences of each
+/// type in the archive and applying them.
+///
+/// Types in the archive that aren't in the [`PrefsRegistry`] are ignored, and types that aren't
+/// in the archive are left unchanged. Every type in the archive is deserialized before any of
+/// them are applied, so nothing is changed if one of them can't be deserialized. See
+/// [`import_prefs`].
+///
+/// With the `compact` feature, both compressed and plain archives can be restored.
+pub fn import_all_prefs(world: &mut World, archive: &str) -> Result<(), PrefsError> {
+    #[cfg(feature = "compact")]
+    let archive = &compact::decode(archive.to_string())?;
+
+    let archive: std::collections::BTreeMap<String, String> =
+        ron::from_str(archive).map_err(|e| PrefsError::Deserialize(e.to_string()))?;
+
+    let Some(registry) = world.get_resource::<PrefsRegistry>() else {
+        return Ok(());
+    };
+    let imports: Vec<_> = registry
+        .iter()
+        .filter_map(|r| archive.get(r.type_path).map(|s| (r.prepare_import, s)))
+        .collect();
+
+    for type_path in archive.keys() {
+        if !registry.iter().any(|r| r.type_path == type_path) {
+            warn!("Ignoring unknown prefs type in archive: {}", type_path);
+        }
+    }
+
+    let prepared = imports
+        .into_iter()
+        .map(|(prepare, serialized)| prepare(world, serialized))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for import in prepared {
+        import.apply(world)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn switch_prefs_path<T: Prefs + Send + Sync + 'static>(world: &mut World, path: PathBuf) {
+    switch_storage::<T>(world, |settings| settings.path = path);
+}
+
+/// Completes any pending save, applies `switch` to the settings of `T` and loads the preferences
+/// stored there.
+#[cfg(not(target_arch = "wasm32"))]
+fn switch_storage<T: Prefs + Send + Sync + 'static>(
+    world: &mut World,
+    switch: impl FnOnce(&mut PrefsSettings<T>),
+) {
+    finish_save_tasks::<T>(world);
+    cancel_load_tasks::<T>(world);
+
+    switch(&mut world.resource_mut::<PrefsSettings<T>>());
+
+    let mut status = world.resource_mut::<PrefsStatus<T>>();
+    status.loaded = false;
+    status.load_progress = 0.0;
+
+    T::load(world);
+}
+
+/// The active save slot of `T`, for [`PrefsPlugin::slots`].
+///
+/// When `active` is changed, any changes made to the preferences in the meantime are saved to
+/// the previous slot, and then the preferences stored in the new slot are loaded, as with
+/// [`PrefsCommandsExt::switch_prefs_path`].
+///
+/// This type does not exist in WASM builds.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource)]
+pub struct PrefsSlot<T> {
+    /// The index of the active slot, which is less than [`PrefsPlugin::slots`].
+    pub active: u32,
+    loaded: u32,
+    filename: String,
+    /// The status of each slot that has been active, as of when it was left.
+    statuses: HashMap<u32, PrefsSlotStatus>,
+    /// [`PrefsStatus::save_count`] when the loaded slot became active.
+    save_count_base: u64,
+    _phantom: PhantomData<T>,
+}
+
+/// The status of a save slot, for [`PrefsPlugin::slots`].
+///
+/// See [`slot_status`].
+///
+/// This type does not exist in WASM builds.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrefsSlotStatus {
+    /// `true` if the preferences of the slot have been loaded.
+    pub loaded: bool,
+    /// The number of times a save of the slot has been initiated since the app started.
+    pub save_count: u64,
+    /// The [`PrefsStatus::content_hash`] of the slot.
+    pub content_hash: Option<u64>,
+    /// The [`PrefsStatus::last_save_bytes`] of the slot.
+    pub last_save_bytes: Option<usize>,
+    /// The error from the most recent failed load or save of the slot.
+    pub last_error: Option<PrefsError>,
+    /// The [`PrefsStatus::metadata`] of the slot.
+    pub metadata: Option<PrefsMetadata>,
+    /// The [`PrefsStatus::loaded_version`] of the slot.
+    pub loaded_version: Option<u32>,
+    last_error_from_save: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<T> PrefsSlot<T> {
+    /// Returns the filename of the preferences file of `slot`.
+    pub fn filename(&self, slot: u32) -> String {
+        slot_filename(&self.filename, slot)
+    }
+}
+
+/// Returns `filename` with `_slot{slot}` appended to its stem.
+#[cfg(not(target_arch = "wasm32"))]
+fn slot_filename(filename: &str, slot: u32) -> String {
+    match filename.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}_slot{}.{}", stem, slot, extension),
+        None => format!("{}_slot{}", filename, slot),
+    }
+}
+
+/// Loads the preferences of the active slot of `T`, if it has changed.
+#[cfg(not(target_arch = "wasm32"))]
+fn switch_slot<T: Prefs + TypePath + Send + Sync + 'static>(world: &mut World) {
+    let slot = world.resource::<PrefsSlot<T>>();
+    if slot.active == slot.loaded {
+        return;
+    }
+
+    let slots = world.resource::<PrefsSettings<T>>().slots;
+    let mut slot = world.resource_mut::<PrefsSlot<T>>();
+    let slot = slot.bypass_change_detection();
+    if slot.active >= slots {
+        warn!(
+            "Ignoring slot {} of {}, which only has {} slots",
+            slot.active,
+            T::type_path(),
+            slots
+        );
+        slot.active = slot.loaded;
+        return;
+    }
+    let (left, entered) = (slot.loaded, slot.active);
+    let filename = slot.filename(entered);
+
+    // Saves of the slot being left are finished first, so that its status includes them.
+    finish_save_tasks::<T>(world);
+    let left_status = slot_status::<T>(world, left).unwrap_or_default();
+
+    let mut slot = world.resource_mut::<PrefsSlot<T>>();
+    let slot = slot.bypass_change_detection();
+    slot.loaded = entered;
+    slot.statuses.insert(left, left_status);
+    let entered_status = slot.statuses.get(&entered).cloned().unwrap_or_default();
+
+    let mut status = world.resource_mut::<PrefsStatus<T>>();
+    status.content_hash = entered_status.content_hash;
+    status.last_save_bytes = entered_status.last_save_bytes;
+    status.last_error = entered_status.last_error;
+    status.last_error_from_save = entered_status.last_error_from_save;
+    status.metadata = entered_status.metadata;
+    status.loaded_version = entered_status.loaded_version;
+    let save_count = status.save_count;
+    world
+        .resource_mut::<PrefsSlot<T>>()
+        .bypass_change_detection()
+        .save_count_base = save_count;
+
+    switch_storage::<T>(world, |settings| settings.filename = filename);
+}
+
+/// Returns the status of `slot` of `T`, or `None` if it hasn't been active since the app
+/// started.
+///
+/// The status of the active slot is kept up to date, while the status of any other slot is as
+/// of when it was left. This returns `None` if [`PrefsPlugin::slots`] is `0`.
+///
+/// This function does not exist in WASM builds.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn slot_status<T: Send + Sync + 'static>(world: &World, slot: u32) -> Option<PrefsSlotStatus> {
+    let slots = world.get_resource::<PrefsSlot<T>>()?;
+    if slot != slots.loaded {
+        return slots.statuses.get(&slot).cloned();
+    }
+
+    let status = world.resource::<PrefsStatus<T>>();
+    let previous = slots.statuses.get(&slot);
+    Some(PrefsSlotStatus {
+        loaded: status.loaded,
+        save_count: previous.map_or(0, |previous| previous.save_count) + status.save_count
+            - slots.save_count_base,
+        content_hash: status.content_hash,
+        last_save_bytes: status.last_save_bytes,
+        last_error: status.last_error.clone(),
+        metadata: status.metadata.clone(),
+        loaded_version: status.loaded_version,
+        last_error_from_save: status.last_error_from_save,
+    })
+}
+
+/// Returns the slots of `T` that have stored preferences, for a slot selection screen.
+///
+/// This returns an empty `Vec` if [`PrefsPlugin::slots`] is `0`. This blocks the calling thread
+/// while reading each slot.
+///
+/// This function does not exist in WASM builds.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn stored_slots<T: TypePath + Send + Sync + 'static>(world: &World) -> Vec<u32> {
+    let Some(slot) = world.get_resource::<PrefsSlot<T>>() else {
+        return Vec::new();
+    };
+    let mut settings = world.resource::<PrefsSettings<T>>().clone();
+
+    (0..settings.slots)
+        .filter(|&index| {
+            settings.filename = slot.filename(index);
+            settings.read().is_ok_and(|serialized| serialized.is_some())
+        })
+        .collect()
+}
+
+/// Marks a load of `T` as requested while another load was in progress, for [`Prefs::load`].
+#[derive(Resource)]
+struct PrefsLoadQueued<T> {
+    _phantom: PhantomData<T>,
+}
+
+/// Returns `true` if a load of `T` is already in progress, in which case another load is queued
+/// to start once it has finished. If `blocking` is set, any load in progress is discarded
+/// instead.
+///
+/// This is used by the derive macro, and is not part of the public API.
+#[doc(hidden)]
+pub fn __queue_load<T: Send + Sync + 'static>(world: &mut World, blocking: bool) -> bool {
+    if blocking {
+        cancel_load_tasks::<T>(world);
+        return false;
+    }
+
+    let in_progress = world
+        .query_filtered::<(), With<LoadPrefsTask<T>>>()
+        .iter(world)
+        .next()
+        .is_some();
+    if in_progress {
+        world.insert_resource(PrefsLoadQueued::<T> {
+            _phantom: PhantomData,
+        });
+    }
+    in_progress
+}
+
+/// Starts the load of `T` that was queued while the previous one was in progress, if any.
+fn start_queued_load<T: Prefs + Send + Sync + 'static>(world: &mut World) {
+    if world.remove_resource::<PrefsLoadQueued<T>>().is_some() {
+        T::load(world);
+    }
+}
+
+/// Discards any in-progress loads of `T`, along with any load queued to start after them.
+fn cancel_load_tasks<T: Send + Sync + 'static>(world: &mut World) {
+    world.remove_resource::<PrefsLoadQueued<T>>();
+    let load_tasks: Vec<Entity> = world
+        .query_filtered::<Entity, With<LoadPrefsTask<T>>>()
+        .iter(world)
+        .collect();
+    for entity in load_tasks {
+        world.despawn(entity);
+    }
+}
+
+/// Blocks until any in-progress loads of `T`, including queued ones, and then any in-progress
+/// saves of `T` have finished, and applies their results.
+#[cfg(all(any(test, feature = "testing"), not(target_arch = "wasm32")))]
+pub(crate) fn finish_tasks<T: Prefs + Send + Sync + 'static>(world: &mut World) {
+    loop {
+        let mut query = world.query::<(Entity, &mut LoadPrefsTask<T>)>();
+        let finished: Vec<(Entity, CommandQueue)> = query
+            .iter_mut(world)
+            .map(|(entity, mut task)| (entity, block_on(&mut task.task)))
+            .collect();
+        if finished.is_empty() {
+            break;
+        }
+        for (entity, mut command_queue) in finished {
+            command_queue.apply(world);
+            if world.get_entity(entity).is_ok() {
+                world.despawn(entity);
+            }
+            start_queued_load::<T>(world);
+        }
+    }
+    finish_save_tasks::<T>(world);
+}
+
+/// Blocks until any in-progress saves of `T` have finished.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn finish_save_tasks<T: Send + Sync + 'static>(world: &mut World) {
+    let mut query = world.query::<(Entity, &mut SavePrefsTask<T>)>();
+    // Finished tasks are either handled by `handle_tasks` already, or will be on its next run.
+    let finished: Vec<(Entity, CommandQueue)> = query
+        .iter_mut(world)
+        .filter(|(_, task)| !task.task.is_finished())
+        .map(|(entity, mut task)| (entity, block_on(&mut task.task)))
+        .collect();
+    for (entity, mut command_queue) in finished {
+        command_queue.apply(world);
+        world.despawn(entity);
+    }
+}
+
+impl<T: Prefs + Reflect + TypePath + GetTypeRegistration + Default> Plugin for PrefsPlugin<T> {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.insert_resource::<PrefsSettings<T>>(PrefsSettings {
+            filename: self.filename.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            path: self.path.clone(),
+            prefix: if self.prefix.is_empty() {
+                app.world()
+                    .get_resource::<PrefsPrefix>()
+                    .map(|prefix| prefix.0.clone())
+                    .unwrap_or_default()
+            } else {
+                self.prefix.clone()
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            load_paths: self.load_paths.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            fallback_path: self.fallback_path.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            mirror_paths: self.mirror_paths.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            base_path: self.base_path.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            create_dirs: self.create_dirs,
+            #[cfg(not(target_arch = "wasm32"))]
+            file_mode: self.file_mode,
+            #[cfg(not(target_arch = "wasm32"))]
+            save_retry: self.save_retry.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            history: self.history.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            journal: self.journal.clone(),
+            format: self.format.clone(),
+            blocking_load: self.blocking_load,
+            read_only: self.read_only,
+            embedded_default: self.embedded_default,
+            #[cfg(feature = "asset")]
+            asset_default: None,
+            #[cfg(feature = "http")]
+            http_defaults: self.http_defaults.clone(),
+            #[cfg(feature = "http")]
+            http_default: None,
+            periodic_save: self.periodic_save,
+            serialize_in_place: self.serialize_in_place,
+            warn_constant_changes: self.warn_constant_changes,
+            shared_file: self.shared_file,
+            pre_write: self.pre_write,
+            post_read: self.post_read,
+            post_load: self.post_load,
+            log_loaded: self.log_loaded,
+            save_if: self.save_if,
+            #[cfg(not(target_arch = "wasm32"))]
+            sidecar: self.sidecar,
+            #[cfg(not(target_arch = "wasm32"))]
+            env_prefix: self.env_prefix.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            fragment_dir: self.fragment_dir.clone(),
+            #[cfg(not(target_arch = "wasm32"))]
+            slots: self.slots,
+            #[cfg(all(feature = "keychain", not(target_arch = "wasm32")))]
+            keychain: self.keychain,
+            #[cfg(all(feature = "compact", target_arch = "wasm32"))]
+            compact_storage: self.compact_storage,
+            backend: self.backend.clone(),
+            _phantom: Default::default(),
+        });
+        app.init_resource::<PrefsStatus<T>>();
+        app.add_event::<PrefsSaved<T>>();
+        app.init_resource::<PrefsLoadWaiters<T>>();
+        app.init_resource::<PrefsPreservedFields<T>>();
+        app.init_resource::<PrefsChangeStreaks<T>>();
+        if self.warm_up_frames > 0 {
+            app.insert_resource(PrefsWarmUp::<T> {
+                frames_left: self.warm_up_frames,
+                _phantom: PhantomData,
+            });
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.slots > 0 {
+            app.world_mut().resource_mut::<PrefsSettings<T>>().filename =
+                slot_filename(&self.filename, 0);
+            app.insert_resource(PrefsSlot::<T> {
+                active: 0,
+                loaded: 0,
+                filename: self.filename.clone(),
+                statuses: HashMap::new(),
+                save_count_base: 0,
+                _phantom: PhantomData,
+            });
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        app.init_resource::<PrefsHistoryState<T>>();
+        let on_loaded = std::mem::take(
+            &mut *self
+                .on_loaded
+                .0
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        let systems = on_loaded
+            .into_iter()
+            .map(|system| app.world_mut().register_boxed_system(system))
+            .collect();
+        app.insert_resource(PrefsOnLoaded::<T> {
+            systems,
+            _phantom: PhantomData,
+        });
+        app.init_resource::<PrefsRegistry>();
+        app.world_mut()
+            .resource_mut::<PrefsRegistry>()
+            .register::<T>();
+        #[cfg(target_arch = "wasm32")]
+        if self.save_on_hide {
+            let flush = PrefsFlush::<T> {
+                pending: Default::default(),
+            };
+            flush_on_hide(flush.pending.clone());
+            app.insert_resource(flush);
+        }
+
+        <T>::init(app);
+
+        // `save` checks load status and needs to run in the same frame after `handle_tasks`.
+        app.configure_sets(Update, PrefsSet::Load.before(PrefsSet::Save));
+        app.add_systems(
+            Update,
+            (
+                handle_tasks::<T>.in_set(PrefsSet::Load),
+                notify_load_waiters::<T>
+                    .after(handle_tasks::<T>)
+                    .in_set(PrefsSet::Load),
+                run_on_loaded::<T>
+                    .after(handle_tasks::<T>)
+                    .in_set(PrefsSet::Load),
+                request_periodic_save::<T>
+                    .before(<T>::save)
+                    .in_set(PrefsSet::Save),
+                <T>::save.in_set(PrefsSet::Save),
+            )
+                // The plugin's resources are gone after `remove_prefs`.
+                .run_if(resource_exists::<PrefsSettings<T>>),
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.slots > 0 {
+            // Changes made before switching are saved to the previous slot.
+            app.add_systems(
+                Update,
+                switch_slot::<T>
+                    .after(<T>::save)
+                    .in_set(PrefsSet::Save)
+                    .run_if(resource_exists::<PrefsSlot<T>>),
+            );
+        }
+        #[cfg(feature = "asset")]
+        if let Some(path) = &self.default_asset {
+            if app.world().contains_resource::<bevy::asset::AssetServer>() {
+                if !app
+                    .world()
+                    .contains_resource::<bevy::asset::Assets<PrefsDefaults>>()
+                {
+                    use bevy::asset::AssetApp;
+                    app.init_asset::<PrefsDefaults>()
+                        .register_asset_loader(PrefsDefaultsLoader);
+                }
+                let handle = app
+                    .world()
+                    .resource::<bevy::asset::AssetServer>()
+                    .load(path);
+                app.insert_resource(PrefsDefaultAsset::<T> {
+                    handle,
+                    waiting: false,
+                    _phantom: PhantomData,
+                });
+                app.add_systems(
+                    Update,
+                    load_after_default_asset::<T>
+                        .before(handle_tasks::<T>)
+                        .in_set(PrefsSet::Load)
+                        .run_if(resource_exists::<PrefsDefaultAsset<T>>),
+                );
+            } else {
+                warn!(
+                    "Ignoring default_asset of {}, because AssetPlugin hasn't been added",
+                    T::type_path()
+                );
+            }
+        }
+
+        #[cfg(feature = "http")]
+        if self.http_defaults.is_some() {
+            app.insert_resource(PrefsHttpFetch::<T> {
+                receiver: None,
+                _phantom: PhantomData,
+            });
+            app.add_systems(
+                Update,
+                load_after_http_defaults::<T>
+                    .before(handle_tasks::<T>)
+                    .in_set(PrefsSet::Load)
+                    .run_if(resource_exists::<PrefsHttpFetch<T>>),
+            );
+        }
+
+        if let Some(load_schedule) = self.load_schedule {
+            app.add_systems(
+                load_schedule,
+                load::<T>.run_if(resource_exists::<PrefsSettings<T>>),
+            );
+        }
+    }
+}
+
+/// Loads the preferences, after fetching the [`PrefsPlugin::http_defaults`] if they are set.
+fn load<T: Prefs + TypePath + Send + Sync + 'static>(world: &mut World) {
+    #[cfg(feature = "http")]
+    if world.contains_resource::<PrefsHttpFetch<T>>() {
+        let Some(http_defaults) = world.resource::<PrefsSettings<T>>().http_defaults.clone() else {
+            return;
+        };
+        world.resource_mut::<PrefsHttpFetch<T>>().receiver = Some(http::fetch(http_defaults));
+        world
+            .resource_mut::<PrefsStatus<T>>()
+            .bypass_change_detection()
+            .__begin_load();
+        return;
+    }
+
+    load_with_defaults::<T>(world);
+}
+
+/// Loads the preferences, unless they are waiting for the [`PrefsPlugin::default_asset`], in
+/// which case they are loaded once it has been resolved.
+fn load_with_defaults<T: Prefs + TypePath + Send + Sync + 'static>(world: &mut World) {
+    #[cfg(feature = "asset")]
+    if let Some(mut default_asset) = world.get_resource_mut::<PrefsDefaultAsset<T>>() {
+        default_asset.waiting = true;
+        world
+            .resource_mut::<PrefsStatus<T>>()
+            .bypass_change_detection()
+            .__begin_load();
+        load_after_default_asset::<T>(world);
+        return;
+    }
+
+    T::load(world);
+}
+
+/// Default preferences that are fetched from a URL, for [`PrefsPlugin::http_defaults`].
+#[cfg(feature = "http")]
+#[derive(Clone, Debug)]
+pub struct HttpDefaults {
+    /// URL of the serialized default preferences.
+    pub url: String,
+    /// How long to wait for a response before falling back to `embedded_default`.
+    pub timeout: Duration,
+}
+
+#[cfg(feature = "http")]
+impl HttpDefaults {
+    /// Creates `HttpDefaults` for `url`, with a timeout of 10 seconds.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// The request for the [`PrefsPlugin::http_defaults`] of `T` that loading the preferences is
+/// waiting for, if any.
+#[cfg(feature = "http")]
+#[derive(Resource)]
+struct PrefsHttpFetch<T> {
+    receiver: Option<async_channel::Receiver<Result<String, PrefsError>>>,
+    _phantom: PhantomData<T>,
+}
+
+/// Loads the preferences once the [`PrefsPlugin::http_defaults`] they are waiting for have been
+/// fetched, after copying them to [`PrefsSettings::http_default`].
+#[cfg(feature = "http")]
+fn load_after_http_defaults<T: Prefs + TypePath + Send + Sync + 'static>(world: &mut World) {
+    let mut fetch = world.resource_mut::<PrefsHttpFetch<T>>();
+    let Some(result) = fetch.receiver.as_ref().and_then(|r| r.try_recv().ok()) else {
+        return;
+    };
+    fetch.receiver = None;
+
+    let mut settings = world.resource_mut::<PrefsSettings<T>>();
+    settings.http_default = match result {
+        Ok(http_default) => Some(http_default),
+        Err(e) => {
+            warn!("Failed to fetch http_defaults of {}: {}", T::type_path(), e);
+            settings.embedded_default.map(str::to_string)
+        }
+    };
+    load_with_defaults::<T>(world);
+}
+
+/// Fetching of [`PrefsPlugin::http_defaults`].
+#[cfg(feature = "http")]
+mod http {
+    use crate::{HttpDefaults, PrefsError};
+
+    /// Fetches `defaults` in the background, sending the response body to the returned receiver.
+    pub fn fetch(defaults: HttpDefaults) -> async_channel::Receiver<Result<String, PrefsError>> {
+        let (sender, receiver) = async_channel::bounded(1);
+
+        #[cfg(not(target_arch = "wasm32"))]
+        bevy::tasks::IoTaskPool::get()
+            .spawn(async move {
+                let _ = sender.send(get(&defaults)).await;
+            })
+            .detach();
+        #[cfg(target_arch = "wasm32")]
+        wasm_bindgen_futures::spawn_local(async move {
+            let _ = sender.send(get(&defaults).await).await;
+        });
+
+        receiver
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn get(defaults: &HttpDefaults) -> Result<String, PrefsError> {
+        ureq::AgentBuilder::new()
+            .timeout(defaults.timeout)
+            .build()
+            .get(&defaults.url)
+            .call()
+            .map_err(PrefsError::other)?
+            .into_string()
+            .map_err(PrefsError::from)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    async fn get(defaults: &HttpDefaults) -> Result<String, PrefsError> {
+        use wasm_bindgen_futures::JsFuture;
+        use web_sys::wasm_bindgen::{closure::Closure, JsCast, JsValue};
+
+        let js_err = |e: JsValue| PrefsError::other(format!("{:?}", e));
+        let window = web_sys::window().ok_or_else(|| PrefsError::other("no window"))?;
+
+        // Abort the request if it takes longer than the timeout.
+        let controller = web_sys::AbortController::new().map_err(js_err)?;
+        let init = web_sys::RequestInit::new();
+        init.set_signal(Some(&controller.signal()));
+        let abort = Closure::once_into_js(move || controller.abort());
+        window
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                abort.unchecked_ref(),
+                defaults.timeout.as_millis().try_into().unwrap_or(i32::MAX),
+            )
+            .map_err(js_err)?;
+
+        let response: web_sys::Response =
+            JsFuture::from(window.fetch_with_str_and_init(&defaults.url, &init))
+                .await
+                .map_err(js_err)?
+                .dyn_into()
+                .map_err(js_err)?;
+        if !response.ok() {
+            return Err(PrefsError::other(format!(
+                "HTTP status {}",
+                response.status()
+            )));
+        }
+
+        JsFuture::from(response.text().map_err(js_err)?)
+            .await
+            .map_err(js_err)?
+            .as_string()
+            .ok_or_else(|| PrefsError::other("response is not text"))
+    }
+}
+
+/// Serialized preferences loaded from an asset file, for [`PrefsPlugin::default_asset`].
+#[cfg(feature = "asset")]
+#[derive(bevy::asset::Asset, TypePath, Debug)]
+pub struct PrefsDefaults(pub String);
+
+/// Loads any file as [`PrefsDefaults`].
+#[cfg(feature = "asset")]
+struct PrefsDefaultsLoader;
+
+#[cfg(feature = "asset")]
+impl bevy::asset::AssetLoader for PrefsDefaultsLoader {
+    type Asset = PrefsDefaults;
+    type Settings = ();
+    type Error = std::io::Error;
+
+    async fn load(
+        &self,
+        reader: &mut dyn bevy::asset::io::Reader,
+        _settings: &(),
+        _load_context: &mut bevy::asset::LoadContext<'_>,
+    ) -> Result<PrefsDefaults, std::io::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        String::from_utf8(bytes)
+            .map(PrefsDefaults)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// The [`PrefsPlugin::default_asset`] of `T`, and whether loading the preferences is waiting for
+/// it.
+#[cfg(feature = "asset")]
+#[derive(Resource)]
+struct PrefsDefaultAsset<T> {
+    handle: bevy::asset::Handle<PrefsDefaults>,
+    waiting: bool,
+    _phantom: PhantomData<T>,
+}
+
+/// Loads the preferences if they are waiting for the [`PrefsPlugin::default_asset`] and it has
+/// been resolved, after copying its contents to [`PrefsSettings::asset_default`].
+#[cfg(feature = "asset")]
+fn load_after_default_asset<T: Prefs + TypePath + Send + Sync + 'static>(world: &mut World) {
+    use bevy::asset::{AssetServer, Assets, LoadState};
+
+    let default_asset = world.resource::<PrefsDefaultAsset<T>>();
+    if !default_asset.waiting {
+        return;
+    }
+
+    let handle = default_asset.handle.clone();
+    let asset_default = match world.resource::<AssetServer>().load_state(&handle) {
+        LoadState::Loaded => world
+            .resource::<Assets<PrefsDefaults>>()
+            .get(&handle)
+            .map(|defaults| defaults.0.clone()),
+        LoadState::Failed(e) => {
+            warn!("Failed to load default_asset of {}: {}", T::type_path(), e);
+            None
+        }
+        _ => return,
+    };
+
+    world.resource_mut::<PrefsDefaultAsset<T>>().waiting = false;
+    world.resource_mut::<PrefsSettings<T>>().asset_default = asset_default;
+    T::load(world);
+}
+
+/// Serialized preferences that haven't been saved yet, along with the settings to write them
+/// with.
+#[cfg(target_arch = "wasm32")]
+type PendingFlush<T> = Arc<Mutex<Option<(PrefsSettings<T>, String)>>>;
+
+/// Unsaved preferences that are written when the page is hidden, shared with the page listeners
+/// added by [`flush_on_hide`].
+#[cfg(target_arch = "wasm32")]
+#[derive(Resource)]
+struct PrefsFlush<T> {
+    pending: PendingFlush<T>,
+}
+
+/// Adds page listeners that write any pending preferences in `pending` when the page is hidden
+/// or closed.
+#[cfg(target_arch = "wasm32")]
+fn flush_on_hide<T: TypePath + 'static>(pending: PendingFlush<T>) {
+    use web_sys::wasm_bindgen::{closure::Closure, JsCast};
+
+    fn flush<T: TypePath>(pending: &Mutex<Option<(PrefsSettings<T>, String)>>) {
+        let pending = pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some((mut settings, serialized)) = pending {
+            if let Err(e) = settings.write(serialized) {
+                warn!("Failed to save {} when hidden: {}", T::type_path(), e);
+            }
+        }
+    }
+
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+
+    // Mobile browsers may discard a hidden page without firing `pagehide`, so also write when
+    // the page is hidden, which is also the last chance to do so on desktop.
+    if let Some(document) = window.document() {
+        let pending = pending.clone();
+        let target = document.clone();
+        let on_visibility_change = Closure::<dyn FnMut()>::new(move || {
+            if target.hidden() {
+                flush(&pending);
+            }
+        });
+        let _ = document.add_event_listener_with_callback(
+            "visibilitychange",
+            on_visibility_change.as_ref().unchecked_ref(),
+        );
+        // The listener lives as long as the page.
+        on_visibility_change.forget();
+    }
+
+    let on_page_hide = Closure::<dyn FnMut()>::new(move || flush(&pending));
+    let _ =
+        window.add_event_listener_with_callback("pagehide", on_page_hide.as_ref().unchecked_ref());
+    on_page_hide.forget();
+}
+
+/// Stores the current preferences to be written if the page is hidden before they are saved, or
+/// discards them if `stage` is `false`.
+///
+/// This is used by the derive macro, and is not part of the public API.
+#[doc(hidden)]
+#[cfg(target_arch = "wasm32")]
+pub fn __stage_flush<T>(world: &World, stage: bool)
+where
+    T: Prefs + Reflect + TypePath + GetTypeRegistration + Default,
+{
+    let Some(flush) = world.get_resource::<PrefsFlush<T>>() else {
+        return;
+    };
+
+    let settings = world.resource::<PrefsSettings<T>>();
+    let staged = (stage && !settings.read_only)
+        .then(|| {
+            settings.serialize(
+                &T::current(world),
+                &world.resource::<PrefsPreservedFields<T>>().preserved,
+            )
+        })
+        .transpose()
+        .inspect_err(|e| warn!("Failed to serialize {}: {}", T::type_path(), e))
+        .ok()
+        .flatten()
+        .map(|serialized| (settings.clone(), serialized));
+
+    *flush.pending.lock().unwrap_or_else(PoisonError::into_inner) = staged;
+}
+
+fn request_periodic_save<T: Send + Sync + 'static>(
+    settings: Res<PrefsSettings<T>>,
+    time: Option<Res<Time<Real>>>,
+    mut status: ResMut<PrefsStatus<T>>,
+    mut last_save: Local<(Duration, u64)>,
+) {
+    let (Some(interval), Some(time)) = (settings.periodic_save, time) else {
+        return;
+    };
+
+    let now = time.elapsed();
+    if !status.loaded || status.save_count != last_save.1 {
+        *last_save = (now, status.save_count);
+        return;
+    }
+
+    if now.saturating_sub(last_save.0) >= interval {
+        status.bypass_change_detection().save_requested = true;
+    }
+}
+
+fn handle_tasks<T: Prefs + Send + Sync + 'static>(
+    mut commands: Commands,
+    mut load_tasks: Query<(Entity, &mut LoadPrefsTask<T>)>,
+    mut save_tasks: Query<(Entity, &mut SavePrefsTask<T>)>,
+) {
+    for (entity, mut task) in &mut load_tasks {
+        if let Some(mut commands_queue) = block_on(future::poll_once(&mut task.task)) {
+            bevy::log::debug!("adding pref resource update commands");
+            commands.append(&mut commands_queue);
+            // The entity may already have been despawned by `remove_prefs` or `clear_prefs`.
+            commands.entity(entity).try_despawn();
+            commands.queue(start_queued_load::<T>);
+        }
+    }
+    for (entity, mut task) in &mut save_tasks {
+        if let Some(mut commands_queue) = block_on(future::poll_once(&mut task.task)) {
+            commands.append(&mut commands_queue);
+            commands.entity(entity).try_despawn();
+        }
+    }
+}
+
+/// Storage of the preferences of several types in sections of a shared file.
+mod sections {
+    use std::sync::{Mutex, MutexGuard, PoisonError};
+
+    use ron::ser::to_string;
+
+    use crate::{PrefsError, Scanner};
+
+    /// Serializes access to shared files, so that types saving at the same time don't overwrite
+    /// each other's sections.
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    pub(crate) fn lock() -> MutexGuard<'static, ()> {
+        LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the section called `name` of a shared file, if it exists.
+    pub(crate) fn get(shared: &str, name: &str) -> Result<Option<String>, PrefsError> {
+        Ok(split(shared)?
+            .into_iter()
+            .find(|(section_name, _)| section_name == name)
+            .map(|(_, section)| section.to_string()))
+    }
+
+    /// Replaces the section called `name` of a shared file, or removes it if `section` is `None`.
+    ///
+    /// Returns `None` if no sections remain. Returns an error if the shared file can't be split
+    /// into sections, rather than replacing the sections of other types.
+    pub(crate) fn set(
+        shared: Option<&str>,
+        name: &str,
+        section: Option<&str>,
+    ) -> Result<Option<String>, PrefsError> {
+        let mut sections = shared.map(split).transpose()?.unwrap_or_default();
+
+        match (sections.iter().position(|(n, _)| n == name), section) {
+            (Some(index), Some(section)) => sections[index].1 = section,
+            (Some(index), None) => _ = sections.remove(index),
+            (None, Some(section)) => sections.push((name.to_string(), section)),
+            (None, None) => {}
+        }
+
+        if sections.is_empty() {
+            return Ok(None);
+        }
+
+        let mut joined = String::from("{\n");
+        for (name, section) in sections {
+            let name = to_string(&name).unwrap_or_default();
+            joined.push_str(&format!("{}: {},\n", name, section.trim()));
+        }
+        joined.push_str("}\n");
+        Ok(Some(joined))
+    }
+
+    /// Splits a shared file into its sections, without deserializing them.
+    ///
+    /// Comments preceding a section are kept as part of it, so that headers such as the
+    /// preferences version survive.
+    fn split(shared: &str) -> Result<Vec<(String, &str)>, PrefsError> {
+        let mut scanner = Scanner {
+            s: shared,
+            pos: 0,
+            what: "shared preferences file",
+        };
+        let mut sections = Vec::new();
+
+        scanner.skip_trivia()?;
+        if scanner.peek().is_none() {
+            return Ok(sections);
+        }
+        scanner.expect(b'{')?;
+
+        loop {
+            scanner.skip_trivia()?;
+            if scanner.peek() == Some(b'}') {
+                scanner.pos += 1;
+                break;
+            }
+
+            let start = scanner.pos;
+            if scanner.peek() != Some(b'"') {
+                return Err(scanner.error("expected a section name"));
+            }
+            scanner.skip_string()?;
+            let name: String = ron::from_str(&shared[start..scanner.pos])
+                .map_err(|e| PrefsError::Deserialize(e.to_string()))?;
+
+            scanner.skip_trivia()?;
+            scanner.expect(b':')?;
+            scanner.skip_whitespace();
+
+            let start = scanner.pos;
+            let end = scanner.skip_value()?;
+            if end == start {
+                return Err(scanner.error("expected a section"));
+            }
+            sections.push((name, &shared[start..end]));
+
+            scanner.skip_trivia()?;
+            match scanner.peek() {
+                Some(b',') => scanner.pos += 1,
+                Some(b'}') => {}
+                _ => return Err(scanner.error("expected `,` or `}`")),
+            }
+        }
+
+        scanner.skip_trivia()?;
+        if scanner.peek().is_some() {
+            return Err(scanner.error("unexpected characters after the last section"));
+        }
+
+        Ok(sections)
+    }
+}
+
+/// Compressed storage of preferences in LocalStorage, and compressed archives.
+#[cfg(feature = "compact")]
+mod compact {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec};
+
+    use crate::PrefsError;
+
+    /// Marks stored preferences as compressed. RON never starts with `~`.
+    const PREFIX: &str = "~deflate:";
+
+    /// Compresses serialized preferences with DEFLATE and encodes them as base64.
+    pub(crate) fn encode(serialized: &str) -> String {
+        let compressed = compress_to_vec(serialized.as_bytes(), 9);
+        format!("{}{}", PREFIX, STANDARD.encode(compressed))
+    }
+
+    /// Decodes preferences produced by [`encode`], leaving uncompressed preferences as they are.
+    pub(crate) fn decode(stored: String) -> Result<String, PrefsError> {
+        let Some(encoded) = stored.strip_prefix(PREFIX) else {
+            return Ok(stored);
+        };
+
+        let error = PrefsError::Deserialize;
+        let compressed = STANDARD.decode(encoded).map_err(|e| error(e.to_string()))?;
+        let serialized = decompress_to_vec(&compressed).map_err(|e| error(e.to_string()))?;
+        String::from_utf8(serialized).map_err(|e| error(e.to_string()))
+    }
+}
+
+/// Loads preferences from the file at `dir/filename`.
+///
+/// Returns `Ok(None)` if the file doesn't exist.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_str(dir: &Path, filename: &str) -> Result<Option<String>, PrefsError> {
+    let path = dir.join(filename);
+
+    match std::fs::read_to_string(path) {
+        Ok(serialized) => Ok(Some(serialized)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Loads preferences from the first of `dirs` containing `filename`.
+///
+/// Directories that can't be read are skipped. If none of `dirs` contain `filename` and one of
+/// them couldn't be read, the first error is returned.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_str_from_any(dirs: &[PathBuf], filename: &str) -> Result<Option<String>, PrefsError> {
+    let mut first_error = None;
+
+    for dir in dirs {
+        match load_str(dir, filename) {
+            Ok(Some(serialized)) => return Ok(Some(serialized)),
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Failed to load preferences from {:?}: {}", dir, e);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    first_error.map_or(Ok(None), Err)
+}
+
+/// Loads preferences from the LocalStorage item at `key`.
+///
+/// Returns `Ok(None)` if the item doesn't exist.
+#[cfg(target_arch = "wasm32")]
+pub fn load_str(key: &str) -> Result<Option<String>, PrefsError> {
+    local_storage()?
+        .get_item(key)
+        .map_err(|e| PrefsError::other(format!("{:?}", e)))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Result<web_sys::Storage, PrefsError> {
+    match web_sys::window().map(|window| window.local_storage()) {
+        Some(Ok(Some(storage))) => Ok(storage),
+        _ => Err(PrefsError::StorageUnavailable),
+    }
+}
+
+/// Persists preferences to the file at `dir/filename`, creating `dir` first if it doesn't exist.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_str(dir: &Path, filename: &str, data: &str) -> Result<(), PrefsError> {
+    save_str_with_mode(dir, filename, data, true, None)
+}
+
+/// Persists preferences to the file at `dir/filename` like [`save_str`], only creating `dir` if
+/// `create_dirs` is set, and setting the Unix permissions of the file to `mode` before writing,
+/// if it is set.
+///
+/// `mode` is ignored on platforms other than Unix.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_str_with_mode(
+    dir: &Path,
+    filename: &str,
+    data: &str,
+    create_dirs: bool,
+    mode: Option<u32>,
+) -> Result<(), PrefsError> {
+    let path = dir.join(filename);
+
+    let write =
+        || open_with_mode(&path, false, mode).and_then(|mut file| file.write_all(data.as_bytes()));
+    let result = if create_dirs {
+        std::fs::create_dir_all(dir).and_then(|_| write())
+    } else {
+        write()
+    };
+
+    result.map_err(PrefsError::from)
+}
+
+/// Opens `path` for writing, truncating it or appending to it, and creating it with the Unix
+/// permissions `mode` if it doesn't exist.
+///
+/// The permissions of an existing file are changed to `mode` before anything is written to it.
+#[cfg(not(target_arch = "wasm32"))]
+fn open_with_mode(path: &Path, append: bool, mode: Option<u32>) -> std::io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.create(true).write(true);
+    match append {
+        true => options.append(true),
+        false => options.truncate(true),
+    };
+
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        options.mode(mode);
+        let file = options.open(path)?;
+        file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+        return Ok(file);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    options.open(path)
+}
+
+/// Deletes the preferences file at `dir/filename`, if it exists.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn delete_str(dir: &Path, filename: &str) {
+    let path = dir.join(filename);
+
+    match std::fs::remove_file(path) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to delete save file: {:?}", e),
+    }
+}
+
+/// Appends entries to the [`PrefsHistory`] log of the preferences file `prefs_filename` in `dir`,
+/// rotating it if it has grown too large.
+///
+/// The log is created with the Unix permissions `file_mode`, as in [`save_str_with_mode`].
+#[cfg(not(target_arch = "wasm32"))]
+pub fn append_history(
+    dir: &Path,
+    prefs_filename: &str,
+    history: &PrefsHistory,
+    entries: &[PrefsHistoryEntry],
+    file_mode: Option<u32>,
+) {
+    if entries.is_empty() {
+        return;
+    }
+
+    let filename = history.resolve_filename(prefs_filename);
+    let path = dir.join(&filename);
+
+    if std::fs::metadata(&path).is_ok_and(|m| m.len() >= history.max_bytes) {
+        if let Err(e) = std::fs::rename(&path, dir.join(format!("{}.old", filename))) {
+            warn!("Failed to rotate history file: {:?}", e);
+        }
+    }
+
+    let time = unix_time();
+
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&format!(
+            "{{\"time\":{},\"field\":{},\"old\":{},\"new\":{}}}\n",
+            time,
+            json_string(entry.field),
+            entry.old.as_deref().map_or("null".to_string(), json_string),
+            json_string(&entry.new)
+        ));
+    }
+
+    let result = open_with_mode(&path, true, file_mode)
+        .and_then(|mut file| file.write_all(lines.as_bytes()));
+
+    if let Err(e) = result {
+        warn!("Failed to append to history file: {:?}", e);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn json_string(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Persists preferences to the LocalStorage item at `key`.
+#[cfg(target_arch = "wasm32")]
+pub fn save_str(key: &str, data: &str) -> Result<(), PrefsError> {
+    use web_sys::wasm_bindgen::JsCast;
+
+    let storage = local_storage()?;
+
+    storage.set_item(key, data).map_err(|e| {
+        match e.dyn_ref::<web_sys::DomException>().map(|e| e.name()) {
+            Some(name) if name == "QuotaExceededError" || name == "NS_ERROR_DOM_QUOTA_REACHED" => {
+                PrefsError::QuotaExceeded
+            }
+            _ => PrefsError::other(format!("{:?}", e)),
+        }
+    })
+}
+
+/// Deletes the LocalStorage item at `key`, if it exists.
+#[cfg(target_arch = "wasm32")]
+pub fn delete_str(key: &str) {
+    let Ok(storage) = local_storage() else {
+        warn!("Failed to delete save file: no storage.");
+        return;
+    };
+
+    if let Err(e) = storage.remove_item(key) {
+        warn!("Failed to delete save file: {:?}", e);
+    }
+}
+
+/// Deserializes preferences
+///
+/// Preferences are deserialized onto `T::default()`, so files written before a field was added
+/// will still load. Missing fields, including fields of nested structs, keep their default
+/// values, and map entries are merged into the default map.
+///
+/// Unknown fields, such as those belonging to preferences that have since been removed, are
+/// ignored rather than causing an error.
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_simple_prefs::{deserialize, Prefs};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     volume: Volume,
+///     graphics: Graphics,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, Eq, PartialEq, Debug, Default)]
+/// struct Volume(u32);
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug)]
+/// struct Graphics {
+///     vsync: bool,
+///     fov: f32,
+/// }
+///
+/// impl Default for Graphics {
+///     fn default() -> Self {
+///         Self { vsync: true, fov: 90. }
+///     }
+/// }
+///
+/// let prefs: ExamplePrefs = deserialize("(volume: (30), removed: (true))").unwrap();
+/// assert_eq!(prefs.volume, Volume(30));
+/// assert_eq!(prefs.graphics, Graphics::default());
+///
+/// let prefs: ExamplePrefs = deserialize("(graphics: (vsync: false, removed: 1))").unwrap();
+/// assert_eq!(prefs.volume, Volume::default());
+/// assert_eq!(prefs.graphics, Graphics { vsync: false, fov: 90. });
+/// ```
+///
+/// Likewise, arrays with fewer elements than their type, such as those written before the array
+/// grew, only replace its first elements, and the remaining elements keep their default values.
+/// Extra elements are ignored unless [`FormatOptions::strict`] is set. Arrays inside an `Option`,
+/// `Vec`, `HashMap` or enum must have exactly the right number of elements, or the preferences
+/// fail to deserialize, so consider using a `Vec` instead of arrays that may grow there.
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_simple_prefs::{deserialize, Prefs};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     hotbar: Hotbar,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug)]
+/// struct Hotbar([char; 4]);
+///
+/// impl Default for Hotbar {
+///     fn default() -> Self {
+///         Self(['1', '2', '3', '4'])
+///     }
+/// }
+///
+/// // Written when the hotbar had two slots
+/// let prefs: ExamplePrefs = deserialize("(hotbar: (('q', 'e')))").unwrap();
+/// assert_eq!(prefs.hotbar, Hotbar(['q', 'e', '3', '4']));
+/// ```
+pub fn deserialize<T: Prefs + Reflect + GetTypeRegistration + Default>(
+    serialized: &str,
+) -> Result<T, PrefsError> {
+    deserialize_with_options(serialized, &FormatOptions::default())
+}
+
+/// Deserializes preferences using the given [`FormatOptions`], also returning any
+/// [`PreservedFields`].
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_simple_prefs::{deserialize_preserving, serialize_preserving, Prefs};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// #[prefs(version = 1)]
+/// struct ExamplePrefs {
+///     volume: Volume,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+/// struct Volume(u32);
+///
+/// // Written by a newer build that added `fov`.
+/// let newer = "// version: 2\n(volume: (20), fov: (110.0))";
+///
+/// let (prefs, preserved) =
+///     deserialize_preserving::<ExamplePrefs>(newer, &default()).unwrap();
+/// assert_eq!(prefs.volume, Volume(20));
+/// assert_eq!(preserved.fields, [("fov".to_string(), "(110.0)".to_string())]);
+///
+/// let saved = serialize_preserving(&prefs, &default(), &preserved).unwrap();
+/// assert!(saved.starts_with("// version: 2\n"));
+/// assert!(saved.contains("fov: (110.0)"));
+/// ```
+pub fn deserialize_preserving<T: Prefs + Reflect + GetTypeRegistration + Default>(
+    serialized: &str,
+    options: &FormatOptions,
+) -> Result<(T, PreservedFields), PrefsError> {
+    deserialize_preserving_onto(T::default(), serialized, options)
+}
+
+/// Deserializes preferences like [`deserialize_preserving`], applying them on top of `base`
+/// rather than `T::default()`.
+fn deserialize_preserving_onto<T: Prefs + Reflect + GetTypeRegistration>(
+    base: T,
+    serialized: &str,
+    options: &FormatOptions,
+) -> Result<(T, PreservedFields), PrefsError> {
+    check_schema_hash::<T>(serialized, options)?;
+    check_limits(serialized, options)?;
+
+    let version = read_version(serialized);
+    if version <= T::VERSION {
+        let value = deserialize_struct(base, serialized, options)?;
+        let preserved = PreservedFields {
+            transient: transient_fields::<T>(serialized),
+            ..Default::default()
+        };
+        return Ok((value, preserved));
+    }
+
+    // Unknown fields of newer preferences files are preserved rather than rejected.
+    let options = FormatOptions {
+        strict: false,
+        ..options.clone()
+    };
+    let value = deserialize_struct(base, serialized, &options)?;
+
+    let known: &[&str] = match T::get_type_registration().type_info() {
+        TypeInfo::Struct(info) => info.field_names(),
+        _ => &[],
+    };
+    let fields = top_level_fields(serialized)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(name, _)| !known.contains(name) && !T::FIELD_KEYS.contains(name))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+    Ok((
+        value,
+        PreservedFields {
+            version,
+            fields,
+            transient: transient_fields::<T>(serialized),
+            overridden: Vec::new(),
+        },
+    ))
+}
+
+/// Returns the keys and raw values of the transient fields stored in `serialized`.
+fn transient_fields<T: Prefs>(serialized: &str) -> Vec<(String, String)> {
+    if T::TRANSIENT_FIELDS.is_empty() {
+        return Vec::new();
+    }
+
+    let keys = FieldKeys::of::<T>();
+    top_level_fields(serialized)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(key, _)| T::TRANSIENT_FIELDS.contains(&keys.name(key)))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Splits the top-level struct of a RON document into field names and raw values.
+///
+/// Returns `None` if the document doesn't look like a struct. The document is assumed to have
+/// already been successfully deserialized, so little validation is done.
+fn top_level_fields(serialized: &str) -> Option<Vec<(&str, &str)>> {
+    let mut scanner = Scanner {
+        s: serialized,
+        pos: 0,
+        what: "preferences file",
+    };
+
+    // Skip `#![enable(...)]` attributes, which may contain parentheses.
+    scanner.skip_trivia().ok()?;
+    while serialized[scanner.pos..].starts_with("#!") {
+        scanner.pos += serialized[scanner.pos..].find(']')? + 1;
+        scanner.skip_trivia().ok()?;
+    }
+
+    // An optional struct name, followed by the opening parenthesis.
+    scanner.skip_identifier();
+    scanner.skip_trivia().ok()?;
+    scanner.expect(b'(').ok()?;
+
+    let mut fields = Vec::new();
+    loop {
+        scanner.skip_trivia().ok()?;
+        if scanner.peek()? == b')' {
+            return Some(fields);
+        }
+
+        let start = scanner.pos;
+        scanner.skip_identifier();
+        let name = serialized[start..scanner.pos].trim_start_matches("r#");
+
+        scanner.skip_trivia().ok()?;
+        scanner.expect(b':').ok()?;
+        scanner.skip_trivia().ok()?;
+
+        let start = scanner.pos;
+        let end = scanner.skip_value().ok()?;
+        fields.push((name, &serialized[start..end]));
+
+        scanner.skip_trivia().ok()?;
+        if scanner.peek() == Some(b',') {
+            scanner.pos += 1;
+        }
+    }
+}
+
+/// Finds the extent of RON values without deserializing them.
+///
+/// All of the delimiters are ASCII, so this works on bytes.
+struct Scanner<'a> {
+    s: &'a str,
+    pos: usize,
+    /// What is being scanned, for error messages.
+    what: &'static str,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<u8> {
+        self.s.as_bytes().get(self.pos + offset).copied()
+    }
+
+    fn error(&self, message: &str) -> PrefsError {
+        PrefsError::Deserialize(format!("{} at byte {} of {}", message, self.pos, self.what))
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), PrefsError> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("expected `{}`", byte as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn at_comment(&self) -> bool {
+        self.peek() == Some(b'/') && matches!(self.peek_at(1), Some(b'/' | b'*'))
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_trivia(&mut self) -> Result<(), PrefsError> {
+        loop {
+            self.skip_whitespace();
+            if !self.at_comment() {
+                return Ok(());
+            }
+            self.skip_comment()?;
+        }
+    }
+
+    fn skip_comment(&mut self) -> Result<(), PrefsError> {
+        if self.peek_at(1) == Some(b'/') {
+            while self.peek().is_some_and(|b| b != b'\n') {
+                self.pos += 1;
+            }
+            return Ok(());
+        }
+
+        // Block comments nest in RON.
+        let mut depth = 0;
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(b'/'), Some(b'*')) => {
+                    depth += 1;
+                    self.pos += 2;
+                }
+                (Some(b'*'), Some(b'/')) => {
+                    depth -= 1;
+                    self.pos += 2;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                (Some(_), _) => self.pos += 1,
+                (None, _) => return Err(self.error("unterminated comment")),
+            }
+        }
+    }
+
+    fn skip_string(&mut self) -> Result<(), PrefsError> {
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                Some(b'\\') => self.pos += 2,
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some(_) => self.pos += 1,
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn skip_char(&mut self) -> Result<(), PrefsError> {
+        self.pos += 1;
+        loop {
+            match self.peek() {
+                Some(b'\\') => self.pos += 2,
+                Some(b'\'') => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                Some(_) => self.pos += 1,
+                None => return Err(self.error("unterminated char")),
+            }
+        }
+    }
+
+    /// Skips an identifier, including raw identifiers, which may also contain `.`, `+` and `-`.
+    fn skip_identifier(&mut self) {
+        while self.peek().is_some_and(|b| {
+            b.is_ascii_alphanumeric() || matches!(b, b'_' | b'#' | b'.' | b'+' | b'-')
+        }) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skips a raw string such as `r#"..."#`, or just the `r` of a raw identifier.
+    fn skip_raw_string(&mut self) -> Result<(), PrefsError> {
+        let mut hashes = 0;
+        while self.peek_at(1 + hashes) == Some(b'#') {
+            hashes += 1;
+        }
+        if self.peek_at(1 + hashes) != Some(b'"') {
+            self.pos += 1;
+            return Ok(());
+        }
+
+        self.pos += 2 + hashes;
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        match self.s[self.pos..].find(&terminator) {
+            Some(offset) => {
+                self.pos += offset + terminator.len();
+                Ok(())
+            }
+            None => Err(self.error("unterminated raw string")),
+        }
+    }
+
+    /// Skips a single value, including any comments preceding it, and returns the position
+    /// of the end of its last token.
+    fn skip_value(&mut self) -> Result<usize, PrefsError> {
+        let mut depth = 0usize;
+        let mut end = self.pos;
+        loop {
+            if self.at_comment() {
+                self.skip_comment()?;
+                continue;
+            }
+
+            match self.peek() {
+                Some(b',') | None if depth == 0 => return Ok(end),
+                Some(b')' | b']' | b'}') if depth == 0 => return Ok(end),
+                None => return Err(self.error("unterminated section")),
+                Some(b'(' | b'[' | b'{') => {
+                    depth += 1;
+                    self.pos += 1;
+                }
+                Some(b')' | b']' | b'}') => {
+                    depth -= 1;
+                    self.pos += 1;
+                }
+                Some(b'"') => self.skip_string()?,
+                Some(b'\'') => self.skip_char()?,
+                Some(b'r')
+                    if !self.s.as_bytes()[..self.pos]
+                        .last()
+                        .is_some_and(|b| b.is_ascii_alphanumeric() || *b == b'_') =>
+                {
+                    self.skip_raw_string()?
+                }
+                Some(b) if b.is_ascii_whitespace() => {
+                    self.pos += 1;
+                    continue;
+                }
+                Some(_) => self.pos += 1,
+            }
+            end = self.pos;
+        }
+    }
+}
+
+/// Deserializes preferences using the given [`FormatOptions`].
+///
+/// With the `json` feature, preferences containing a JSON object are also accepted, so that
+/// preferences files written as JSON are migrated to RON the next time they are saved.
+///
+/// Enum variants with data must be written in serde's externally tagged form, such as
+/// `{ "Custom": { "multiplier": 1.5 } }`. Other representations, such as internally tagged
+/// enums, can't be read.
+///
+/// ```rust
+/// # #[cfg(feature = "json")]
+/// # {
+/// use bevy::prelude::*;
+/// use bevy_simple_prefs::{deserialize, Prefs};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     volume: Volume,
+///     difficulty: Difficulty,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+/// struct Volume(u32);
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+/// enum Difficulty {
+///     #[default]
+///     Normal,
+///     Custom { multiplier: f32 },
+/// }
+///
+/// let prefs: ExamplePrefs = deserialize(r#"{ "volume": 20 }"#).unwrap();
+/// assert_eq!(prefs.volume, Volume(20));
+///
+/// let prefs: ExamplePrefs =
+///     deserialize(r#"{ "difficulty": { "Custom": { "multiplier": 1.5 } } }"#).unwrap();
+/// assert_eq!(prefs.difficulty, Difficulty::Custom { multiplier: 1.5 });
+/// # }
+/// ```
+pub fn deserialize_with_options<T: Prefs + Reflect + GetTypeRegistration + Default>(
+    serialized: &str,
+    options: &FormatOptions,
+) -> Result<T, PrefsError> {
+    check_schema_hash::<T>(serialized, options)?;
+    check_limits(serialized, options)?;
+    deserialize_struct(T::default(), serialized, options)
+}
+
+/// Reads preferences from `reader`, such as [`std::io::stdin`], and deserializes them using the
+/// given [`FormatOptions`].
+///
+/// See [`serialize_to_writer`].
+pub fn deserialize_from_reader<T: Prefs + Reflect + GetTypeRegistration + Default>(
+    mut reader: impl std::io::Read,
+    options: &FormatOptions,
+) -> Result<T, PrefsError> {
+    let mut serialized = String::new();
+    reader.read_to_string(&mut serialized)?;
+    deserialize_with_options(&serialized, options)
+}
+
+/// Returns [`PrefsError::LimitExceeded`] if [`FormatOptions::limits`] is set and the preferences
+/// file exceeds them.
+///
+/// Files that can't be parsed are left for deserialization to report.
+fn check_limits(serialized: &str, options: &FormatOptions) -> Result<(), PrefsError> {
+    let Some(limits) = &options.limits else {
+        return Ok(());
+    };
+    let mut counter = LimitCounter {
+        limits,
+        // The preferences struct itself is at depth `0`.
+        depth: -1,
+        fields: 0,
+        exceeded: None,
+    };
+
+    #[cfg(feature = "json")]
+    if serialized.trim_start().starts_with('{') {
+        let _ = counter.deserialize(&mut serde_json::Deserializer::from_str(serialized));
+        return counter
+            .exceeded
+            .map_or(Ok(()), |e| Err(PrefsError::LimitExceeded(e)));
+    }
+
+    let ron_options = ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME);
+    if let Ok(mut deserializer) = ron::Deserializer::from_str_with_options(serialized, ron_options)
+    {
+        let _ = counter.deserialize(&mut deserializer);
+    }
+    counter
+        .exceeded
+        .map_or(Ok(()), |e| Err(PrefsError::LimitExceeded(e)))
+}
+
+/// Walks a self-describing document, stopping as soon as it exceeds the [`DeserializeLimits`].
+struct LimitCounter<'a> {
+    limits: &'a DeserializeLimits,
+    depth: isize,
+    fields: usize,
+    exceeded: Option<String>,
+}
+
+impl LimitCounter<'_> {
+    fn fail<E: serde::de::Error>(&mut self, message: String) -> E {
+        let e = E::custom(&message);
+        self.exceeded = Some(message);
+        e
+    }
+
+    fn enter<E: serde::de::Error>(&mut self) -> Result<(), E> {
+        self.depth += 1;
+        if self.depth > self.limits.max_depth as isize {
+            return Err(self.fail(format!("nesting is deeper than {}", self.limits.max_depth)));
+        }
+        Ok(())
+    }
+
+    fn count<E: serde::de::Error>(&mut self) -> Result<(), E> {
+        self.fields += 1;
+        if self.fields > self.limits.max_fields {
+            return Err(self.fail(format!("more than {} fields", self.limits.max_fields)));
+        }
+        Ok(())
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for &mut LimitCounter<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for &mut LimitCounter<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i128<E>(self, _: i128) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u128<E>(self, _: u128) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_char<E>(self, _: char) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_bytes<E>(self, _: &[u8]) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_none<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        self.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        self.enter()?;
+        while seq.next_element_seed(&mut *self)?.is_some() {
+            self.count()?;
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        self.enter()?;
+        while map.next_key_seed(&mut *self)?.is_some() {
+            map.next_value_seed(&mut *self)?;
+            self.count()?;
+        }
+        self.depth -= 1;
+        Ok(())
+    }
+}
+
+/// Returns [`PrefsError::SchemaMismatch`] if [`FormatOptions::schema_hash`] is set and the
+/// preferences file was written with a different shape.
+fn check_schema_hash<T: Prefs + Reflect + GetTypeRegistration>(
+    serialized: &str,
+    options: &FormatOptions,
+) -> Result<(), PrefsError> {
+    if !options.schema_hash || read_version(serialized) != T::VERSION {
+        return Ok(());
+    }
+
+    let expected = schema_hash::<T>();
+    match read_schema_hash(serialized) {
+        Some(found) if found != expected => Err(PrefsError::SchemaMismatch { expected, found }),
+        _ => Ok(()),
+    }
+}
+
+/// Deserializes preferences, applying them on top of `base`.
+fn deserialize_struct<T: Prefs + Reflect + GetTypeRegistration>(
+    mut base: T,
+    serialized: &str,
+    options: &FormatOptions,
+) -> Result<T, PrefsError> {
+    deserialize_onto(&mut base, serialized, options)?;
+    Ok(base)
+}
+
+/// Deserializes preferences, applying them to `base`, which is left untouched if they can't be
+/// deserialized.
+fn deserialize_onto<T: Prefs + Reflect + GetTypeRegistration>(
+    base: &mut T,
+    serialized: &str,
+    options: &FormatOptions,
+) -> Result<(), PrefsError> {
+    let registry = options_registry::<T>(options);
+    let registration = registry.get(TypeId::of::<T>()).unwrap();
+
+    let mut primitives = Vec::new();
+    let de = TolerantDeserializer {
+        registration,
+        registry: &registry,
+        strict: options.strict,
+        keys: FieldKeys::of::<T>(),
+        secrets: Secrets {
+            fields: T::SECRET_FIELDS,
+            options,
+        },
+        primitives: Some(&mut primitives),
+    };
+
+    // A RON file never starts with `{`, because the preferences are a struct.
+    #[cfg(feature = "json")]
+    if serialized.trim_start().starts_with('{') {
+        let mut deserializer = serde_json::Deserializer::from_str(serialized);
+        let dynamic_struct = de
+            .deserialize(&mut deserializer)
+            .map_err(|e| PrefsError::Deserialize(format!("invalid JSON: {}", e)))?;
+
+        apply_partial(base.as_partial_reflect_mut(), &*dynamic_struct);
+        apply_primitives(base.as_partial_reflect_mut(), primitives);
+        return Ok(());
+    }
+
+    // Extensions used when writing the file are enabled by its header. `IMPLICIT_SOME` can be
+    // enabled regardless, because explicit `Some(...)` values are still accepted.
+    let ron_options = ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME);
+    let mut deserializer = ron::Deserializer::from_str_with_options(serialized, ron_options)
+        .map_err(|e| PrefsError::Deserialize(e.to_string()))?;
+
+    let dynamic_struct = de
+        .deserialize(&mut deserializer)
+        .map_err(|e| PrefsError::Deserialize(deserializer.span_error(e).to_string()))?;
+
+    apply_partial(base.as_partial_reflect_mut(), &*dynamic_struct);
+    apply_primitives(base.as_partial_reflect_mut(), primitives);
+    Ok(())
+}
+
+/// Applies `value` to `target` like [`PartialReflect::apply`], except that arrays with fewer
+/// elements than `target` only replace its first elements.
+fn apply_partial(target: &mut dyn PartialReflect, value: &dyn PartialReflect) {
+    match value.reflect_ref() {
+        ReflectRef::Struct(value) => {
+            if let ReflectMut::Struct(target) = target.reflect_mut() {
+                for (index, field) in value.iter_fields().enumerate() {
+                    let name = value.name_at(index).unwrap_or_default();
+                    if let Some(target_field) = target.field_mut(name) {
+                        apply_partial(target_field, field);
+                    }
+                }
+                return;
+            }
+        }
+        ReflectRef::TupleStruct(value) => {
+            if let ReflectMut::TupleStruct(target) = target.reflect_mut() {
+                for (index, field) in value.iter_fields().enumerate() {
+                    if let Some(target_field) = target.field_mut(index) {
+                        apply_partial(target_field, field);
+                    }
+                }
+                return;
+            }
+        }
+        ReflectRef::Tuple(value) => {
+            if let ReflectMut::Tuple(target) = target.reflect_mut() {
+                for (index, field) in value.iter_fields().enumerate() {
+                    if let Some(target_field) = target.field_mut(index) {
+                        apply_partial(target_field, field);
+                    }
+                }
+                return;
+            }
+        }
+        ReflectRef::Array(value) => {
+            if let ReflectMut::Array(target) = target.reflect_mut() {
+                for (index, element) in value.iter().enumerate() {
+                    if let Some(target_element) = target.get_mut(index) {
+                        apply_partial(target_element, element);
+                    }
+                }
+                return;
+            }
+        }
+        _ => {}
+    }
+    target.apply(value);
+}
+
+/// Deserializes a reflected value, ignoring unknown fields unless `strict` is set, and leaving out
+/// missing fields of any structs it contains.
+///
+/// Structs that provide their own `Deserialize` implementation are deserialized as usual.
+struct TolerantDeserializer<'a> {
+    registration: &'a TypeRegistration,
+    registry: &'a TypeRegistry,
+    strict: bool,
+    keys: FieldKeys,
+    secrets: Secrets<'a>,
+    /// See [`PrefsStructDeserializer::primitives`].
+    primitives: Option<&'a mut Vec<(&'static str, PrimitiveValue)>>,
+}
+
+impl<'de> DeserializeSeed<'de> for TolerantDeserializer<'_> {
+    type Value = Box<dyn PartialReflect>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        match self.registration.type_info() {
+            TypeInfo::Struct(info) if self.registration.data::<ReflectDeserialize>().is_none() => {
+                let de = PrefsStructDeserializer {
+                    info,
+                    registry: self.registry,
+                    strict: self.strict,
+                    keys: self.keys,
+                    secrets: self.secrets,
+                    primitives: self.primitives,
+                };
+                Ok(Box::new(de.deserialize(deserializer)?))
+            }
+            _ => TypedReflectDeserializer::with_processor(
+                self.registration,
+                self.registry,
+                &mut PartialArrays {
+                    strict: self.strict,
+                },
+            )
+            .deserialize(deserializer),
+        }
+    }
+}
+
+/// Allows arrays to have fewer elements than their type, such as arrays written before they
+/// grew, and ignores any extra elements unless `strict` is set.
+///
+/// The missing elements are left out, so that [`apply_partial`] keeps their previous values.
+/// That isn't possible for arrays in lists, maps, sets and enums, so those are deserialized as
+/// usual and must have exactly the right number of elements.
+struct PartialArrays {
+    strict: bool,
+}
+
+impl ReflectDeserializerProcessor for PartialArrays {
+    fn try_deserialize<'de, D: Deserializer<'de>>(
+        &mut self,
+        registration: &TypeRegistration,
+        registry: &TypeRegistry,
+        deserializer: D,
+    ) -> Result<Result<Box<dyn PartialReflect>, D>, D::Error> {
+        match registration.type_info() {
+            TypeInfo::Array(info) => {
+                let mut array = deserializer.deserialize_tuple(
+                    info.capacity(),
+                    PartialArrayVisitor {
+                        info,
+                        registry,
+                        strict: self.strict,
+                    },
+                )?;
+                array.set_represented_type(Some(registration.type_info()));
+                Ok(Ok(Box::new(array)))
+            }
+            TypeInfo::List(_) | TypeInfo::Map(_) | TypeInfo::Set(_) | TypeInfo::Enum(_) => {
+                TypedReflectDeserializer::new(registration, registry)
+                    .deserialize(deserializer)
+                    .map(Ok)
+            }
+            _ => Ok(Err(deserializer)),
+        }
+    }
+}
+
+struct PartialArrayVisitor<'a> {
+    info: &'static ArrayInfo,
+    registry: &'a TypeRegistry,
+    strict: bool,
+}
+
+impl<'de> Visitor<'de> for PartialArrayVisitor<'_> {
+    type Value = DynamicArray;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "an array of {} elements", self.info.capacity())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let registration = self.registry.get(self.info.item_ty().id()).ok_or_else(|| {
+            A::Error::custom(format!(
+                "no registration for {}",
+                self.info.item_ty().path()
+            ))
+        })?;
+
+        let mut processor = PartialArrays {
+            strict: self.strict,
+        };
+        let mut elements = Vec::with_capacity(self.info.capacity());
+        while elements.len() < self.info.capacity() {
+            let seed = TypedReflectDeserializer::with_processor(
+                registration,
+                self.registry,
+                &mut processor,
+            );
+            let Some(element) = seq.next_element_seed(seed)? else {
+                break;
+            };
+            elements.push(element);
+        }
+
+        let mut extra = 0;
+        while seq.next_element::<IgnoredAny>()?.is_some() {
+            extra += 1;
+        }
+        if extra > 0 {
+            if self.strict {
+                return Err(A::Error::invalid_length(elements.len() + extra, &self));
+            }
+            warn!(
+                "Ignoring {} extra elements of {} in prefs",
+                extra,
+                self.info.type_path()
+            );
+        }
+
+        Ok(DynamicArray::new(elements.into_boxed_slice()))
+    }
+}
+
+/// Deserializes a struct, skipping any fields it doesn't contain unless `strict` is set.
+struct PrefsStructDeserializer<'a> {
+    info: &'static StructInfo,
+    registry: &'a TypeRegistry,
+    strict: bool,
+    keys: FieldKeys,
+    secrets: Secrets<'a>,
+    /// If set, fields wrapping a [`Primitive`] are collected here to be applied with
+    /// [`apply_primitives`], rather than added to the `DynamicStruct`, which takes longer.
+    primitives: Option<&'a mut Vec<(&'static str, PrimitiveValue)>>,
+}
+
+impl<'de> DeserializeSeed<'de> for PrefsStructDeserializer<'_> {
+    type Value = DynamicStruct;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct(
+            self.info.type_path_table().ident().unwrap_or_default(),
+            self.info.field_names(),
+            self,
+        )
+    }
+}
+
+impl<'de> Visitor<'de> for PrefsStructDeserializer<'_> {
+    type Value = DynamicStruct;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a preferences struct")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut dynamic_struct = DynamicStruct::default();
+
+        while let Some(key) = map.next_key_seed(FieldNameDeserializer)? {
+            let key = self.keys.name(&key).to_string();
+            let Some(field) = self.info.field(&key) else {
+                if self.strict {
+                    return Err(A::Error::unknown_field(&key, self.info.field_names()));
+                }
+
+                warn!("Ignoring unknown field in prefs: {}", key);
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            };
+
+            let registration = self
+                .registry
+                .get(field.type_id())
+                .ok_or_else(|| A::Error::custom(format!("no registration for field `{}`", key)))?;
+
+            let value = if self.secrets.fields.contains(&key.as_str()) {
+                let sealed: String = map.next_value()?;
+                self.secrets
+                    .open(&sealed, registration, self.registry)
+                    .map_err(|e| A::Error::custom(format!("field `{}`: {}", key, e)))?
+            } else if let Some((ident, primitive)) = primitive_newtype(registration) {
+                let value =
+                    map.next_value_seed(PrimitiveNewtypeDeserializer { ident, primitive })?;
+                if let Some(primitives) = &mut self.primitives {
+                    primitives.push((field.name(), value));
+                    continue;
+                }
+
+                let mut newtype = DynamicTupleStruct::default();
+                newtype.insert_boxed(value.into_boxed());
+                newtype.set_represented_type(Some(registration.type_info()));
+                Box::new(newtype)
+            } else {
+                map.next_value_seed(TolerantDeserializer {
+                    registration,
+                    registry: self.registry,
+                    strict: self.strict,
+                    keys: FieldKeys::NONE,
+                    secrets: self.secrets.nested(),
+                    primitives: None,
+                })?
+            };
+            dynamic_struct.insert_boxed(&key, value);
+        }
+
+        Ok(dynamic_struct)
+    }
+}
+
+/// Deserializes a struct field name, which RON represents as an identifier.
+struct FieldNameDeserializer;
+
+impl<'de> DeserializeSeed<'de> for FieldNameDeserializer {
+    type Value = String;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl Visitor<'_> for FieldNameDeserializer {
+    type Value = String;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a field name")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(v.to_string())
+    }
+}
+
+/// Returns a registry containing `T`, the types it depends on, and common std types.
+///
+/// Building the registry takes a significant part of the time spent serializing and
+/// deserializing, so it's only built once for each type.
+fn prefs_registry<T: GetTypeRegistration>() -> RwLockReadGuard<'static, TypeRegistry> {
+    static REGISTRIES: OnceLock<Mutex<HashMap<TypeId, &'static TypeRegistryArc>>> = OnceLock::new();
+
+    let registry = *REGISTRIES
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(TypeId::of::<T>())
+        .or_insert_with(|| {
+            let mut registry = TypeRegistry::new();
+            register_prefs_types::<T>(&mut registry);
+            Box::leak(Box::new(TypeRegistryArc {
+                internal: Arc::new(RwLock::new(registry)),
+            }))
+        });
+    registry.read()
+}
+
+/// Registers `T`, the types it depends on, and common std types.
+fn register_prefs_types<T: GetTypeRegistration>(registry: &mut TypeRegistry) {
+    registry.register::<T>();
+    registry.register::<String>();
+    registry.register::<Duration>();
+    registry.register::<std::path::PathBuf>();
+}
+
+/// Returns [`FormatOptions::type_registry`] with `T` registered in it, or a registry containing
+/// `T` and the types it depends on if there is none.
+fn options_registry<T: GetTypeRegistration>(
+    options: &FormatOptions,
+) -> RwLockReadGuard<'_, TypeRegistry> {
+    let Some(type_registry) = &options.type_registry else {
+        return prefs_registry::<T>();
+    };
+
+    if !type_registry.read().contains(TypeId::of::<T>()) {
+        register_prefs_types::<T>(&mut type_registry.write());
+    }
+    type_registry.read()
+}
+
+/// Serialize preferences
+///
+/// Fields may contain common std types, such as `String`, `Duration`, `PathBuf`, `Option`, `Vec`,
+/// `HashMap` and fixed-size arrays. Note that some std types, such as `IpAddr`, don't implement `Reflect`, and need
+/// to be stored in another form, such as a `String`.
+///
+/// ```rust
+/// use bevy::{prelude::*, utils::HashMap};
+/// use bevy_simple_prefs::{deserialize, serialize, Prefs};
+/// use std::{path::PathBuf, time::Duration};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     autosave: Autosave,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+/// struct Autosave {
+///     name: String,
+///     interval: Duration,
+///     dir: PathBuf,
+///     last_saved_secs: Option<u64>,
+///     recent: Vec<PathBuf>,
+///     intervals: HashMap<String, Duration>,
+///     slots: [u8; 3],
+/// }
+///
+/// let autosave = Autosave {
+///     name: "Slot 1".to_string(),
+///     interval: Duration::from_millis(1500),
+///     dir: PathBuf::from("saves/slot_1"),
+///     last_saved_secs: Some(1_700_000_000),
+///     recent: vec![PathBuf::from("saves/slot_0")],
+///     intervals: HashMap::from([("quick".to_string(), Duration::from_secs(30))]),
+///     slots: [1, 0, 2],
+/// };
+///
+/// let serialized = serialize(&ExamplePrefs {
+///     autosave: autosave.clone(),
+/// })
+/// .unwrap();
+/// let prefs: ExamplePrefs = deserialize(&serialized).unwrap();
+/// assert_eq!(prefs.autosave, autosave);
+/// ```
+///
+/// Enums may have unit, tuple, and struct variants, which are written as `Easy`,
+/// `Preset(3, "Veteran")`, and `Custom(multiplier: 1.5)` respectively, with or without
+/// [`FormatOptions::extensions`].
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_simple_prefs::{deserialize, serialize, Prefs};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     difficulty: Difficulty,
+///     fallback: FallbackDifficulty,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+/// enum Difficulty {
+///     #[default]
+///     Easy,
+///     Preset(u8, String),
+///     Custom { multiplier: f32 },
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+/// struct FallbackDifficulty(Option<Difficulty>);
+///
+/// for difficulty in [
+///     Difficulty::Easy,
+///     Difficulty::Preset(3, "Veteran".to_string()),
+///     Difficulty::Custom { multiplier: 1.5 },
+/// ] {
+///     let serialized = serialize(&ExamplePrefs {
+///         difficulty: difficulty.clone(),
+///         fallback: FallbackDifficulty(Some(difficulty.clone())),
+///     })
+///     .unwrap();
+///     let prefs: ExamplePrefs = deserialize(&serialized).unwrap();
+///     assert_eq!(prefs.difficulty, difficulty);
+///     assert_eq!(prefs.fallback.0, Some(difficulty));
+/// }
+///
+/// let prefs: ExamplePrefs = deserialize("(difficulty: Custom(multiplier: 2.0))").unwrap();
+/// assert_eq!(prefs.difficulty, Difficulty::Custom { multiplier: 2.0 });
+/// ```
+pub fn serialize<T: Prefs + Reflect + GetTypeRegistration>(
+    to_save: &T,
+) -> Result<String, PrefsError> {
+    serialize_with_options(to_save, &FormatOptions::default())
+}
+
+/// Serialize preferences using the given [`FormatOptions`].
+pub fn serialize_with_options<T: Prefs + Reflect + GetTypeRegistration>(
+    to_save: &T,
+    options: &FormatOptions,
+) -> Result<String, PrefsError> {
+    serialize_preserving(to_save, options, &PreservedFields::default())
+}
+
+/// Serializes preferences using the given [`FormatOptions`] and writes them to `writer`, such as
+/// [`std::io::stdout`].
+///
+/// Together with [`deserialize_from_reader`], this allows preferences to be processed by
+/// command-line tools.
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_simple_prefs::{deserialize_from_reader, serialize_to_writer, Prefs};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     volume: Volume,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, Default)]
+/// struct Volume(u32);
+///
+/// // With `std::io::stdin().lock()` rather than a string, this reads the preferences from a pipe.
+/// let input = "(volume: (20))";
+/// let mut prefs: ExamplePrefs = deserialize_from_reader(input.as_bytes(), &default()).unwrap();
+/// prefs.volume.0 += 10;
+///
+/// // With `std::io::stdout()` rather than a `Vec`, this writes them back out.
+/// let mut output = Vec::new();
+/// serialize_to_writer(&mut output, &prefs, &default()).unwrap();
+/// assert!(String::from_utf8(output).unwrap().contains("volume: (30)"));
+/// ```
+pub fn serialize_to_writer<T: Prefs + Reflect + GetTypeRegistration>(
+    mut writer: impl std::io::Write,
+    to_save: &T,
+    options: &FormatOptions,
+) -> Result<(), PrefsError> {
+    let serialized = serialize_with_options(to_save, options)?;
+    writer.write_all(serialized.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Serialize preferences using the given [`FormatOptions`], writing back any [`PreservedFields`].
+///
+/// If there are preserved fields, the file is marked with their version rather than
+/// [`Prefs::VERSION`], so that they're preserved again the next time it's loaded by this build.
+pub fn serialize_preserving<T: Prefs + Reflect + GetTypeRegistration>(
+    to_save: &T,
+    options: &FormatOptions,
+    preserved: &PreservedFields,
+) -> Result<String, PrefsError> {
+    serialize_preserving_skipping(to_save, options, preserved, T::TRANSIENT_FIELDS)
+}
+
+/// Serializes preferences like [`serialize_preserving`], leaving out the fields in `skip`.
+fn serialize_preserving_skipping<T: Prefs + Reflect + GetTypeRegistration>(
+    to_save: &T,
+    options: &FormatOptions,
+    preserved: &PreservedFields,
+    skip: &[&str],
+) -> Result<String, PrefsError> {
+    let mut serialized = serialize_struct(to_save, options, skip).map_err(PrefsError::Serialize)?;
+    if options.field_comments {
+        serialized = insert_field_comments::<T>(&serialized);
+    }
+
+    let mut version = T::VERSION;
+    if !preserved.fields.is_empty() {
+        version = version.max(preserved.version);
+    }
+
+    // Transient fields that were left out keep their stored values.
+    let keys = FieldKeys::of::<T>();
+    let transient = preserved
+        .transient
+        .iter()
+        .filter(|(key, _)| skip.contains(&keys.name(key)));
+    let mut preserved_fields = preserved.fields.iter().chain(transient).peekable();
+    if preserved_fields.peek().is_some() {
+        if let Some(end) = serialized.rfind(')') {
+            let fields: String = preserved_fields
+                .map(|(name, value)| {
+                    let raw = name.contains(['.', '+', '-']).then_some("r#");
+                    format!("    {}{}: {},\n", raw.unwrap_or_default(), name, value)
+                })
+                .collect();
+            if !serialized[..end].ends_with('\n') {
+                serialized.insert(end, '\n');
+            }
+            serialized.insert_str(serialized.rfind(')').unwrap_or(end), &fields);
+        }
+    }
+
+    // Files marked with a newer version have a different shape by design.
+    if options.schema_hash && version == T::VERSION {
+        serialized.insert_str(
+            0,
+            &format!("{}{:016x}\n", SCHEMA_HEADER, schema_hash::<T>()),
+        );
+    }
+
+    if options.timestamp || options.build_version.is_some() {
+        serialized.insert_str(0, &format!("{}{}\n", SAVED_AT_HEADER, unix_time()));
+    }
+    if let Some(build_version) = options.build_version {
+        serialized.insert_str(0, &format!("{}{}\n", BUILD_VERSION_HEADER, build_version));
+    }
+
+    if version > 0 {
+        serialized.insert_str(0, &format!("{}{}\n", VERSION_HEADER, version));
+    }
+
+    Ok(serialized)
+}
+
+/// Inserts the [`Prefs::FIELD_DOCS`] of each top-level field as comments above it.
+fn insert_field_comments<T: Prefs>(serialized: &str) -> String {
+    let keys = FieldKeys::of::<T>();
+    let mut commented = String::with_capacity(serialized.len());
+    for line in serialized.split_inclusive('\n') {
+        // Top-level fields are the only lines with a single level of indentation that don't
+        // close a value.
+        let key = line
+            .strip_prefix("    ")
+            .filter(|rest| !rest.starts_with(char::is_whitespace))
+            .and_then(|rest| rest.split_once(':'))
+            .map(|(key, _)| key.trim_start_matches("r#"));
+        let docs = key.and_then(|key| {
+            T::FIELD_NAMES
+                .iter()
+                .position(|name| keys.key(name) == key)
+                .and_then(|index| T::FIELD_DOCS.get(index))
+        });
+        for doc in docs
+            .into_iter()
+            .filter(|doc| !doc.is_empty())
+            .flat_map(|doc| doc.lines())
+        {
+            commented.push_str("    //");
+            if !doc.is_empty() {
+                commented.push(' ');
+                commented.push_str(doc);
+            }
+            commented.push('\n');
+        }
+        commented.push_str(line);
+    }
+
+    commented
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+fn unix_time() -> u64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    #[cfg(target_arch = "wasm32")]
+    let time = (js_sys::Date::now() / 1000.) as u64;
+    time
+}
+
+/// Metadata recorded in the header of a preferences file.
+///
+/// See [`FormatOptions::build_version`] and [`FormatOptions::timestamp`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrefsMetadata {
+    /// The [`Prefs::VERSION`] that the file was written with, or `0` if it isn't recorded.
+    pub version: u32,
+    /// The version of the app that wrote the file, if it was recorded.
+    pub build_version: Option<String>,
+    /// The time the file was written, in seconds since the Unix epoch, if it was recorded.
+    pub saved_at: Option<u64>,
+}
+
+/// Reads the metadata from the header of a preferences file.
+///
+/// Fields that aren't recorded in the header, such as in files written before the metadata
+/// header was enabled, are left empty.
+pub fn read_metadata(serialized: &str) -> PrefsMetadata {
+    PrefsMetadata {
+        version: read_version(serialized),
+        build_version: read_header(serialized, BUILD_VERSION_HEADER).map(str::to_string),
+        saved_at: read_header(serialized, SAVED_AT_HEADER).and_then(|secs| secs.parse().ok()),
+    }
+}
+
+/// Prefix of the line at the top of the preferences file containing [`Prefs::VERSION`].
+const VERSION_HEADER: &str = "// version: ";
+
+/// Prefix of the line at the top of the preferences file containing the [`schema_hash`].
+const SCHEMA_HEADER: &str = "// schema: ";
+
+/// Prefix of the line at the top of the preferences file containing
+/// [`FormatOptions::build_version`].
+const BUILD_VERSION_HEADER: &str = "// written by: ";
+
+/// Prefix of the line at the top of the preferences file containing the time of the save.
+const SAVED_AT_HEADER: &str = "// saved at: ";
+
+/// Returns the value of the header starting with `prefix` at the top of a preferences file.
+fn read_header<'a>(serialized: &'a str, prefix: &str) -> Option<&'a str> {
+    // Headers may be preceded by other comments, such as those added by `pre_write`.
+    serialized
+        .lines()
+        .map(str::trim)
+        .take_while(|line| line.is_empty() || line.starts_with("//"))
+        .find_map(|line| line.strip_prefix(prefix))
+        .map(str::trim)
+}
+
+/// Returns the version recorded in the header of a preferences file, or `0` if there is none.
+fn read_version(serialized: &str) -> u32 {
+    read_header(serialized, VERSION_HEADER)
+        .and_then(|version| version.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Returns the schema hash recorded in the header of a preferences file, if there is one.
+fn read_schema_hash(serialized: &str) -> Option<u64> {
+    read_header(serialized, SCHEMA_HEADER).and_then(|hash| u64::from_str_radix(hash, 16).ok())
+}
+
+/// Returns a hash of the shape of the preferences: the names and types of their fields, and of
+/// the fields within those types.
+///
+/// Adding, removing, or renaming a field, or changing its type, changes the hash. Renaming a type
+/// or moving it to another module doesn't, as long as its shape stays the same. Transient fields
+/// are not included. Unlike [`content_hash`], the hash is stable across builds.
+///
+/// Fields with `#[cfg(...)]` attributes are not included either, so preferences with
+/// platform-specific fields have the same hash on every platform, and a file written on one
+/// platform can be loaded on another. Changes to those fields aren't detected.
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_simple_prefs::{schema_hash, Prefs};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     volume: Volume,
+/// }
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct RenamedPrefs {
+///     volume: RenamedVolume,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, Default)]
+/// struct Volume(u32);
+///
+/// #[derive(Resource, Reflect, Clone, Default)]
+/// struct RenamedVolume(u32);
+///
+/// assert_eq!(schema_hash::<ExamplePrefs>(), schema_hash::<RenamedPrefs>());
+/// ```
+pub fn schema_hash<T: Prefs + Reflect + GetTypeRegistration>() -> u64 {
+    let registry = prefs_registry::<T>();
+
+    let mut schema = String::new();
+    if let TypeInfo::Struct(info) = T::get_type_registration().type_info() {
+        for field in info.iter() {
+            if T::TRANSIENT_FIELDS.contains(&field.name()) || T::CFG_FIELDS.contains(&field.name())
+            {
+                continue;
+            }
+            schema.push_str(FieldKeys::of::<T>().key(field.name()));
+            schema.push(':');
+            write_schema(
+                &registry,
+                field.type_id(),
+                field.type_path(),
+                &mut Vec::new(),
+                &mut schema,
+            );
+            schema.push(',');
+        }
+    }
+
+    // FNV-1a, which unlike the hashers in `std` is guaranteed to be stable.
+    schema.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Writes a description of the shape of a type to `out`, for [`schema_hash`].
+fn write_schema(
+    registry: &TypeRegistry,
+    type_id: TypeId,
+    type_path: &str,
+    visiting: &mut Vec<TypeId>,
+    out: &mut String,
+) {
+    // Opaque and unregistered types are described by their type path, and recursive types stop at
+    // the first repetition.
+    let Some(info) = registry.get_type_info(type_id) else {
+        out.push_str(type_path);
+        return;
+    };
+    if visiting.contains(&type_id) {
+        out.push_str("..");
+        return;
+    }
+    visiting.push(type_id);
+
+    let mut named = |fields: &mut dyn Iterator<Item = (&str, TypeId, &str)>, out: &mut String| {
+        out.push('(');
+        for (name, type_id, type_path) in fields {
+            out.push_str(name);
+            out.push(':');
+            write_schema(registry, type_id, type_path, visiting, out);
+            out.push(',');
+        }
+        out.push(')');
+    };
+
+    match info {
+        TypeInfo::Struct(info) => named(
+            &mut info.iter().map(|f| (f.name(), f.type_id(), f.type_path())),
+            out,
+        ),
+        TypeInfo::TupleStruct(info) => named(
+            &mut info.iter().map(|f| ("", f.type_id(), f.type_path())),
+            out,
+        ),
+        TypeInfo::Tuple(info) => named(
+            &mut info.iter().map(|f| ("", f.type_id(), f.type_path())),
+            out,
+        ),
+        TypeInfo::List(info) => {
+            let item = info.item_ty();
+            named(&mut std::iter::once(("[]", item.id(), item.path())), out);
+        }
+        TypeInfo::Array(info) => {
+            let item = info.item_ty();
+            let len = info.capacity().to_string();
+            named(
+                &mut std::iter::once((len.as_str(), item.id(), item.path())),
+                out,
+            );
+        }
+        TypeInfo::Map(info) => {
+            let (key, value) = (info.key_ty(), info.value_ty());
+            named(
+                &mut [("{}", key.id(), key.path()), ("", value.id(), value.path())].into_iter(),
+                out,
+            );
+        }
+        TypeInfo::Set(info) => {
+            let value = info.value_ty();
+            named(&mut std::iter::once(("{}", value.id(), value.path())), out);
+        }
+        TypeInfo::Enum(info) => {
+            for variant in info.iter() {
+                out.push_str(variant.name());
+                match variant {
+                    VariantInfo::Struct(variant) => named(
+                        &mut variant
+                            .iter()
+                            .map(|f| (f.name(), f.type_id(), f.type_path())),
+                        out,
+                    ),
+                    VariantInfo::Tuple(variant) => named(
+                        &mut variant.iter().map(|f| ("", f.type_id(), f.type_path())),
+                        out,
+                    ),
+                    VariantInfo::Unit(_) => {}
+                }
+                out.push('|');
+            }
+        }
+        TypeInfo::Opaque(info) => out.push_str(info.type_path()),
+    }
+
+    visiting.pop();
+}
+
+fn serialize_struct<T: Prefs + Reflect + GetTypeRegistration>(
+    to_save: &T,
+    options: &FormatOptions,
+    skip: &[&str],
+) -> Result<String, ron::Error> {
+    let registry = options_registry::<T>(options);
+
+    let config = PrettyConfig::default().extensions(options.extensions);
+
+    let registration = registry
+        .get(TypeId::of::<T>())
+        .expect("prefs type is registered");
+
+    // Structs that provide their own `Serialize` implementation are serialized as usual, unless
+    // one of the options needs to handle their fields individually.
+    let needs_struct_serializer = registration.data::<ReflectSerialize>().is_none()
+        || options.sort_keys
+        || !T::SECRET_FIELDS.is_empty()
+        || !T::FIELD_KEYS.is_empty()
+        || !skip.is_empty();
+
+    if let (true, ReflectRef::Struct(value), TypeInfo::Struct(info)) = (
+        needs_struct_serializer,
+        to_save.reflect_ref(),
+        registration.type_info(),
+    ) {
+        let serializer = PrefsStructSerializer {
+            value,
+            info,
+            registry: &registry,
+            sort_keys: options.sort_keys,
+            skip,
+            keys: FieldKeys::of::<T>(),
+            secrets: Secrets {
+                fields: T::SECRET_FIELDS,
+                options,
+            },
+        };
+        return to_string_pretty(&serializer, config);
+    }
+
+    let reflect_serializer = TypedReflectSerializer::new(to_save, &registry);
+    to_string_pretty(&reflect_serializer, config)
+}
+
+/// Serializes the top-level preferences struct, optionally with its fields in alphabetical order,
+/// with secret fields encrypted, renamed fields written under their keys, and without transient
+/// fields.
+struct PrefsStructSerializer<'a> {
+    value: &'a dyn Struct,
+    info: &'static StructInfo,
+    registry: &'a TypeRegistry,
+    sort_keys: bool,
+    skip: &'a [&'a str],
+    keys: FieldKeys,
+    secrets: Secrets<'a>,
+}
+
+impl Serialize for PrefsStructSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let serialization_data = self
+            .registry
+            .get(self.info.type_id())
+            .and_then(|registration| registration.data::<SerializationData>());
+
+        let mut names: Vec<&'static str> = self
+            .info
+            .field_names()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| {
+                !serialization_data.is_some_and(|data| data.is_field_skipped(*index))
+            })
+            .map(|(_, name)| *name)
+            .filter(|name| !self.skip.contains(name))
+            .collect();
+        if self.sort_keys {
+            names.sort_unstable_by_key(|name| self.keys.key(name));
+        }
+
+        let mut state = serializer.serialize_struct(
+            self.info.type_path_table().ident().unwrap_or_default(),
+            names.len(),
+        )?;
+        for name in names {
+            let field = self
+                .value
+                .field(name)
+                .ok_or_else(|| S::Error::custom(format!("missing field `{}`", name)))?;
+            let field_serializer = TypedReflectSerializer::new(field, self.registry);
+            if self.secrets.fields.contains(&name) {
+                let sealed = self
+                    .secrets
+                    .seal(&field_serializer)
+                    .map_err(|e| S::Error::custom(format!("field `{}`: {}", name, e)))?;
+                state.serialize_field(self.keys.key(name), &sealed)?;
+            } else if let Some(newtype) = PrimitiveNewtypeSerializer::new(field, self.registry) {
+                state.serialize_field(self.keys.key(name), &newtype)?;
+            } else {
+                state.serialize_field(self.keys.key(name), &field_serializer)?;
+            }
+        }
+        state.end()
+    }
+}
+
+macro_rules! primitives {
+    ($($variant:ident($ty:ty)),* $(,)?) => {
+        /// A primitive type that a newtype field may wrap, such as the `f32` in `Volume(f32)`.
+        ///
+        /// Preferences are often made of many fields like these, so they are serialized and
+        /// deserialized directly instead of through reflection, which takes two to three times
+        /// longer for them (see `benches/flat_prefs.rs`). The output is the same either way.
+        #[derive(Clone, Copy)]
+        enum Primitive {
+            $($variant,)*
+        }
+
+        impl Primitive {
+            fn of(type_id: TypeId) -> Option<Self> {
+                $(
+                    if type_id == TypeId::of::<$ty>() {
+                        return Some(Self::$variant);
+                    }
+                )*
+                None
+            }
+
+            fn downcast(self, value: &dyn PartialReflect) -> Option<PrimitiveRef<'_>> {
+                match self {
+                    $(Self::$variant => value.try_downcast_ref().map(PrimitiveRef::$variant),)*
+                }
+            }
+        }
+
+        impl<'de> DeserializeSeed<'de> for Primitive {
+            type Value = PrimitiveValue;
+
+            fn deserialize<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Self::Value, D::Error> {
+                match self {
+                    $(Self::$variant => Ok(PrimitiveValue::$variant(<$ty>::deserialize(deserializer)?)),)*
+                }
+            }
+        }
+
+        enum PrimitiveValue {
+            $($variant($ty),)*
+        }
+
+        impl PrimitiveValue {
+            fn into_boxed(self) -> Box<dyn PartialReflect> {
+                match self {
+                    $(Self::$variant(value) => Box::new(value),)*
+                }
+            }
+
+            /// Assigns the value to `target` if it's of the same type.
+            fn apply(self, target: &mut dyn PartialReflect) {
+                match self {
+                    $(
+                        Self::$variant(value) => {
+                            if let Some(target) = target.try_downcast_mut() {
+                                *target = value;
+                            }
+                        }
+                    )*
+                }
+            }
+        }
+
+        enum PrimitiveRef<'a> {
+            $($variant(&'a $ty),)*
+        }
+
+        impl Serialize for PrimitiveRef<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self {
+                    $(Self::$variant(value) => value.serialize(serializer),)*
+                }
+            }
+        }
+    };
+}
+
+primitives!(
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    Usize(usize),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    Isize(isize),
+    F32(f32),
+    F64(f64),
+    Char(char),
+    String(String),
+);
+
+/// Returns the name of the newtype registered by `registration` and the [`Primitive`] it wraps, if
+/// reflection would serialize it as that primitive.
+fn primitive_newtype(registration: &TypeRegistration) -> Option<(&'static str, Primitive)> {
+    let TypeInfo::TupleStruct(info) = registration.type_info() else {
+        return None;
+    };
+    if info.field_len() != 1
+        || registration.data::<ReflectSerialize>().is_some()
+        || registration.data::<ReflectDeserialize>().is_some()
+        || registration.data::<SerializationData>().is_some()
+    {
+        return None;
+    }
+
+    Some((
+        info.type_path_table().ident()?,
+        Primitive::of(info.field_at(0)?.type_id())?,
+    ))
+}
+
+/// Serializes a newtype wrapping a [`Primitive`] the same way as [`TypedReflectSerializer`].
+struct PrimitiveNewtypeSerializer<'a> {
+    ident: &'static str,
+    value: PrimitiveRef<'a>,
+}
+
+impl<'a> PrimitiveNewtypeSerializer<'a> {
+    fn new(value: &'a dyn PartialReflect, registry: &TypeRegistry) -> Option<Self> {
+        let registration = registry.get(value.get_represented_type_info()?.type_id())?;
+        let (ident, primitive) = primitive_newtype(registration)?;
+        let ReflectRef::TupleStruct(newtype) = value.reflect_ref() else {
+            return None;
+        };
+
+        Some(Self {
+            ident,
+            value: primitive.downcast(newtype.field(0)?)?,
+        })
+    }
+}
+
+impl Serialize for PrimitiveNewtypeSerializer<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(self.ident, &self.value)
+    }
+}
+
+/// Deserializes a newtype wrapping a [`Primitive`] the same way as [`TypedReflectDeserializer`].
+struct PrimitiveNewtypeDeserializer {
+    ident: &'static str,
+    primitive: Primitive,
+}
+
+impl<'de> DeserializeSeed<'de> for PrimitiveNewtypeDeserializer {
+    type Value = PrimitiveValue;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_newtype_struct(self.ident, self)
+    }
+}
+
+impl<'de> Visitor<'de> for PrimitiveNewtypeDeserializer {
+    type Value = PrimitiveValue;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "a {}", self.ident)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        self.primitive.deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        seq.next_element_seed(self.primitive)?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))
+    }
+}
+
+/// Applies the fields collected by [`PrefsStructDeserializer::primitives`] to `target`.
+fn apply_primitives(
+    target: &mut dyn PartialReflect,
+    primitives: Vec<(&'static str, PrimitiveValue)>,
+) {
+    let ReflectMut::Struct(target) = target.reflect_mut() else {
+        return;
+    };
+    for (name, value) in primitives {
+        let Some(field) = target.field_mut(name) else {
+            continue;
+        };
+        if let ReflectMut::TupleStruct(newtype) = field.reflect_mut() {
+            if let Some(inner) = newtype.field_mut(0) {
+                value.apply(inner);
+            }
+        }
+    }
+}
+
+/// Maps between the names of the top-level fields and the keys they are written under.
+///
+/// See [`Prefs::FIELD_KEYS`].
+#[derive(Clone, Copy)]
+struct FieldKeys {
+    names: &'static [&'static str],
+    keys: &'static [&'static str],
+}
+
+impl FieldKeys {
+    /// Fields of nested structs are never renamed.
+    const NONE: Self = Self {
+        names: &[],
+        keys: &[],
+    };
+
+    fn of<T: Prefs>() -> Self {
+        Self {
+            names: T::FIELD_NAMES,
+            keys: T::FIELD_KEYS,
+        }
+    }
+
+    /// Returns the key that the field `name` is written under.
+    fn key(&self, name: &'static str) -> &'static str {
+        self.names
+            .iter()
+            .position(|n| *n == name)
+            .and_then(|index| self.keys.get(index))
+            .unwrap_or(&name)
+    }
+
+    /// Returns the name of the field written under `key`.
+    fn name<'k>(&self, key: &'k str) -> &'k str {
+        self.keys
+            .iter()
+            .position(|k| *k == key)
+            .and_then(|index| self.names.get(index))
+            .copied()
+            .unwrap_or(key)
+    }
+}
+
+/// Fails to compile without the `secret` feature, for fields marked with `#[prefs(secret)]`.
+///
+/// This is used by the derive macro, and is not part of the public API.
+#[cfg(not(feature = "secret"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __secret_field {
+    ($field:literal) => {
+        compile_error!(concat!(
+            "`#[prefs(secret)]` on `",
+            $field,
+            "` requires the `secret` feature of bevy_simple_prefs"
+        ));
+    };
+}
+
+/// Fails to compile without the `secret` feature, for fields marked with `#[prefs(secret)]`.
+///
+/// This is used by the derive macro, and is not part of the public API.
+#[cfg(feature = "secret")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __secret_field {
+    ($field:literal) => {};
+}
+
+/// Fields marked with `#[prefs(secret)]`, and the options holding the key used to encrypt them.
+#[derive(Clone, Copy)]
+struct Secrets<'a> {
+    fields: &'static [&'static str],
+    #[cfg_attr(not(feature = "secret"), allow(dead_code))]
+    options: &'a FormatOptions,
+}
+
+impl<'a> Secrets<'a> {
+    /// Secret fields only exist at the top level of the preferences struct.
+    fn nested(self) -> Self {
+        Self {
+            fields: &[],
+            ..self
+        }
+    }
+
+    #[cfg(feature = "secret")]
+    fn key(&self) -> Result<&'a SecretKey, String> {
+        self.options
+            .secret_key
+            .as_ref()
+            .ok_or_else(|| "no secret key was provided".to_string())
+    }
+
+    /// Serializes a value as compact RON and encrypts it.
+    fn seal(&self, value: &TypedReflectSerializer) -> Result<String, String> {
+        #[cfg(feature = "secret")]
+        {
+            let serialized = to_string(value).map_err(|e| e.to_string())?;
+            secret::seal(self.key()?, serialized.as_bytes())
+        }
+
+        #[cfg(not(feature = "secret"))]
+        {
+            let _ = value;
+            Err("secret fields require the `secret` feature".to_string())
+        }
+    }
+
+    /// Decrypts a value and deserializes it.
+    fn open(
+        &self,
+        sealed: &str,
+        registration: &TypeRegistration,
+        registry: &TypeRegistry,
+    ) -> Result<Box<dyn PartialReflect>, String> {
+        #[cfg(feature = "secret")]
+        {
+            let serialized = secret::open(self.key()?, sealed)?;
+            let mut deserializer =
+                ron::Deserializer::from_bytes(&serialized).map_err(|e| e.to_string())?;
+            TolerantDeserializer {
+                registration,
+                registry,
+                strict: self.options.strict,
+                keys: FieldKeys::NONE,
+                secrets: self.nested(),
+                primitives: None,
+            }
+            .deserialize(&mut deserializer)
+            .map_err(|e| e.to_string())
+        }
+
+        #[cfg(not(feature = "secret"))]
+        {
+            let _ = (sealed, registration, registry);
+            Err("secret fields require the `secret` feature".to_string())
+        }
+    }
+}
+
+#[cfg(feature = "secret")]
+mod secret {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use chacha20poly1305::{
+        aead::{Aead, AeadCore, KeyInit, OsRng},
+        XChaCha20Poly1305, XNonce,
+    };
+
+    use crate::SecretKey;
+
+    const NONCE_LEN: usize = 24;
+
+    /// Encrypts `plaintext`, returning the nonce and ciphertext as base64.
+    pub(crate) fn seal(key: &SecretKey, plaintext: &[u8]) -> Result<String, String> {
+        let cipher = XChaCha20Poly1305::new(&key.0.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| "failed to encrypt".to_string())?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(STANDARD.encode(sealed))
+    }
+
+    /// Decrypts a value produced by [`seal`].
+    pub(crate) fn open(key: &SecretKey, sealed: &str) -> Result<Vec<u8>, String> {
+        let sealed = STANDARD.decode(sealed).map_err(|e| e.to_string())?;
+        if sealed.len() < NONCE_LEN {
+            return Err("encrypted value is too short".to_string());
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let cipher = XChaCha20Poly1305::new(&key.0.into());
+        cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "failed to decrypt".to_string())
+    }
+}
+
+/// Serialize a single value as compact RON, without any whitespace.
+pub fn serialize_compact<T: Reflect + GetTypeRegistration>(
+    value: &T,
+) -> Result<String, PrefsError> {
+    let registry = prefs_registry::<T>();
+
+    let reflect_serializer = TypedReflectSerializer::new(value, &registry);
+    to_string(&reflect_serializer).map_err(PrefsError::Serialize)
+}
+
+/// Wraps preferences to implement `serde`'s [`Serialize`] and [`Deserialize`] through
+/// reflection, so that they can be used with any `serde` format.
+///
+/// Fields are written under the same keys as in the preferences file, and transient fields are
+/// left out. Unlike the preferences file, secret fields aren't encrypted. When deserializing,
+/// missing fields keep their default values and unknown fields are ignored.
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_simple_prefs::{Prefs, SerdePrefs};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     volume: Volume,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+/// struct Volume(u32);
+///
+/// let prefs = ExamplePrefs {
+///     volume: Volume(80),
+/// };
+/// let serialized = ron::to_string(&SerdePrefs(prefs)).unwrap();
+/// assert_eq!(serialized, "(volume:(80))");
+///
+/// let SerdePrefs(prefs) = ron::from_str::<SerdePrefs<ExamplePrefs>>(&serialized).unwrap();
+/// assert_eq!(prefs.volume, Volume(80));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SerdePrefs<T>(pub T);
+
+impl<T: Prefs + Reflect + GetTypeRegistration> Serialize for SerdePrefs<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let registry = prefs_registry::<T>();
+        let options = FormatOptions::default();
+
+        if let (ReflectRef::Struct(value), TypeInfo::Struct(info)) =
+            (self.0.reflect_ref(), T::get_type_registration().type_info())
+        {
+            let struct_serializer = PrefsStructSerializer {
+                value,
+                info,
+                registry: &registry,
+                sort_keys: false,
+                skip: T::TRANSIENT_FIELDS,
+                keys: FieldKeys::of::<T>(),
+                secrets: Secrets {
+                    fields: &[],
+                    options: &options,
+                },
+            };
+            return struct_serializer.serialize(serializer);
+        }
+
+        TypedReflectSerializer::new(&self.0, &registry).serialize(serializer)
+    }
+}
+
+impl<'de, T: Prefs + Reflect + GetTypeRegistration + Default> Deserialize<'de> for SerdePrefs<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let registry = prefs_registry::<T>();
+        let registration = registry.get(TypeId::of::<T>()).unwrap();
+        let options = FormatOptions::default();
+
+        let de = TolerantDeserializer {
+            registration,
+            registry: &registry,
+            strict: false,
+            keys: FieldKeys::of::<T>(),
+            secrets: Secrets {
+                fields: &[],
+                options: &options,
+            },
+            primitives: None,
+        };
+
+        let mut value = T::default();
+        apply_partial(
+            value.as_partial_reflect_mut(),
+            &*de.deserialize(deserializer)?,
+        );
+        Ok(Self(value))
+    }
 }
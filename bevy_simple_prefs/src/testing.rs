@@ -0,0 +1,110 @@
+//! Helpers for testing preferences types, enabled by the `testing` feature.
+//!
+//! This module is not available in WASM builds.
+
+use bevy::{
+    app::App,
+    ecs::system::Resource,
+    reflect::{GetTypeRegistration, Reflect, TypePath},
+    tasks::{IoTaskPool, TaskPool},
+};
+
+use crate::{MemoryBackend, Prefs, PrefsPlugin};
+
+/// Returns `true` if `value`, an individual preference of `T`, survives being saved and loaded.
+///
+/// This runs an `App` with a `PrefsPlugin<T>` storing its preferences in a [`MemoryBackend`],
+/// inserts `value`, waits for it to be saved, and then loads the preferences in a second `App`.
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_simple_prefs::{testing::round_trip, Prefs};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     volume: Volume,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+/// struct Volume(u32);
+///
+/// assert!(round_trip::<ExamplePrefs, _>(Volume(20)));
+/// ```
+pub fn round_trip<T, R>(value: R) -> bool
+where
+    T: Prefs + Reflect + TypePath + GetTypeRegistration + Default,
+    R: Resource + Clone + PartialEq,
+{
+    let backend = MemoryBackend::default();
+
+    let saved = {
+        let mut app = app::<T>(PrefsPlugin::default().with_backend(backend.clone()));
+        app.world_mut().insert_resource(value.clone());
+        app.update();
+        finish_tasks::<T>(&mut app);
+        app.world()
+            .get_resource::<crate::PrefsSaveError<T>>()
+            .is_none()
+    };
+
+    let loaded = app::<T>(PrefsPlugin::default().with_backend(backend))
+        .world()
+        .get_resource::<R>()
+        .cloned();
+
+    saved && loaded.is_some_and(|loaded| loaded == value)
+}
+
+/// Builds an `App` with `plugin`, and runs its first update and any loads that it starts.
+///
+/// Unlike adding `plugin` to an `App` directly, this doesn't need the `TaskPoolPlugin`.
+///
+/// ```rust
+/// use bevy::prelude::*;
+/// use bevy_simple_prefs::{testing, MemoryBackend, Prefs, PrefsPlugin, StorageBackend};
+///
+/// #[derive(Prefs, Reflect, Default)]
+/// struct ExamplePrefs {
+///     volume: Volume,
+/// }
+///
+/// #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+/// struct Volume(u32);
+///
+/// let backend = MemoryBackend::default();
+/// bevy::tasks::block_on(backend.save("prefs.ron", "(volume: (30))")).unwrap();
+///
+/// let app = testing::app(
+///     PrefsPlugin::<ExamplePrefs> {
+///         filename: "prefs.ron".into(),
+///         ..default()
+///     }
+///     .with_backend(backend),
+/// );
+/// assert_eq!(*app.world().resource::<Volume>(), Volume(30));
+/// ```
+pub fn app<T>(plugin: PrefsPlugin<T>) -> App
+where
+    T: Prefs + Reflect + TypePath + GetTypeRegistration + Default,
+{
+    IoTaskPool::get_or_init(TaskPool::new);
+
+    let mut app = App::new();
+    app.add_plugins(plugin);
+    app.update();
+    finish_tasks::<T>(&mut app);
+    app
+}
+
+/// Blocks until the in-progress loads and saves of `T` have finished, and applies their results
+/// without running any systems.
+///
+/// Loads that are queued behind an in-progress load are started and finished too, so after
+/// this, the individual preference `Resources` hold the loaded values and everything that was
+/// saved is in storage.
+pub fn finish_tasks<T>(app: &mut App)
+where
+    T: Prefs + Send + Sync + 'static,
+{
+    crate::finish_tasks::<T>(app.world_mut());
+}
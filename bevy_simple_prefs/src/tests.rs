@@ -0,0 +1,1775 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use bevy::{
+    prelude::*,
+    reflect::GetTypeRegistration,
+    tasks::{block_on, IoTaskPool, TaskPool},
+    utils::BoxedFuture,
+};
+
+use crate::{finish_tasks, MemoryBackend, Prefs, PrefsPlugin, PrefsStatus, StorageBackend};
+
+#[derive(Prefs, Reflect, Default)]
+struct TestPrefs {
+    volume: Volume,
+    fov: Fov,
+}
+
+#[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+struct Volume(u32);
+
+#[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+struct Fov(u32);
+
+/// A plugin that stores `T` under `prefs.ron` in `backend`, and loads it blocking.
+fn plugin<T: Reflect + TypePath>(backend: &MemoryBackend) -> PrefsPlugin<T> {
+    PrefsPlugin {
+        filename: "prefs.ron".into(),
+        blocking_load: true,
+        ..default()
+    }
+    .with_backend(backend.clone())
+}
+
+/// A plugin that stores `T` in `prefs.ron` in `dir`, and loads it blocking.
+fn file_plugin<T: Reflect + TypePath>(dir: &Path) -> PrefsPlugin<T> {
+    PrefsPlugin {
+        filename: "prefs.ron".into(),
+        path: dir.to_path_buf(),
+        blocking_load: true,
+        ..default()
+    }
+}
+
+/// Builds an `App` with `plugin`, and runs its first update.
+fn test_app<T>(plugin: PrefsPlugin<T>) -> App
+where
+    T: Prefs + Reflect + TypePath + GetTypeRegistration + Default,
+{
+    IoTaskPool::get_or_init(TaskPool::new);
+
+    let mut app = App::new();
+    app.add_plugins(plugin);
+    update::<T>(&mut app);
+    app
+}
+
+/// Runs an update of `app`, and waits for the loads and saves of `T` that it starts.
+fn update<T: Prefs + Send + Sync + 'static>(app: &mut App) {
+    app.update();
+    finish_tasks::<T>(app.world_mut());
+}
+
+/// Runs updates of `app` until `done` returns `true`, for work that needs updates to progress,
+/// such as loading assets.
+fn update_until(app: &mut App, done: impl Fn(&World) -> bool) {
+    for _ in 0..5000 {
+        if done(app.world()) {
+            return;
+        }
+        app.update();
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    panic!("timed out");
+}
+
+fn stored(backend: &MemoryBackend) -> String {
+    block_on(backend.load("prefs.ron")).unwrap().unwrap()
+}
+
+fn saves<T: Send + Sync + 'static>(app: &App) -> u64 {
+    app.world().resource::<PrefsStatus<T>>().save_count
+}
+
+/// A temporary directory that is removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = std::env::temp_dir().join(format!(
+            "bevy_simple_prefs_tests_{}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn validate_corrects_loaded_and_changed_values() {
+    #[derive(Prefs, Reflect, Default)]
+    struct ValidatedPrefs {
+        #[prefs(validate = "clamp_volume")]
+        volume: Volume,
+    }
+
+    fn clamp_volume(volume: &mut Volume) {
+        volume.0 = volume.0.min(100);
+    }
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (250))")).unwrap();
+
+    let mut app = test_app(plugin::<ValidatedPrefs>(&backend));
+    assert_eq!(*app.world().resource::<Volume>(), Volume(100));
+
+    // The corrected value is written back.
+    update::<ValidatedPrefs>(&mut app);
+    assert!(stored(&backend).contains("100"));
+
+    app.world_mut().insert_resource(Volume(120));
+    update::<ValidatedPrefs>(&mut app);
+    assert_eq!(*app.world().resource::<Volume>(), Volume(100));
+}
+
+#[test]
+fn load_doesnt_trigger_change_detection() {
+    #[derive(Resource, Default)]
+    struct Changes(u32);
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (30))")).unwrap();
+
+    let mut app = test_app(plugin::<TestPrefs>(&backend));
+    app.init_resource::<Changes>();
+    app.add_systems(
+        Update,
+        (|mut changes: ResMut<Changes>| changes.0 += 1).run_if(resource_changed::<Volume>),
+    );
+
+    // Like any system, this one sees the resource as changed the first time it runs.
+    update::<TestPrefs>(&mut app);
+    assert_eq!(app.world().resource::<Changes>().0, 1);
+
+    block_on(backend.save("prefs.ron", "(volume: (40))")).unwrap();
+    TestPrefs::load(app.world_mut());
+    update::<TestPrefs>(&mut app);
+    assert_eq!(*app.world().resource::<Volume>(), Volume(40));
+    assert_eq!(app.world().resource::<Changes>().0, 1);
+}
+
+#[test]
+fn applied_prefs_dont_trigger_change_detection() {
+    #[derive(Resource, Default)]
+    struct Changes(u32);
+
+    #[derive(Resource, Default)]
+    struct Loads(u32);
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (30))")).unwrap();
+
+    let mut app = test_app(plugin::<TestPrefs>(&backend));
+    app.init_resource::<Changes>();
+    app.init_resource::<Loads>();
+    app.add_systems(
+        Update,
+        (
+            (|mut changes: ResMut<Changes>| changes.0 += 1).run_if(resource_changed::<Volume>),
+            (|mut loads: ResMut<Loads>| loads.0 += 1)
+                .run_if(crate::prefs_just_loaded::<TestPrefs>()),
+        ),
+    );
+    update::<TestPrefs>(&mut app);
+    assert_eq!(app.world().resource::<Changes>().0, 1);
+    assert_eq!(app.world().resource::<Loads>().0, 1);
+
+    let snapshot = TestPrefs::snapshot(app.world()).unwrap();
+
+    block_on(backend.save("prefs.ron", "(volume: (40))")).unwrap();
+    TestPrefs::load_field::<Volume>(app.world_mut()).unwrap();
+    update::<TestPrefs>(&mut app);
+    assert_eq!(*app.world().resource::<Volume>(), Volume(40));
+
+    crate::import_prefs::<TestPrefs>(app.world_mut(), "(volume: (50))").unwrap();
+    update::<TestPrefs>(&mut app);
+    assert_eq!(*app.world().resource::<Volume>(), Volume(50));
+
+    TestPrefs::restore(app.world_mut(), &snapshot).unwrap();
+    update::<TestPrefs>(&mut app);
+    assert_eq!(*app.world().resource::<Volume>(), Volume(30));
+
+    assert_eq!(app.world().resource::<Changes>().0, 1);
+    assert_eq!(app.world().resource::<Loads>().0, 4);
+}
+
+#[test]
+fn load_field_only_inserts_that_field() {
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (20), fov: (110))")).unwrap();
+
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        load_schedule: None,
+        ..plugin(&backend)
+    });
+
+    assert!(TestPrefs::load_field::<Volume>(app.world_mut()).unwrap());
+    assert_eq!(*app.world().resource::<Volume>(), Volume(20));
+    assert_eq!(*app.world().resource::<Fov>(), Fov::default());
+}
+
+#[test]
+fn field_methods_reject_other_types() {
+    #[derive(Resource, Reflect, Clone, Default)]
+    struct Brightness(u32);
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (20), fov: (110))")).unwrap();
+    let mut app = test_app(plugin::<TestPrefs>(&backend));
+
+    assert!(matches!(
+        TestPrefs::load_field::<Brightness>(app.world_mut()),
+        Err(crate::PrefsError::UnknownField(_))
+    ));
+    assert!(matches!(
+        TestPrefs::reset_field::<Brightness>(app.world_mut()),
+        Err(crate::PrefsError::UnknownField(_))
+    ));
+    assert!(!app.world().contains_resource::<Brightness>());
+}
+
+#[test]
+fn reset_field_resets_only_that_field_and_saves() {
+    #[derive(Prefs, Reflect)]
+    struct DefaultedPrefs {
+        volume: Volume,
+        fov: Fov,
+    }
+
+    impl Default for DefaultedPrefs {
+        fn default() -> Self {
+            Self {
+                volume: Volume(80),
+                fov: Fov(90),
+            }
+        }
+    }
+
+    let backend = MemoryBackend::default();
+    let mut app = test_app(plugin::<DefaultedPrefs>(&backend));
+    app.insert_resource(Volume(20));
+    app.insert_resource(Fov(110));
+    update::<DefaultedPrefs>(&mut app);
+
+    DefaultedPrefs::reset_field::<Volume>(app.world_mut()).unwrap();
+    assert_eq!(*app.world().resource::<Volume>(), Volume(80));
+    assert_eq!(*app.world().resource::<Fov>(), Fov(110));
+
+    let save_count = saves::<DefaultedPrefs>(&app);
+    update::<DefaultedPrefs>(&mut app);
+    assert_eq!(saves::<DefaultedPrefs>(&app), save_count + 1);
+}
+
+#[test]
+fn diff_lists_changed_fields() {
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (20), fov: (110))")).unwrap();
+
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        read_only: true,
+        ..plugin(&backend)
+    });
+    assert!(TestPrefs::diff(app.world()).unwrap().is_empty());
+
+    app.world_mut().resource_mut::<Fov>().0 = 90;
+    assert_eq!(TestPrefs::diff(app.world()).unwrap(), ["fov"]);
+}
+
+#[test]
+fn restore_applies_snapshot_and_saves() {
+    let backend = MemoryBackend::default();
+    let mut app = test_app(plugin::<TestPrefs>(&backend));
+    app.insert_resource(Volume(20));
+    app.insert_resource(Fov(110));
+    update::<TestPrefs>(&mut app);
+
+    let snapshot = TestPrefs::snapshot(app.world()).unwrap();
+
+    app.insert_resource(Volume(90));
+    app.insert_resource(Fov(60));
+    update::<TestPrefs>(&mut app);
+
+    let save_count = saves::<TestPrefs>(&app);
+    TestPrefs::restore(app.world_mut(), &snapshot).unwrap();
+    assert_eq!(*app.world().resource::<Volume>(), Volume(20));
+    assert_eq!(*app.world().resource::<Fov>(), Fov(110));
+    assert_eq!(saves::<TestPrefs>(&app), save_count + 1);
+}
+
+#[test]
+fn compact_drops_unknown_fields() {
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (80), show_fps: (true))")).unwrap();
+
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        load_schedule: None,
+        ..plugin(&backend)
+    });
+
+    assert!(TestPrefs::compact(app.world_mut()).unwrap());
+    let stored = stored(&backend);
+    assert!(stored.contains("80") && !stored.contains("show_fps"));
+}
+
+#[test]
+fn peek_reads_detached_values() {
+    let dir = TempDir::new();
+    std::fs::write(dir.join("slot_1.ron"), "(volume: (20), fov: (110))").unwrap();
+
+    let slot = TestPrefs::peek(dir.join("slot_1.ron")).unwrap();
+    assert_eq!(slot.volume, Volume(20));
+    assert_eq!(slot.fov, Fov(110));
+
+    assert!(TestPrefs::peek(dir.join("slot_2.ron")).is_none());
+}
+
+#[test]
+fn save_to_and_load_from() {
+    let dir = TempDir::new();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        load_schedule: None,
+        ..file_plugin(&dir)
+    });
+
+    app.world_mut().insert_resource(Volume(80));
+    TestPrefs::save_to(app.world(), dir.join("exported.ron")).unwrap();
+    assert!(!dir.join("prefs.ron").exists());
+
+    app.world_mut().insert_resource(Volume(20));
+    TestPrefs::load_from(app.world_mut(), dir.join("exported.ron")).unwrap();
+    assert_eq!(*app.world().resource::<Volume>(), Volume(80));
+
+    let stored = TestPrefs::peek(dir.join("prefs.ron")).unwrap();
+    assert_eq!(stored.volume, Volume(80));
+}
+
+#[test]
+fn mirror_is_loaded_when_local_file_is_corrupted() {
+    let dir = TempDir::new();
+    let plugin = || PrefsPlugin::<TestPrefs> {
+        mirror_paths: vec![dir.join("share")],
+        ..file_plugin(&dir.join("local"))
+    };
+
+    let mut app = test_app(plugin());
+    app.insert_resource(Volume(30));
+    TestPrefs::save_current(app.world_mut()).unwrap();
+    assert!(dir.join("share/prefs.ron").exists());
+
+    std::fs::write(dir.join("local/prefs.ron"), "(volume: (").unwrap();
+    let app = test_app(plugin());
+    assert_eq!(*app.world().resource::<Volume>(), Volume(30));
+}
+
+#[test]
+fn unreadable_dirs_are_skipped_when_loading() {
+    use crate::load_str_from_any;
+
+    let dir = TempDir::new();
+    std::fs::create_dir_all(dir.join("local/prefs.ron")).unwrap();
+    std::fs::create_dir_all(dir.join("share")).unwrap();
+    std::fs::write(dir.join("share/prefs.ron"), "(volume: (30))").unwrap();
+
+    let local = dir.join("local");
+    let dirs = [local.clone(), dir.join("share")];
+    assert_eq!(
+        load_str_from_any(&dirs, "prefs.ron").unwrap().as_deref(),
+        Some("(volume: (30))")
+    );
+    assert!(load_str_from_any(&[local, dir.join("missing")], "prefs.ron").is_err());
+}
+
+#[test]
+fn io_errors_are_kept() {
+    use std::{error::Error as _, io::ErrorKind};
+
+    use crate::{load_str, PrefsError};
+
+    // Reading a directory as the preferences file fails with an IO error other than `NotFound`.
+    let dir = TempDir::new();
+    std::fs::create_dir(dir.join("prefs.ron")).unwrap();
+    let error = load_str(&dir, "prefs.ron").unwrap_err();
+    let PrefsError::Io(io_error) = &error else {
+        panic!("expected an IO error, got {:?}", error);
+    };
+    assert_ne!(io_error.kind(), ErrorKind::NotFound);
+
+    let source = error.source().unwrap();
+    let source = source.downcast_ref::<std::io::Error>().unwrap();
+    assert_eq!(source.kind(), io_error.kind());
+}
+
+#[test]
+fn only_read_only_errors_use_the_fallback_path() {
+    use std::io::{Error, ErrorKind};
+
+    use crate::PrefsError;
+
+    assert!(matches!(
+        PrefsError::from(Error::from(ErrorKind::PermissionDenied)),
+        PrefsError::ReadOnly(_)
+    ));
+    #[cfg(unix)]
+    assert!(matches!(
+        PrefsError::from(Error::from_raw_os_error(30)),
+        PrefsError::ReadOnly(_)
+    ));
+
+    // A file in place of the preferences directory isn't something the fallback path can fix.
+    let dir = TempDir::new();
+    std::fs::write(dir.join("local"), "").unwrap();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        fallback_path: Some(dir.join("fallback")),
+        ..file_plugin(&dir.join("local"))
+    });
+    app.insert_resource(Volume(30));
+    assert!(matches!(
+        TestPrefs::save_current(app.world_mut()),
+        Err(PrefsError::Io(_))
+    ));
+    assert!(!dir.join("fallback/prefs.ron").exists());
+}
+
+#[test]
+fn base_path_provides_defaults() {
+    let dir = TempDir::new();
+    std::fs::create_dir_all(dir.join("system")).unwrap();
+    std::fs::create_dir_all(dir.join("user")).unwrap();
+    std::fs::write(dir.join("system/prefs.ron"), "(volume: (50), fov: (90))").unwrap();
+    std::fs::write(dir.join("user/prefs.ron"), "(fov: (110))").unwrap();
+
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        base_path: Some(dir.join("system")),
+        ..file_plugin(&dir.join("user"))
+    });
+    assert_eq!(*app.world().resource::<Volume>(), Volume(50));
+    assert_eq!(*app.world().resource::<Fov>(), Fov(110));
+
+    TestPrefs::save_current(app.world_mut()).unwrap();
+    let saved = std::fs::read_to_string(dir.join("user/prefs.ron")).unwrap();
+    assert!(saved.contains("fov") && !saved.contains("volume"));
+}
+
+#[cfg(unix)]
+#[test]
+fn file_mode_is_applied() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TempDir::new();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        file_mode: Some(0o600),
+        history: Some(crate::PrefsHistory::default()),
+        ..file_plugin(&dir)
+    });
+    TestPrefs::save_current(app.world_mut()).unwrap();
+
+    let metadata = std::fs::metadata(dir.join("prefs.ron")).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+
+    app.world_mut().resource_mut::<Volume>().0 = 20;
+    update::<TestPrefs>(&mut app);
+    let metadata = std::fs::metadata(dir.join("prefs.ron.history")).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+}
+
+#[test]
+fn embedded_default_is_used_without_stored_prefs() {
+    let backend = MemoryBackend::default();
+    let app = test_app(PrefsPlugin::<TestPrefs> {
+        embedded_default: Some("(volume: (80))"),
+        ..plugin(&backend)
+    });
+    assert_eq!(*app.world().resource::<Volume>(), Volume(80));
+}
+
+#[cfg(feature = "asset")]
+#[test]
+fn default_asset_is_used_without_stored_prefs() {
+    let dir = TempDir::new();
+    std::fs::create_dir_all(dir.join("assets/config")).unwrap();
+    std::fs::write(
+        dir.join("assets/config/default_prefs.ron"),
+        "(volume: (80))",
+    )
+    .unwrap();
+
+    IoTaskPool::get_or_init(TaskPool::new);
+    let mut app = App::new();
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        AssetPlugin {
+            file_path: dir.join("assets").to_string_lossy().into(),
+            ..default()
+        },
+    ));
+    app.add_plugins(PrefsPlugin::<TestPrefs> {
+        default_asset: Some("config/default_prefs.ron".into()),
+        blocking_load: false,
+        ..file_plugin(&dir.join("prefs"))
+    });
+
+    update_until(&mut app, |world| {
+        world.resource::<PrefsStatus<TestPrefs>>().loaded
+    });
+    assert_eq!(*app.world().resource::<Volume>(), Volume(80));
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn http_defaults_are_layered_under_stored_prefs() {
+    use crate::HttpDefaults;
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/defaults.ron", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let _ = stream.read(&mut [0; 1024]);
+        let body = "(volume: (80), fov: (90))";
+        let header = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len());
+        stream.write_all((header + body).as_bytes()).unwrap();
+    });
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(fov: (100))")).unwrap();
+
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        http_defaults: Some(HttpDefaults::new(url)),
+        ..plugin(&backend)
+    });
+    update_until(&mut app, |world| {
+        world.resource::<PrefsStatus<TestPrefs>>().loaded
+    });
+    assert_eq!(*app.world().resource::<Volume>(), Volume(80));
+    assert_eq!(*app.world().resource::<Fov>(), Fov(100));
+}
+
+#[test]
+fn periodic_save_saves_unchanged_prefs() {
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+    use std::time::Duration;
+
+    let backend = MemoryBackend::default();
+    IoTaskPool::get_or_init(TaskPool::new);
+    let mut app = App::new();
+    app.add_plugins(TimePlugin);
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs(1)));
+    app.add_plugins(PrefsPlugin::<TestPrefs> {
+        periodic_save: Some(Duration::from_secs(5)),
+        ..plugin(&backend)
+    });
+
+    for _ in 0..12 {
+        update::<TestPrefs>(&mut app);
+    }
+    assert_eq!(saves::<TestPrefs>(&app), 2);
+}
+
+#[test]
+fn serialize_in_place_keeps_values() {
+    #[derive(Prefs, Reflect, Default)]
+    struct MapPrefs {
+        discovered: Discovered,
+    }
+
+    #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+    struct Discovered(Vec<(i32, i32)>);
+
+    let backend = MemoryBackend::default();
+    let mut app = test_app(PrefsPlugin::<MapPrefs> {
+        serialize_in_place: true,
+        ..plugin(&backend)
+    });
+
+    let tiles: Vec<_> = (0..1000).map(|i| (i % 32, i / 32)).collect();
+    app.insert_resource(Discovered(tiles.clone()));
+    update::<MapPrefs>(&mut app);
+    assert_eq!(app.world().resource::<Discovered>().0, tiles);
+
+    let stored = crate::peek_str::<MapPrefs>(stored(&backend)).unwrap();
+    assert_eq!(stored.discovered.0, tiles);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn warn_constant_changes_counts_streaks() {
+    let backend = MemoryBackend::default();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        warn_constant_changes: Some(30),
+        ..plugin(&backend)
+    });
+
+    // Oops, this marks `Volume` as changed every frame.
+    app.add_systems(Update, |mut volume: ResMut<Volume>| volume.0 = 50);
+
+    // The first update initialized `Volume`, which counts as a change.
+    for _ in 0..4 {
+        update::<TestPrefs>(&mut app);
+    }
+    assert_eq!(
+        app.world()
+            .resource::<crate::PrefsChangeStreaks<TestPrefs>>()
+            .streaks["volume"],
+        5
+    );
+}
+
+#[test]
+fn shared_file_keeps_each_types_section() {
+    #[derive(Prefs, Reflect, Default)]
+    struct ExampleStats {
+        high_score: HighScore,
+    }
+
+    #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+    struct HighScore(u32);
+
+    let dir = TempDir::new();
+    std::fs::write(
+        dir.join("prefs.ron"),
+        "{ \"TestPrefs\": (volume: (50)), \"ExampleStats\": (high_score: (9000)) }",
+    )
+    .unwrap();
+
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        shared_file: true,
+        ..file_plugin(&dir)
+    });
+    let mut stats = test_app(PrefsPlugin::<ExampleStats> {
+        shared_file: true,
+        ..file_plugin(&dir)
+    });
+
+    assert_eq!(*app.world().resource::<Volume>(), Volume(50));
+    assert_eq!(*stats.world().resource::<HighScore>(), HighScore(9000));
+
+    app.world_mut().insert_resource(Volume(80));
+    stats.world_mut().insert_resource(HighScore(9001));
+    TestPrefs::save_current(app.world_mut()).unwrap();
+    ExampleStats::save_current(stats.world_mut()).unwrap();
+
+    let saved = std::fs::read_to_string(dir.join("prefs.ron")).unwrap();
+    assert!(saved.contains("80") && saved.contains("9001"));
+}
+
+#[test]
+fn invalid_shared_file_isnt_replaced() {
+    let dir = TempDir::new();
+    let invalid = "{ \"ExampleStats\": (high_score: (9000)), TestPrefs: () }";
+    std::fs::write(dir.join("prefs.ron"), invalid).unwrap();
+
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        shared_file: true,
+        ..file_plugin(&dir)
+    });
+    app.world_mut().insert_resource(Volume(80));
+    assert!(TestPrefs::save_current(app.world_mut()).is_err());
+
+    // The sections of other types are kept.
+    let stored = std::fs::read_to_string(dir.join("prefs.ron")).unwrap();
+    assert_eq!(stored, invalid);
+}
+
+#[test]
+fn post_load_corrects_loaded_prefs() {
+    #[derive(Prefs, Reflect, Default)]
+    struct RangePrefs {
+        min_volume: MinVolume,
+        max_volume: MaxVolume,
+    }
+
+    #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+    struct MinVolume(u32);
+
+    #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+    struct MaxVolume(u32);
+
+    fn fix_volume_range(prefs: &mut RangePrefs) -> bool {
+        if prefs.min_volume.0 <= prefs.max_volume.0 {
+            return false;
+        }
+        std::mem::swap(&mut prefs.min_volume.0, &mut prefs.max_volume.0);
+        true
+    }
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(min_volume: (80), max_volume: (20))")).unwrap();
+
+    let app = test_app(PrefsPlugin::<RangePrefs> {
+        post_load: Some(fix_volume_range),
+        ..plugin(&backend)
+    });
+    assert_eq!(*app.world().resource::<MinVolume>(), MinVolume(20));
+    assert_eq!(*app.world().resource::<MaxVolume>(), MaxVolume(80));
+}
+
+#[test]
+fn save_if_keeps_changes_pending() {
+    #[derive(Resource)]
+    struct GuestMode(bool);
+
+    let backend = MemoryBackend::default();
+    IoTaskPool::get_or_init(TaskPool::new);
+    let mut app = App::new();
+    app.insert_resource(GuestMode(true));
+    app.add_plugins(PrefsPlugin::<TestPrefs> {
+        save_if: Some(|world| !world.resource::<GuestMode>().0),
+        ..plugin(&backend)
+    });
+    update::<TestPrefs>(&mut app);
+
+    app.world_mut().insert_resource(Volume(80));
+    for _ in 0..5 {
+        update::<TestPrefs>(&mut app);
+    }
+    assert!(block_on(backend.load("prefs.ron")).unwrap().is_none());
+
+    // The pending change is saved once the guest has left.
+    app.world_mut().insert_resource(GuestMode(false));
+    update::<TestPrefs>(&mut app);
+    assert!(stored(&backend).contains("80"));
+}
+
+#[test]
+fn warm_up_frames_skip_saves() {
+    let backend = MemoryBackend::default();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        warm_up_frames: 5,
+        ..plugin(&backend)
+    });
+    app.add_systems(
+        Update,
+        |mut volume: ResMut<Volume>, mut frame: Local<u32>| {
+            // Settle on a volume over the first few frames
+            *frame += 1;
+            if *frame <= 3 {
+                volume.0 = *frame * 10;
+            }
+        },
+    );
+
+    for _ in 0..4 {
+        update::<TestPrefs>(&mut app);
+    }
+    assert!(block_on(backend.load("prefs.ron")).unwrap().is_none());
+
+    // The settled volume is saved once warming up is over.
+    for _ in 0..6 {
+        update::<TestPrefs>(&mut app);
+    }
+    assert!(stored(&backend).contains("volume: (30)"));
+    assert_eq!(saves::<TestPrefs>(&app), 1);
+
+    app.world_mut().insert_resource(Volume(80));
+    update::<TestPrefs>(&mut app);
+    assert!(stored(&backend).contains("80"));
+}
+
+#[test]
+fn env_prefix_overrides_loaded_prefs() {
+    #[derive(Prefs, Reflect, Default)]
+    struct ServerPrefs {
+        max_players: MaxPlayers,
+        motd: Motd,
+    }
+
+    #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+    struct MaxPlayers(u32);
+
+    #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+    struct Motd(String);
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", r#"(max_players: (8), motd: ("Hi"))"#)).unwrap();
+    std::env::set_var("ENV_PREFIX_TEST_MAX_PLAYERS", "64");
+    std::env::set_var("ENV_PREFIX_TEST_MOTD", "Welcome!");
+
+    let app = test_app(PrefsPlugin::<ServerPrefs> {
+        env_prefix: Some("ENV_PREFIX_TEST_".into()),
+        read_only: true,
+        ..plugin(&backend)
+    });
+    assert_eq!(*app.world().resource::<MaxPlayers>(), MaxPlayers(64));
+    assert_eq!(app.world().resource::<Motd>().0, "Welcome!");
+}
+
+#[test]
+fn env_overrides_are_not_saved() {
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (50), fov: (90))")).unwrap();
+    std::env::set_var("ENV_SAVE_TEST_FOV", "110");
+
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        env_prefix: Some("ENV_SAVE_TEST_".into()),
+        ..plugin(&backend)
+    });
+    assert_eq!(*app.world().resource::<Fov>(), Fov(110));
+
+    app.world_mut().resource_mut::<Volume>().0 = 60;
+    update::<TestPrefs>(&mut app);
+    let loaded = test_app(plugin::<TestPrefs>(&backend));
+    assert_eq!(*loaded.world().resource::<Volume>(), Volume(60));
+    assert_eq!(*loaded.world().resource::<Fov>(), Fov(90));
+
+    // Overridden preferences that are changed are saved as usual.
+    app.world_mut().resource_mut::<Fov>().0 = 100;
+    update::<TestPrefs>(&mut app);
+    let loaded = test_app(plugin::<TestPrefs>(&backend));
+    assert_eq!(*loaded.world().resource::<Fov>(), Fov(100));
+}
+
+#[test]
+fn fragments_override_in_filename_order() {
+    let dir = TempDir::new();
+    std::fs::create_dir_all(dir.join("prefs.d")).unwrap();
+    std::fs::write(dir.join("prefs.ron"), "(volume: (50), fov: (90))").unwrap();
+    std::fs::write(dir.join("prefs.d/10_wide.ron"), "(fov: (110))").unwrap();
+    std::fs::write(dir.join("prefs.d/20_wider.ron"), "(fov: (120))").unwrap();
+    std::fs::write(dir.join("prefs.d/30_widest.ron.bak"), "(fov: (130))").unwrap();
+    std::fs::write(dir.join("prefs.d/README.txt"), "Not a fragment").unwrap();
+
+    let app = test_app(PrefsPlugin::<TestPrefs> {
+        fragment_dir: Some("prefs.d".into()),
+        ..file_plugin(&dir)
+    });
+    assert_eq!(*app.world().resource::<Volume>(), Volume(50));
+    assert_eq!(*app.world().resource::<Fov>(), Fov(120));
+}
+
+#[test]
+fn fragment_overrides_are_not_saved() {
+    let dir = TempDir::new();
+    std::fs::create_dir_all(dir.join("prefs.d")).unwrap();
+    std::fs::write(dir.join("prefs.ron"), "(volume: (50), fov: (90))").unwrap();
+    std::fs::write(dir.join("prefs.d/wide.ron"), "(fov: (110))").unwrap();
+
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        fragment_dir: Some("prefs.d".into()),
+        ..file_plugin(&dir)
+    });
+    assert_eq!(*app.world().resource::<Fov>(), Fov(110));
+
+    app.world_mut().resource_mut::<Volume>().0 = 60;
+    update::<TestPrefs>(&mut app);
+    let loaded = test_app(file_plugin::<TestPrefs>(&dir));
+    assert_eq!(*loaded.world().resource::<Volume>(), Volume(60));
+    assert_eq!(*loaded.world().resource::<Fov>(), Fov(90));
+}
+
+#[test]
+fn memory_backend_round_trip() {
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (30))")).unwrap();
+
+    let mut app = test_app(plugin::<TestPrefs>(&backend));
+    assert_eq!(*app.world().resource::<Volume>(), Volume(30));
+
+    app.world_mut().resource_mut::<Volume>().0 = 60;
+    TestPrefs::save_current(app.world_mut()).unwrap();
+    assert!(stored(&backend).contains("60"));
+
+    block_on(backend.delete("prefs.ron")).unwrap();
+    assert!(block_on(backend.load("prefs.ron")).unwrap().is_none());
+}
+
+#[test]
+fn file_backend_can_be_wrapped() {
+    let dir = TempDir::new();
+    let backend = crate::FileBackend {
+        mirror_dirs: vec![dir.join("mirror")],
+        ..crate::FileBackend::new(dir.join("prefs"))
+    };
+
+    let mut app = test_app(
+        PrefsPlugin::<TestPrefs> {
+            filename: "prefs.ron".into(),
+            blocking_load: true,
+            ..default()
+        }
+        .with_backend(std::sync::Arc::new(backend)),
+    );
+    app.world_mut().resource_mut::<Volume>().0 = 60;
+    TestPrefs::save_current(app.world_mut()).unwrap();
+
+    let saved = std::fs::read_to_string(dir.join("prefs/prefs.ron")).unwrap();
+    assert!(saved.contains("60"));
+    let mirrored = std::fs::read_to_string(dir.join("mirror/prefs.ron")).unwrap();
+    assert_eq!(saved, mirrored);
+
+    crate::clear_prefs::<TestPrefs>(app.world_mut());
+    assert!(!dir.join("prefs/prefs.ron").exists());
+    assert!(!dir.join("mirror/prefs.ron").exists());
+}
+
+#[test]
+fn async_backend_is_awaited() {
+    /// Yields before each operation, like a backend waiting on a network request.
+    struct Yielding(MemoryBackend);
+
+    impl StorageBackend for Yielding {
+        fn load<'a>(
+            &'a self,
+            key: &'a str,
+        ) -> BoxedFuture<'a, Result<Option<String>, crate::PrefsError>> {
+            Box::pin(async move {
+                bevy::tasks::futures_lite::future::yield_now().await;
+                self.0.load(key).await
+            })
+        }
+
+        fn save<'a>(
+            &'a self,
+            key: &'a str,
+            data: &'a str,
+        ) -> BoxedFuture<'a, Result<(), crate::PrefsError>> {
+            Box::pin(async move {
+                bevy::tasks::futures_lite::future::yield_now().await;
+                self.0.save(key, data).await
+            })
+        }
+
+        fn delete<'a>(&'a self, key: &'a str) -> BoxedFuture<'a, Result<(), crate::PrefsError>> {
+            Box::pin(async move {
+                bevy::tasks::futures_lite::future::yield_now().await;
+                self.0.delete(key).await
+            })
+        }
+    }
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (30))")).unwrap();
+
+    let mut app = test_app(plugin::<TestPrefs>(&backend).with_backend(Yielding(backend.clone())));
+    assert_eq!(*app.world().resource::<Volume>(), Volume(30));
+
+    app.world_mut().resource_mut::<Volume>().0 = 60;
+    update::<TestPrefs>(&mut app);
+    assert!(stored(&backend).contains("60"));
+}
+
+#[test]
+fn timestamp_is_recorded() {
+    let dir = TempDir::new();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        format: crate::FormatOptions {
+            timestamp: true,
+            ..default()
+        },
+        ..file_plugin(&dir)
+    });
+    TestPrefs::save_current(app.world_mut()).unwrap();
+
+    let metadata = TestPrefs::peek_metadata(dir.join("prefs.ron")).unwrap();
+    assert!(metadata.saved_at.is_some());
+}
+
+#[test]
+fn journal_is_replayed_until_torn_entry() {
+    let dir = TempDir::new();
+    std::fs::write(dir.join("prefs.ron"), "(volume: (50), fov: (90))").unwrap();
+    std::fs::write(
+        dir.join("prefs.ron.journal"),
+        "(volume:(60))\n(fov:(100))\n(volume:(70))\n(fov:(1",
+    )
+    .unwrap();
+
+    let app = test_app(PrefsPlugin::<TestPrefs> {
+        journal: Some(crate::PrefsJournal::default()),
+        ..file_plugin(&dir)
+    });
+    assert_eq!(*app.world().resource::<Volume>(), Volume(70));
+    assert_eq!(*app.world().resource::<Fov>(), Fov(100));
+}
+
+#[test]
+fn journals_are_named_after_prefs_file() {
+    let dir = TempDir::new();
+    std::fs::write(dir.join("a.ron"), "(volume: (50), fov: (90))").unwrap();
+    std::fs::write(dir.join("a.ron.journal"), "(volume:(60))\n").unwrap();
+    std::fs::write(dir.join("b.ron"), "(volume: (50), fov: (90))").unwrap();
+
+    let journal_plugin = |filename: &str| PrefsPlugin::<TestPrefs> {
+        filename: filename.into(),
+        journal: Some(crate::PrefsJournal::default()),
+        ..file_plugin(&dir)
+    };
+
+    let app = test_app(journal_plugin("a.ron"));
+    assert_eq!(*app.world().resource::<Volume>(), Volume(60));
+    let app = test_app(journal_plugin("b.ron"));
+    assert_eq!(*app.world().resource::<Volume>(), Volume(50));
+}
+
+#[test]
+fn history_is_named_after_prefs_file() {
+    let dir = TempDir::new();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        history: Some(crate::PrefsHistory::default()),
+        ..file_plugin(&dir)
+    });
+
+    app.world_mut().resource_mut::<Volume>().0 = 20;
+    update::<TestPrefs>(&mut app);
+    let history = std::fs::read_to_string(dir.join("prefs.ron.history")).unwrap();
+    assert!(history.contains("volume"));
+}
+
+#[cfg(feature = "secret")]
+#[test]
+fn history_does_not_contain_secret_values() {
+    #[derive(Prefs, Reflect, Default)]
+    struct SecretPrefs {
+        volume: Volume,
+        #[prefs(secret)]
+        token: Token,
+    }
+
+    #[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+    struct Token(String);
+
+    let dir = TempDir::new();
+    let mut app = test_app(PrefsPlugin::<SecretPrefs> {
+        history: Some(crate::PrefsHistory::default()),
+        format: crate::FormatOptions {
+            secret_key: Some(crate::SecretKey([7; 32])),
+            ..default()
+        },
+        ..file_plugin(&dir)
+    });
+
+    app.world_mut().resource_mut::<Token>().0 = "hunter2".into();
+    update::<SecretPrefs>(&mut app);
+    app.world_mut().resource_mut::<Token>().0 = "hunter3".into();
+    update::<SecretPrefs>(&mut app);
+    let history = std::fs::read_to_string(dir.join("prefs.ron.history")).unwrap();
+    assert!(history.contains("\"field\":\"token\""));
+    assert!(history.contains("<secret>"));
+    assert!(!history.contains("hunter"));
+}
+
+#[test]
+fn changes_during_load_are_overwritten() {
+    let backend = MemoryBackend::default();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        load_schedule: None,
+        blocking_load: false,
+        ..plugin(&backend)
+    });
+
+    TestPrefs::load(app.world_mut());
+    let status = app.world().resource::<PrefsStatus<TestPrefs>>();
+    assert_eq!(status.load_state, crate::PrefsLoadState::InFlight);
+
+    // This change is overwritten by the load rather than saved.
+    app.world_mut().resource_mut::<Volume>().0 = 20;
+    finish_tasks::<TestPrefs>(app.world_mut());
+    update::<TestPrefs>(&mut app);
+    let status = app.world().resource::<PrefsStatus<TestPrefs>>();
+    assert_eq!(status.load_state, crate::PrefsLoadState::Applied);
+    assert_eq!(status.save_count, 0);
+}
+
+#[test]
+fn changes_in_one_frame_are_saved_once() {
+    let backend = MemoryBackend::default();
+    let mut app = test_app(plugin::<TestPrefs>(&backend));
+
+    for volume in 0..10 {
+        app.world_mut().resource_mut::<Volume>().0 = volume;
+    }
+    update::<TestPrefs>(&mut app);
+    assert_eq!(saves::<TestPrefs>(&app), 1);
+}
+
+#[test]
+fn last_save_bytes_is_recorded() {
+    let backend = MemoryBackend::default();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        load_schedule: None,
+        ..plugin(&backend)
+    });
+    let status = app.world().resource::<PrefsStatus<TestPrefs>>();
+    assert_eq!(status.last_save_bytes, None);
+
+    TestPrefs::save_current(app.world_mut()).unwrap();
+    let status = app.world().resource::<PrefsStatus<TestPrefs>>();
+    assert_eq!(status.last_save_bytes, Some(stored(&backend).len()));
+}
+
+#[test]
+fn paused_autosave_saves_once_resumed() {
+    use crate::PrefsCommandsExt;
+
+    let backend = MemoryBackend::default();
+    let mut app = test_app(plugin::<TestPrefs>(&backend));
+
+    app.world_mut()
+        .commands()
+        .pause_prefs_autosave::<TestPrefs>();
+    for volume in 0..10 {
+        app.world_mut().resource_mut::<Volume>().0 = volume;
+        update::<TestPrefs>(&mut app);
+    }
+    assert_eq!(saves::<TestPrefs>(&app), 0);
+
+    app.world_mut()
+        .commands()
+        .resume_prefs_autosave::<TestPrefs>();
+    update::<TestPrefs>(&mut app);
+    update::<TestPrefs>(&mut app);
+    assert_eq!(saves::<TestPrefs>(&app), 1);
+}
+
+#[test]
+fn last_error_records_failed_load() {
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: oops)")).unwrap();
+
+    let app = test_app(plugin::<TestPrefs>(&backend));
+    let status = app.world().resource::<PrefsStatus<TestPrefs>>();
+    assert!(matches!(
+        status.last_error,
+        Some(crate::PrefsError::Deserialize(_))
+    ));
+}
+
+#[test]
+fn loaded_version_is_recorded() {
+    #[derive(Prefs, Reflect, Default)]
+    #[prefs(version = 1)]
+    struct VersionedPrefs {
+        volume: Volume,
+    }
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "// version: 3\n(volume: (20))")).unwrap();
+
+    let app = test_app(PrefsPlugin::<VersionedPrefs> {
+        read_only: true,
+        ..plugin(&backend)
+    });
+    let status = app.world().resource::<PrefsStatus<VersionedPrefs>>();
+    assert_eq!(status.loaded_version, Some(3));
+    assert!(status.loaded_version > Some(VersionedPrefs::VERSION));
+}
+
+#[test]
+fn saved_event_reports_unchanged_saves() {
+    let backend = MemoryBackend::default();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        load_schedule: None,
+        ..plugin(&backend)
+    });
+
+    app.world_mut().resource_mut::<Volume>().0 = 5;
+    TestPrefs::save_current(app.world_mut()).unwrap();
+
+    app.world_mut().resource_mut::<Volume>().0 = 6;
+    app.world_mut().resource_mut::<Volume>().0 = 5;
+    TestPrefs::save_current(app.world_mut()).unwrap();
+
+    let changed: Vec<bool> = app
+        .world_mut()
+        .resource_mut::<Events<crate::PrefsSaved<TestPrefs>>>()
+        .drain()
+        .map(|saved| saved.changed)
+        .collect();
+    assert_eq!(changed, [true, false]);
+}
+
+#[test]
+fn previewed_value_isnt_saved() {
+    use crate::{Persisted, PrefsCommandsExt};
+
+    let backend = MemoryBackend::default();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        load_schedule: None,
+        ..plugin(&backend)
+    });
+
+    app.world_mut().commands().preview_pref(Volume(80));
+    app.world_mut().flush();
+    assert_eq!(*app.world().resource::<Volume>(), Volume(80));
+    assert_eq!(app.world().resource::<Persisted<Volume>>().0, Volume(0));
+
+    TestPrefs::save_current(app.world_mut()).unwrap();
+    let stored = crate::peek_str::<TestPrefs>(stored(&backend)).unwrap();
+    assert_eq!(stored.volume, Volume(0));
+
+    app.world_mut().commands().revert_pref::<Volume>();
+    app.world_mut().flush();
+    assert_eq!(*app.world().resource::<Volume>(), Volume(0));
+    assert!(!app.world().contains_resource::<Persisted<Volume>>());
+}
+
+#[test]
+fn registry_saves_every_type() {
+    #[derive(Prefs, Reflect, Default)]
+    struct AudioPrefs {
+        volume: Volume,
+    }
+
+    #[derive(Prefs, Reflect, Default)]
+    struct VideoPrefs {
+        fov: Fov,
+    }
+
+    let backend = MemoryBackend::default();
+    let mut app = App::new();
+    app.add_plugins((
+        PrefsPlugin::<AudioPrefs> {
+            filename: "audio.ron".into(),
+            ..default()
+        }
+        .with_backend(backend.clone()),
+        PrefsPlugin::<VideoPrefs> {
+            filename: "video.ron".into(),
+            ..default()
+        }
+        .with_backend(backend.clone()),
+    ));
+
+    let registrations: Vec<_> = app
+        .world()
+        .resource::<crate::PrefsRegistry>()
+        .iter()
+        .cloned()
+        .collect();
+    for registration in registrations {
+        (registration.save_current)(app.world_mut()).unwrap();
+    }
+    assert!(block_on(backend.load("audio.ron")).unwrap().is_some());
+    assert!(block_on(backend.load("video.ron")).unwrap().is_some());
+}
+
+#[test]
+fn archive_round_trip() {
+    use crate::{export_all_prefs, import_all_prefs};
+
+    let backend = MemoryBackend::default();
+    let mut app = test_app(plugin::<TestPrefs>(&backend));
+
+    app.world_mut().insert_resource(Volume(20));
+    let archive = export_all_prefs(app.world()).unwrap();
+
+    app.world_mut().insert_resource(Volume(80));
+    import_all_prefs(app.world_mut(), &archive).unwrap();
+    assert_eq!(*app.world().resource::<Volume>(), Volume(20));
+}
+
+#[test]
+fn archive_import_is_all_or_nothing() {
+    use crate::import_all_prefs;
+
+    #[derive(Prefs, Reflect, Default)]
+    struct AudioPrefs {
+        volume: Volume,
+    }
+
+    #[derive(Prefs, Reflect, Default)]
+    struct VideoPrefs {
+        fov: Fov,
+    }
+
+    let backend = MemoryBackend::default();
+    let mut app = App::new();
+    app.add_plugins((
+        PrefsPlugin::<AudioPrefs> {
+            filename: "audio.ron".into(),
+            blocking_load: true,
+            ..default()
+        }
+        .with_backend(backend.clone()),
+        PrefsPlugin::<VideoPrefs> {
+            filename: "video.ron".into(),
+            blocking_load: true,
+            ..default()
+        }
+        .with_backend(backend.clone()),
+    ));
+    app.update();
+
+    let archive = ron::to_string(&std::collections::BTreeMap::from([
+        (AudioPrefs::type_path(), "(volume: (20))"),
+        (VideoPrefs::type_path(), "(fov: \"wide\")"),
+    ]))
+    .unwrap();
+
+    assert!(import_all_prefs(app.world_mut(), &archive).is_err());
+    assert_eq!(*app.world().resource::<Volume>(), Volume(0));
+    assert!(block_on(backend.load("audio.ron")).unwrap().is_none());
+}
+
+#[test]
+fn slots_are_stored_separately() {
+    use crate::{stored_slots, PrefsSlot};
+
+    let dir = TempDir::new();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        slots: 3,
+        ..file_plugin(&dir)
+    });
+
+    app.world_mut().resource_mut::<Volume>().0 = 5;
+    app.world_mut()
+        .resource_mut::<PrefsSlot<TestPrefs>>()
+        .active = 1;
+    update::<TestPrefs>(&mut app);
+
+    assert_eq!(*app.world().resource::<Volume>(), Volume(0));
+    assert!(dir.join("prefs_slot0.ron").exists());
+    assert_eq!(stored_slots::<TestPrefs>(app.world()), [0]);
+
+    app.world_mut()
+        .resource_mut::<PrefsSlot<TestPrefs>>()
+        .active = 0;
+    update::<TestPrefs>(&mut app);
+    assert_eq!(*app.world().resource::<Volume>(), Volume(5));
+}
+
+#[test]
+fn slot_status_is_tracked_per_slot() {
+    use crate::{slot_status, PrefsSlot};
+
+    let dir = TempDir::new();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        slots: 3,
+        ..file_plugin(&dir)
+    });
+
+    app.world_mut().resource_mut::<Volume>().0 = 5;
+    update::<TestPrefs>(&mut app);
+    app.world_mut()
+        .resource_mut::<PrefsSlot<TestPrefs>>()
+        .active = 1;
+    update::<TestPrefs>(&mut app);
+
+    let slot0 = slot_status::<TestPrefs>(app.world(), 0).unwrap();
+    assert_eq!(slot0.save_count, 1);
+    assert!(slot0.last_save_bytes.is_some());
+    assert!(slot0.content_hash.is_some());
+
+    // The newly active slot starts out with its own status, rather than that of slot 0.
+    let slot1 = slot_status::<TestPrefs>(app.world(), 1).unwrap();
+    assert!(slot1.loaded);
+    assert_eq!(slot1.save_count, 0);
+    assert_eq!(slot1.last_save_bytes, None);
+    assert_eq!(slot1.content_hash, None);
+    assert_eq!(
+        app.world()
+            .resource::<PrefsStatus<TestPrefs>>()
+            .last_save_bytes,
+        None
+    );
+    assert_eq!(slot_status::<TestPrefs>(app.world(), 2), None);
+
+    update::<TestPrefs>(&mut app);
+    app.world_mut().resource_mut::<Volume>().0 = 7;
+    update::<TestPrefs>(&mut app);
+    assert_eq!(
+        slot_status::<TestPrefs>(app.world(), 1).unwrap().save_count,
+        1
+    );
+    assert_eq!(
+        slot_status::<TestPrefs>(app.world(), 0),
+        Some(slot0.clone())
+    );
+
+    // Returning to a slot picks up where it left off.
+    app.world_mut()
+        .resource_mut::<PrefsSlot<TestPrefs>>()
+        .active = 0;
+    update::<TestPrefs>(&mut app);
+    let status = app.world().resource::<PrefsStatus<TestPrefs>>();
+    assert_eq!(status.last_save_bytes, slot0.last_save_bytes);
+    assert_eq!(
+        slot_status::<TestPrefs>(app.world(), 0).unwrap().save_count,
+        1
+    );
+}
+
+#[test]
+fn on_loaded_systems_run_once() {
+    #[derive(Resource, Default)]
+    struct AudioEngine {
+        volume: u32,
+        configured: u32,
+    }
+
+    fn configure_audio(volume: Res<Volume>, mut engine: ResMut<AudioEngine>) {
+        engine.volume = volume.0;
+        engine.configured += 1;
+    }
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (40))")).unwrap();
+
+    IoTaskPool::get_or_init(TaskPool::new);
+    let mut app = App::new();
+    app.init_resource::<AudioEngine>();
+    app.add_plugins(plugin::<TestPrefs>(&backend).on_loaded(configure_audio));
+    update::<TestPrefs>(&mut app);
+    update::<TestPrefs>(&mut app);
+
+    let engine = app.world().resource::<AudioEngine>();
+    assert_eq!((engine.volume, engine.configured), (40, 1));
+}
+
+#[test]
+fn remove_prefs_drops_in_progress_load() {
+    use crate::{remove_prefs, PrefsSettings};
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (50))")).unwrap();
+
+    IoTaskPool::get_or_init(TaskPool::new);
+    let mut app = App::new();
+    app.add_plugins(PrefsPlugin::<TestPrefs> {
+        blocking_load: false,
+        ..plugin(&backend)
+    });
+
+    // Start loading, then tear the plugin down before the load is handled.
+    app.world_mut().run_schedule(Startup);
+    remove_prefs::<TestPrefs>(app.world_mut());
+    update::<TestPrefs>(&mut app);
+
+    assert!(!app.world().contains_resource::<PrefsSettings<TestPrefs>>());
+    assert_eq!(*app.world().resource::<Volume>(), Volume(0));
+}
+
+#[test]
+fn just_loaded_is_true_once() {
+    use crate::{prefs_just_loaded, PrefsSet};
+
+    #[derive(Resource, Default)]
+    struct Runs(u32);
+
+    let backend = MemoryBackend::default();
+    let mut app = test_app(plugin::<TestPrefs>(&backend));
+    app.init_resource::<Runs>();
+    app.add_systems(
+        Update,
+        (|mut runs: ResMut<Runs>| runs.0 += 1)
+            .run_if(prefs_just_loaded::<TestPrefs>())
+            .after(PrefsSet::Load),
+    );
+
+    update::<TestPrefs>(&mut app);
+    update::<TestPrefs>(&mut app);
+    assert_eq!(app.world().resource::<Runs>().0, 1);
+}
+
+#[derive(Prefs, Reflect, Default)]
+struct TransientPrefs {
+    volume: Volume,
+    #[prefs(transient)]
+    fov: Fov,
+}
+
+#[test]
+fn transient_values_survive_saves() {
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (20), fov: (110))")).unwrap();
+
+    let mut app = test_app(plugin::<TransientPrefs>(&backend));
+    assert_eq!(*app.world().resource::<Fov>(), Fov(110));
+
+    app.world_mut().insert_resource(Volume(30));
+    app.world_mut().insert_resource(Fov(90));
+    update::<TransientPrefs>(&mut app);
+    assert_eq!(saves::<TransientPrefs>(&app), 1);
+
+    let app = test_app(plugin::<TransientPrefs>(&backend));
+    assert_eq!(*app.world().resource::<Volume>(), Volume(30));
+    assert_eq!(*app.world().resource::<Fov>(), Fov(110));
+}
+
+#[test]
+fn compact_keeps_transient_values() {
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (20), fov: (110), show_fps: (true))")).unwrap();
+
+    let mut app = test_app(PrefsPlugin::<TransientPrefs> {
+        load_schedule: None,
+        ..plugin(&backend)
+    });
+    assert!(TransientPrefs::compact(app.world_mut()).unwrap());
+    assert!(!stored(&backend).contains("show_fps"));
+
+    // Saving again after compacting keeps the transient value too.
+    app.world_mut().insert_resource(Volume(30));
+    TransientPrefs::save_current(app.world_mut()).unwrap();
+
+    let app = test_app(plugin::<TransientPrefs>(&backend));
+    assert_eq!(*app.world().resource::<Volume>(), Volume(30));
+    assert_eq!(*app.world().resource::<Fov>(), Fov(110));
+}
+
+#[test]
+fn snapshots_include_transient_fields() {
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (20), fov: (110))")).unwrap();
+
+    let mut app = test_app(plugin::<TransientPrefs>(&backend));
+    app.world_mut().insert_resource(Fov(90));
+    let snapshot = TransientPrefs::snapshot(app.world()).unwrap();
+
+    app.world_mut().insert_resource(Fov(60));
+    TransientPrefs::restore(app.world_mut(), &snapshot).unwrap();
+    assert_eq!(*app.world().resource::<Fov>(), Fov(90));
+
+    // The stored value of the transient field is kept.
+    let app = test_app(plugin::<TransientPrefs>(&backend));
+    assert_eq!(*app.world().resource::<Fov>(), Fov(110));
+}
+
+#[derive(Prefs, Reflect, Default, PartialEq, Debug)]
+struct PrimitivePrefs {
+    flag: Flag,
+    offset: Offset,
+    ratio: Ratio,
+    letter: Letter,
+    name: Name,
+    volume: Volume,
+    sizes: Sizes,
+    display: Display,
+}
+
+#[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+struct Flag(bool);
+
+#[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+struct Offset(i8);
+
+#[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+struct Ratio(f64);
+
+#[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+struct Letter(char);
+
+#[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+struct Name(String);
+
+#[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+struct Sizes(Vec<u32>);
+
+#[derive(Resource, Reflect, Clone, PartialEq, Debug, Default)]
+struct Display {
+    scale: Ratio,
+    fullscreen: Flag,
+}
+
+#[test]
+fn primitive_fields_match_reflection() {
+    let prefs = PrimitivePrefs {
+        flag: Flag(true),
+        offset: Offset(-3),
+        ratio: Ratio(0.25),
+        letter: Letter('q'),
+        name: Name("Ferris \"the crab\"".into()),
+        volume: Volume(70),
+        sizes: Sizes(vec![1, 2]),
+        display: Display {
+            scale: Ratio(1.5),
+            fullscreen: Flag(true),
+        },
+    };
+
+    let mut registry = bevy::reflect::TypeRegistry::new();
+    registry.register::<PrimitivePrefs>();
+    let reflected = ron::ser::to_string_pretty(
+        &bevy::reflect::serde::TypedReflectSerializer::new(&prefs, &registry),
+        ron::ser::PrettyConfig::default(),
+    )
+    .unwrap();
+
+    assert_eq!(crate::serialize(&prefs).unwrap(), reflected);
+    assert_eq!(
+        crate::deserialize::<PrimitivePrefs>(&reflected).unwrap(),
+        prefs
+    );
+
+    // Newtypes may also be written with their names, like reflection allows.
+    let named = "(flag: Flag(true), offset: Offset(-3), ratio: Ratio(0.25), letter: Letter('q'), \
+                 name: Name(\"Ferris \\\"the crab\\\"\"), volume: Volume(70), sizes: Sizes([1, 2]), \
+                 display: Display(scale: Ratio(1.5), fullscreen: Flag(true)))";
+    assert_eq!(crate::deserialize::<PrimitivePrefs>(named).unwrap(), prefs);
+}
+
+#[test]
+fn next_to_executable_removes_its_probe() {
+    let plugin = PrefsPlugin::<TestPrefs>::next_to_executable().unwrap();
+
+    let probes = std::fs::read_dir(&plugin.path)
+        .unwrap()
+        .filter(|entry| {
+            let name = entry.as_ref().unwrap().file_name();
+            name.to_string_lossy().starts_with(".prefs_probe_")
+        })
+        .count();
+    assert_eq!(probes, 0);
+}
+
+#[test]
+fn schema_hash_ignores_cfg_fields() {
+    use crate::{deserialize_with_options, schema_hash, serialize_with_options, FormatOptions};
+
+    // The same preferences, as compiled on a platform with and without `fov`.
+    #[derive(Prefs, Reflect, Default)]
+    struct WithFov {
+        volume: Volume,
+        #[cfg(test)]
+        fov: Fov,
+    }
+
+    #[derive(Prefs, Reflect, Default)]
+    struct WithoutFov {
+        volume: Volume,
+        #[cfg(not(test))]
+        fov: Fov,
+    }
+
+    assert_eq!(WithFov::CFG_FIELDS, ["fov"]);
+    assert!(WithoutFov::CFG_FIELDS.is_empty());
+    assert_eq!(schema_hash::<WithFov>(), schema_hash::<WithoutFov>());
+    assert_ne!(schema_hash::<WithFov>(), schema_hash::<TestPrefs>());
+
+    let options = FormatOptions {
+        schema_hash: true,
+        ..default()
+    };
+    let saved = serialize_with_options(&WithFov::default(), &options).unwrap();
+    assert!(deserialize_with_options::<WithoutFov>(&saved, &options).is_ok());
+}
+
+#[test]
+fn field_type_names_use_type_paths() {
+    type FovAlias = Fov;
+
+    #[derive(Prefs, Reflect, Default)]
+    struct SpelledPrefs {
+        volume: self::Volume,
+        fov: FovAlias,
+    }
+
+    assert_eq!(SpelledPrefs::field_type_names(), ["Volume", "Fov"]);
+}
+
+#[test]
+fn failed_saves_are_retried() {
+    use crate::{PrefsSaveError, PrefsSaveRetry, SaveRetry};
+
+    let dir = TempDir::new();
+    let mut app = test_app(PrefsPlugin::<TestPrefs> {
+        save_retry: SaveRetry {
+            retries: 1,
+            delay: std::time::Duration::from_millis(50),
+        },
+        ..file_plugin(&dir.join("local"))
+    });
+
+    // A file in place of the preferences directory makes saving fail until it's removed.
+    std::fs::write(dir.join("local"), "").unwrap();
+    app.insert_resource(Volume(30));
+    update_until(&mut app, |world| {
+        world.contains_resource::<PrefsSaveRetry<TestPrefs>>()
+    });
+    assert!(!app.world().contains_resource::<PrefsSaveError<TestPrefs>>());
+
+    std::fs::remove_file(dir.join("local")).unwrap();
+    update_until(&mut app, |world| {
+        !world.contains_resource::<PrefsSaveRetry<TestPrefs>>()
+    });
+    assert!(!app.world().contains_resource::<PrefsSaveError<TestPrefs>>());
+    let saved = std::fs::read_to_string(dir.join("local/prefs.ron")).unwrap();
+    assert!(saved.contains("volume: (30)"));
+
+    // Once the retries are used up, the error is reported.
+    std::fs::remove_dir_all(dir.join("local")).unwrap();
+    std::fs::write(dir.join("local"), "").unwrap();
+    let saves_before = saves::<TestPrefs>(&app);
+    app.insert_resource(Volume(40));
+    update_until(&mut app, |world| {
+        world.contains_resource::<PrefsSaveError<TestPrefs>>()
+    });
+    assert_eq!(saves::<TestPrefs>(&app), saves_before + 2);
+}
+
+#[test]
+fn read_can_be_split_into_raw_and_finish() {
+    use crate::PrefsSettings;
+
+    let backend = MemoryBackend::default();
+    block_on(backend.save("prefs.ron", "(volume: (10))")).unwrap();
+    let app = test_app(PrefsPlugin::<TestPrefs> {
+        post_read: Some(|serialized| serialized.replace("(10)", "(20)")),
+        ..plugin(&backend)
+    });
+
+    let settings = app.world().resource::<PrefsSettings<TestPrefs>>();
+    let raw = settings.read_raw();
+    assert_eq!(raw.as_ref().unwrap().as_deref(), Some("(volume: (10))"));
+    assert_eq!(settings.finish_read(raw), settings.read());
+    assert_eq!(*app.world().resource::<Volume>(), Volume(20));
+}
+
+#[test]
+fn kebab_case_keys_round_trip() {
+    #[derive(Prefs, Reflect, Default)]
+    #[prefs(rename_all = "kebab-case")]
+    struct KebabPrefs {
+        master_volume: Volume,
+        field_of_view: Fov,
+    }
+
+    let dir = TempDir::new();
+    let kebab_plugin = || PrefsPlugin::<KebabPrefs> {
+        journal: Some(crate::PrefsJournal::default()),
+        history: Some(crate::PrefsHistory::default()),
+        ..file_plugin(&dir)
+    };
+
+    let mut app = test_app(kebab_plugin());
+    app.insert_resource(Volume(20));
+    app.insert_resource(Fov(90));
+    KebabPrefs::save_current(app.world_mut()).unwrap();
+    let saved = std::fs::read_to_string(dir.join("prefs.ron")).unwrap();
+    assert!(saved.contains("master-volume: (20)"));
+    assert!(saved.contains("field-of-view: (90)"));
+
+    // A change to one field is appended to the journal and logged in the history.
+    update::<KebabPrefs>(&mut app);
+    app.world_mut().resource_mut::<Volume>().0 = 30;
+    update::<KebabPrefs>(&mut app);
+    let journal = std::fs::read_to_string(dir.join("prefs.ron.journal")).unwrap();
+    assert_eq!(journal, "(r#master-volume:(30))\n");
+    let history = std::fs::read_to_string(dir.join("prefs.ron.history")).unwrap();
+    assert!(history.contains("\"field\":\"master-volume\""));
+
+    let app = test_app(kebab_plugin());
+    assert_eq!(*app.world().resource::<Volume>(), Volume(30));
+    assert_eq!(*app.world().resource::<Fov>(), Fov(90));
+}
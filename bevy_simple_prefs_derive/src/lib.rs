@@ -2,11 +2,226 @@
 
 extern crate proc_macro;
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, Data, DeriveInput, Fields};
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::{parse_macro_input, spanned::Spanned, Attribute, Data, DeriveInput, Field, Fields};
+
+/// Attributes that can be applied to individual fields with `#[prefs(...)]`.
+#[derive(Default)]
+struct FieldAttrs {
+    /// The field's value is encrypted when saved.
+    secret: bool,
+    /// The field is loaded, but never saved.
+    transient: bool,
+    /// Changes to the field only take effect after the app restarts.
+    restart_required: bool,
+    /// A function that validates or corrects the field's value.
+    validate: Option<syn::ExprPath>,
+}
+
+impl FieldAttrs {
+    fn parse(field: &Field) -> syn::Result<Self> {
+        let mut attrs = Self::default();
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("prefs")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("secret") {
+                    attrs.secret = true;
+                    Ok(())
+                } else if meta.path.is_ident("transient") {
+                    attrs.transient = true;
+                    Ok(())
+                } else if meta.path.is_ident("restart_required") {
+                    attrs.restart_required = true;
+                    Ok(())
+                } else if meta.path.is_ident("validate") {
+                    let path: syn::LitStr = meta.value()?.parse()?;
+                    attrs.validate = Some(path.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported prefs attribute"))
+                }
+            })?;
+        }
+
+        Ok(attrs)
+    }
+}
+
+/// Returns the `#[cfg(...)]` attributes of a field, and an expression that is `true` if the field
+/// is compiled, if it has any.
+fn field_cfg(field: &Field) -> syn::Result<(Vec<&Attribute>, Option<TokenStream2>)> {
+    let attrs: Vec<_> = field
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("cfg"))
+        .collect();
+    let predicates = attrs
+        .iter()
+        .map(|a| Ok(a.meta.require_list()?.tokens.clone()))
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let enabled = (!predicates.is_empty()).then(|| quote! { cfg!(all(#(#predicates),*)) });
+
+    Ok((attrs, enabled))
+}
+
+/// Returns the doc comment of a field, one line per `#[doc = "..."]` attribute, or an empty string
+/// if it has none.
+fn field_docs_of(field: &Field) -> String {
+    let lines: Vec<_> = field
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("doc"))
+        .filter_map(|a| match &a.meta.require_name_value().ok()?.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(doc),
+                ..
+            }) => Some(doc.value()),
+            _ => None,
+        })
+        .collect();
+
+    // Doc comments are written as `/// text`, so each line has a leading space.
+    lines
+        .iter()
+        .map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns a `&'static [&'static str]` expression containing the names of the compiled fields.
+fn names_const(names: &[(String, Option<TokenStream2>)]) -> TokenStream2 {
+    if names.iter().all(|(_, enabled)| enabled.is_none()) {
+        let names = names.iter().map(|(name, _)| name);
+        return quote! { &[#(#names),*] };
+    }
+
+    let len = names.len();
+    let entries = names.iter().map(|(name, enabled)| {
+        let enabled = enabled.clone().unwrap_or_else(|| quote! { true });
+        quote! { (#name, #enabled) }
+    });
+
+    quote! {
+        {
+            const NAMES: ([&str; #len], usize) =
+                ::bevy_simple_prefs::__compiled_names([#(#entries),*]);
+            NAMES.0.split_at(NAMES.1).0
+        }
+    }
+}
+
+/// Attributes that can be applied to the struct with `#[prefs(...)]`.
+#[derive(Default)]
+struct StructAttrs {
+    /// The version of the preferences.
+    version: Option<syn::LitInt>,
+    /// The casing of the keys that the fields are written under.
+    rename_all: Option<syn::LitStr>,
+}
+
+impl StructAttrs {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut attrs = Self::default();
+
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("prefs")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("version") {
+                    attrs.version = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("rename_all") {
+                    let rule: syn::LitStr = meta.value()?.parse()?;
+                    if rename("", &rule.value()).is_none() {
+                        return Err(syn::Error::new(
+                            rule.span(),
+                            format!(
+                                "unsupported rename_all casing, expected one of: {}",
+                                RENAME_RULES.join(", ")
+                            ),
+                        ));
+                    }
+                    attrs.rename_all = Some(rule);
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported prefs attribute"))
+                }
+            })?;
+        }
+
+        Ok(attrs)
+    }
+}
+
+/// Casings supported by `#[prefs(rename_all = "...")]`.
+const RENAME_RULES: &[&str] = &[
+    "snake_case",
+    "kebab-case",
+    "camelCase",
+    "PascalCase",
+    "SCREAMING_SNAKE_CASE",
+];
+
+/// Converts a snake_case field name to the given casing, or returns `None` if the casing isn't
+/// supported.
+fn rename(name: &str, rule: &str) -> Option<String> {
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    };
+    let words = name.split('_').filter(|word| !word.is_empty());
+
+    Some(match rule {
+        "snake_case" => name.to_string(),
+        "kebab-case" => name.replace('_', "-"),
+        "camelCase" => words
+            .enumerate()
+            .map(|(i, word)| {
+                if i == 0 {
+                    word.to_string()
+                } else {
+                    capitalize(word)
+                }
+            })
+            .collect(),
+        "PascalCase" => words.map(capitalize).collect(),
+        "SCREAMING_SNAKE_CASE" => name.to_uppercase(),
+        _ => return None,
+    })
+}
 
 /// Derive macro for `bevy_simple_prefs`.
-#[proc_macro_derive(Prefs)]
+///
+/// The struct may be annotated with `#[prefs(version = 1)]` to record a version in the
+/// preferences file. See `Prefs::VERSION`.
+///
+/// The struct may be annotated with `#[prefs(rename_all = "kebab-case")]` to write its fields
+/// under keys with a different casing. See `Prefs::FIELD_KEYS`.
+///
+/// Fields may be annotated with `#[prefs(secret)]` to encrypt their values when saved. This
+/// requires the `secret` feature of `bevy_simple_prefs` and a key in `FormatOptions::secret_key`.
+///
+/// Fields annotated with `#[prefs(transient)]` are loaded from the preferences file, but changes
+/// to them never trigger a save and they are never written back. Their stored values are kept in
+/// the file as they were loaded.
+///
+/// Fields may be annotated with `#[prefs(restart_required)]` to record that changes to them only
+/// take effect after the app restarts. This doesn't change how they are saved or loaded. See
+/// `Prefs::RESTART_REQUIRED_FIELDS`.
+///
+/// Fields may be annotated with `#[prefs(validate = "path::to::function")]` to correct their
+/// values with a `fn(&mut T)`, both after they are loaded and whenever they change, before they are
+/// saved. Loaded values that it corrects are written back.
+///
+/// Fields may be conditionally compiled with `#[cfg(...)]`. Fields that aren't compiled are
+/// ignored when loading a preferences file that contains them.
+///
+/// Each field is stored as the `Resource` of its type, so the types of the fields must be
+/// distinct, unless the fields are never compiled together.
+#[proc_macro_derive(Prefs, attributes(prefs))]
 pub fn prefs_derive(input: TokenStream) -> TokenStream {
     // Parse the input tokens into a syntax tree
     let input = parse_macro_input!(input as DeriveInput);
@@ -14,15 +229,46 @@ pub fn prefs_derive(input: TokenStream) -> TokenStream {
     // Get the struct name
     let name = &input.ident;
 
+    let struct_attrs = match StructAttrs::parse(&input) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let version = struct_attrs.version.map(|version| {
+        quote! { const VERSION: u32 = #version; }
+    });
+    let rename_all = struct_attrs.rename_all.map(|rule| rule.value());
+
     // Generate the code
     let expanded = match input.data {
         Data::Struct(ref data_struct) => {
             let mut field_bindings = Vec::new();
             let mut field_checks = Vec::new();
+            let mut field_change_names = Vec::new();
             let mut fields = Vec::new();
             let mut field_assignments = Vec::new();
+            let mut field_swaps = Vec::new();
+            let mut field_currents = Vec::new();
             let mut field_inits = Vec::new();
             let mut field_inserts = Vec::new();
+            let mut field_loads = Vec::new();
+            let mut field_stages = Vec::new();
+            let mut field_commits = Vec::new();
+            let mut field_unstages = Vec::new();
+            let mut field_history_changes = Vec::new();
+            let mut field_history_values = Vec::new();
+            let mut field_validations = Vec::new();
+            let mut field_change_validations = Vec::new();
+            let mut field_load_logs = Vec::new();
+            let mut field_names = Vec::new();
+            let mut field_keys = Vec::new();
+            let mut field_type_names = Vec::new();
+            let mut field_docs = Vec::new();
+            let mut secret_fields = Vec::new();
+            let mut secret_checks = Vec::new();
+            let mut transient_fields = Vec::new();
+            let mut restart_required_fields = Vec::new();
+            let mut cfg_fields = Vec::new();
+            let mut distinct_checks = Vec::new();
 
             // Iterate over the fields of the struct
             match &data_struct.fields {
@@ -30,24 +276,212 @@ pub fn prefs_derive(input: TokenStream) -> TokenStream {
                     for field in &fields_named.named {
                         let field_name = &field.ident;
                         let field_type = &field.ty;
+                        let field_name_str = field_name.as_ref().unwrap().to_string();
+
+                        let attrs = match FieldAttrs::parse(field) {
+                            Ok(attrs) => attrs,
+                            Err(e) => return e.to_compile_error().into(),
+                        };
+                        let (cfg, enabled) = match field_cfg(field) {
+                            Ok(cfg) => cfg,
+                            Err(e) => return e.to_compile_error().into(),
+                        };
 
-                        field_bindings.push(quote! {
-                            let #field_name = world.get_resource_ref::<#field_type>().unwrap();
+                        // Each field is stored as the `Resource` of its type, so two fields of the
+                        // same type would share a value. Implementing a trait for each field type
+                        // lets the compiler reject duplicates, including through type aliases,
+                        // while fields that are never compiled together are fine.
+                        distinct_checks.push(quote_spanned! {field_type.span()=>
+                            #(#cfg)*
+                            impl EachFieldMustHaveADistinctType for #field_type {}
                         });
-                        field_checks.push(quote! {
-                            !#field_name.is_changed()
+
+                        field_names.push((field_name_str.clone(), enabled.clone()));
+                        if let Some(rule) = &rename_all {
+                            field_keys.push((
+                                rename(&field_name_str, rule).unwrap_or_default(),
+                                enabled.clone(),
+                            ));
+                        }
+                        field_type_names.push(quote! {
+                            #(#cfg)*
+                            names.push(<#field_type as ::bevy::reflect::TypePath>::short_type_path());
                         });
+                        field_docs.push((field_docs_of(field), enabled.clone()));
+                        if attrs.secret {
+                            secret_fields.push((field_name_str.clone(), enabled.clone()));
+                            secret_checks.push(quote! {
+                                #(#cfg)*
+                                ::bevy_simple_prefs::__secret_field!(#field_name_str);
+                            });
+                        }
+                        if attrs.restart_required {
+                            restart_required_fields.push((field_name_str.clone(), enabled.clone()));
+                        }
+                        if enabled.is_some() {
+                            cfg_fields.push((field_name_str.clone(), enabled.clone()));
+                        }
+
                         fields.push(quote! {
                             #field_name: #field_type
                         });
-                        field_assignments.push(quote! {
-                            #field_name: #field_name.clone()
+
+                        let logged_value = match attrs.secret {
+                            true => quote!("<secret>"),
+                            false => quote! {
+                                format!(
+                                    "{:?}",
+                                    ::bevy::reflect::PartialReflect::as_partial_reflect(&val.#field_name)
+                                )
+                            },
+                        };
+                        // Secret values are never written to the history log in plaintext.
+                        let history_value = |value: TokenStream2| match attrs.secret {
+                            true => quote! {
+                                Ok::<_, ::bevy_simple_prefs::PrefsError>(String::from("<secret>"))
+                            },
+                            false => quote!(::bevy_simple_prefs::serialize_compact(#value)),
+                        };
+                        let changed_history_value = history_value(quote!(&*#field_name));
+                        let loaded_history_value = history_value(quote!(&val.#field_name));
+                        field_load_logs.push(quote! {
+                            #(#cfg)*
+                            summary.push_str(&format!("\n  {}: {}", #field_name_str, #logged_value));
+                        });
+
+                        if let Some(validate) = &attrs.validate {
+                            field_validations.push(quote! {
+                                #(#cfg)*
+                                {
+                                    let loaded = val.#field_name.clone();
+                                    #validate(&mut val.#field_name);
+                                    write_back |= ::bevy::reflect::PartialReflect::reflect_partial_eq(
+                                        &loaded,
+                                        &val.#field_name,
+                                    ) != Some(true);
+                                }
+                            });
+                            // Bypass change detection, which would otherwise trigger another save.
+                            field_change_validations.push(quote! {
+                                #(#cfg)*
+                                if world.resource_ref::<#field_type>().is_changed() {
+                                    #validate(
+                                        world.resource_mut::<#field_type>().bypass_change_detection(),
+                                    );
+                                }
+                            });
+                        }
+
+                        if attrs.transient {
+                            transient_fields.push((field_name_str.clone(), enabled));
+                            field_assignments.push(quote! {
+                                #(#cfg)*
+                                #field_name: Default::default()
+                            });
+                        } else {
+                            field_bindings.push(quote! {
+                                #(#cfg)*
+                                let #field_name = world.get_resource_ref::<#field_type>().unwrap();
+                            });
+                            field_checks.push(quote! {
+                                #(#cfg)*
+                                {
+                                    unchanged &= !#field_name.is_changed()
+                                        || world.contains_resource::<::bevy_simple_prefs::Persisted<#field_type>>();
+                                }
+                            });
+                            field_change_names.push(quote! {
+                                #(#cfg)*
+                                if world.resource_ref::<#field_type>().is_changed() {
+                                    changed.push(#field_name_str);
+                                }
+                            });
+                            field_assignments.push(quote! {
+                                #(#cfg)*
+                                #field_name: match world.get_resource::<::bevy_simple_prefs::Persisted<#field_type>>() {
+                                    Some(persisted) => persisted.0.clone(),
+                                    None => #field_name.clone(),
+                                }
+                            });
+                            field_swaps.push(quote! {
+                                #(#cfg)*
+                                match world.contains_resource::<::bevy_simple_prefs::Persisted<#field_type>>() {
+                                    true => ::std::mem::swap(
+                                        &mut to_save.#field_name,
+                                        &mut world
+                                            .resource_mut::<::bevy_simple_prefs::Persisted<#field_type>>()
+                                            .bypass_change_detection()
+                                            .0,
+                                    ),
+                                    false => ::std::mem::swap(
+                                        &mut to_save.#field_name,
+                                        world.resource_mut::<#field_type>().bypass_change_detection(),
+                                    ),
+                                }
+                            });
+                            field_history_changes.push(quote! {
+                                #(#cfg)*
+                                if #field_name.is_changed()
+                                    && !world.contains_resource::<::bevy_simple_prefs::Persisted<#field_type>>()
+                                {
+                                    if let Ok(value) = #changed_history_value {
+                                        changes.push((#field_name_str, value));
+                                    }
+                                }
+                            });
+                        }
+                        field_currents.push(quote! {
+                            #(#cfg)*
+                            #field_name: match world.get_resource::<::bevy_simple_prefs::Persisted<#field_type>>() {
+                                Some(persisted) => persisted.0.clone(),
+                                None => world.resource::<#field_type>().clone(),
+                            }
                         });
                         field_inits.push(quote! {
+                            #(#cfg)*
                             app.init_resource::<#field_type>();
                         });
                         field_inserts.push(quote! {
-                            world.insert_resource(val.#field_name);
+                            #(#cfg)*
+                            {
+                                world.remove_resource::<::bevy_simple_prefs::Persisted<#field_type>>();
+                                world.insert_resource(val.#field_name);
+                            }
+                        });
+                        field_loads.push(quote! {
+                            #(#cfg)*
+                            {
+                                world.remove_resource::<::bevy_simple_prefs::Persisted<#field_type>>();
+                                match world.get_resource_mut::<#field_type>() {
+                                    Some(mut current) => *current.bypass_change_detection() = val.#field_name,
+                                    None => world.insert_resource(val.#field_name),
+                                }
+                            }
+                        });
+                        field_stages.push(quote! {
+                            #(#cfg)*
+                            world.insert_resource(::bevy_simple_prefs::Staged(
+                                world.resource::<#field_type>().clone(),
+                            ));
+                        });
+                        field_commits.push(quote! {
+                            #(#cfg)*
+                            if let Some(::bevy_simple_prefs::Staged(value)) =
+                                world.remove_resource::<::bevy_simple_prefs::Staged<#field_type>>()
+                            {
+                                world.insert_resource(value);
+                            }
+                        });
+                        field_unstages.push(quote! {
+                            #(#cfg)*
+                            world.remove_resource::<::bevy_simple_prefs::Staged<#field_type>>();
+                        });
+
+                        field_history_values.push(quote! {
+                            #(#cfg)*
+                            if let Ok(value) = #loaded_history_value {
+                                history_state.values.insert(#field_name_str, value);
+                            }
                         });
                     }
                 }
@@ -56,110 +490,473 @@ pub fn prefs_derive(input: TokenStream) -> TokenStream {
                 }
             }
 
+            let field_names = names_const(&field_names);
+            let field_docs = names_const(&field_docs);
+            let secret_fields = names_const(&secret_fields);
+            let transient_fields = names_const(&transient_fields);
+            let restart_required_fields = names_const(&restart_required_fields);
+            let cfg_fields = names_const(&cfg_fields);
+            let field_keys = rename_all.is_some().then(|| {
+                let field_keys = names_const(&field_keys);
+                quote! { const FIELD_KEYS: &'static [&'static str] = #field_keys; }
+            });
+
             quote! {
+                #(#secret_checks)*
+
+                const _: () = {
+                    trait EachFieldMustHaveADistinctType {}
+                    #(#distinct_checks)*
+                };
+
                 impl Prefs for #name {
+                    const FIELD_NAMES: &'static [&'static str] = #field_names;
+                    const FIELD_DOCS: &'static [&'static str] = #field_docs;
+                    const SECRET_FIELDS: &'static [&'static str] = #secret_fields;
+                    const TRANSIENT_FIELDS: &'static [&'static str] = #transient_fields;
+                    const RESTART_REQUIRED_FIELDS: &'static [&'static str] = #restart_required_fields;
+                    const CFG_FIELDS: &'static [&'static str] = #cfg_fields;
+                    #field_keys
+                    #version
+
+                    fn field_type_names() -> &'static [&'static str] {
+                        static NAMES: ::std::sync::OnceLock<Vec<&'static str>> =
+                            ::std::sync::OnceLock::new();
+                        NAMES.get_or_init(|| {
+                            let mut names = Vec::new();
+                            #(#field_type_names)*
+                            names
+                        })
+                    }
+
                     fn save(world: &mut World) {
+                        #[cfg(debug_assertions)]
+                        if let Some(frames) = world
+                            .resource::<::bevy_simple_prefs::PrefsSettings<#name>>()
+                            .warn_constant_changes
+                        {
+                            #[allow(unused_mut)]
+                            let mut changed = Vec::new();
+                            #(#field_change_names)*
+                            world
+                                .resource_mut::<::bevy_simple_prefs::PrefsChangeStreaks<#name>>()
+                                .record(&changed, frames);
+                        }
+
+                        #(#field_change_validations)*
+
+                        // Changes made while warming up are kept pending, like with `save_if`.
+                        let warming_up = ::bevy_simple_prefs::__warming_up::<#name>(world);
+
                         #(#field_bindings)*
 
-                        if #(#field_checks)&&* {
+                        // With only transient fields, there is never anything to save.
+                        #[allow(unused_mut)]
+                        let mut unchanged = true;
+                        #(#field_checks)*
+                        let save_allowed = !warming_up
+                            && world
+                                .resource::<::bevy_simple_prefs::PrefsSettings<#name>>()
+                                .save_if
+                                .is_none_or(|save_if| save_if(world));
+                        let status = world.get_resource_ref::<::bevy_simple_prefs::PrefsStatus<#name>>().unwrap();
+                        if status.autosave_paused || !save_allowed {
+                            // Remember changes made after loading, so that they're saved on resume,
+                            // after warming up, or once saving is allowed again.
+                            if !unchanged
+                                && status.load_state == ::bevy_simple_prefs::PrefsLoadState::Applied
+                                && !status.is_changed()
+                            {
+                                world
+                                    .resource_mut::<::bevy_simple_prefs::PrefsStatus<#name>>()
+                                    .bypass_change_detection()
+                                    .save_requested = true;
+                                #[cfg(target_arch = "wasm32")]
+                                ::bevy_simple_prefs::__stage_flush::<#name>(world, true);
+                            }
+                            return;
+                        }
+                        if unchanged && !status.save_requested {
                             return;
                         }
 
-                        // Prevent saving from happening on the initial change detection after
-                        // inserting the resources on load.
-                        let status = world.get_resource_ref::<::bevy_simple_prefs::PrefsStatus<#name>>().unwrap();
-                        if status.is_changed() {
+                        // Wait for the delay before retrying a failed save.
+                        if ::bevy_simple_prefs::__retry_pending::<#name>(world) {
+                            return;
+                        }
+
+                        // Prevent saving from happening until the most recent load has been applied,
+                        // which would write values that are about to be replaced, or on the initial
+                        // change detection after inserting the loaded values.
+                        if status.load_state != ::bevy_simple_prefs::PrefsLoadState::Applied
+                            || status.is_changed()
+                        {
                             return;
                         }
 
+                        if world.resource::<::bevy_simple_prefs::PrefsSettings<#name>>().read_only {
+                            ::bevy::log::info_once!(
+                                "{} is read-only, changes won't be saved",
+                                <#name as ::bevy::reflect::TypePath>::type_path()
+                            );
+                            return;
+                        }
+
+                        let span = ::bevy::log::info_span!(
+                            "prefs_save",
+                            prefs = <#name as ::bevy::reflect::TypePath>::type_path()
+                        );
+                        let _entered = span.clone().entered();
+
                         ::bevy::log::debug!("bevy_simple_prefs initiating save");
 
-                        let to_save = #name {
-                            #(#field_assignments,)*
+                        #[cfg(not(target_arch = "wasm32"))]
+                        let (history_enabled, field_changes) = {
+                            let settings = world.resource::<::bevy_simple_prefs::PrefsSettings<#name>>();
+                            let history_enabled = settings.history.is_some();
+                            let field_changes = (history_enabled || settings.journal.is_some()).then(|| {
+                                let mut changes = Vec::new();
+                                #(#field_history_changes)*
+                                changes
+                            });
+                            (history_enabled, field_changes)
+                        };
+
+                        let serialize_in_place = world
+                            .resource::<::bevy_simple_prefs::PrefsSettings<#name>>()
+                            .serialize_in_place;
+                        #[allow(unused_mut)]
+                        let mut to_save = match serialize_in_place {
+                            true => <#name as Default>::default(),
+                            false => #name {
+                                #(#field_assignments,)*
+                            },
                         };
 
-                        let settings = world.resource::<::bevy_simple_prefs::PrefsSettings<#name>>();
-                        let path = settings.path.clone();
-                        let filename = settings.filename.clone();
+                        #[cfg(not(target_arch = "wasm32"))]
+                        let history_entries = field_changes.clone().filter(|_| history_enabled).map(|changes| {
+                            world
+                                .resource_mut::<::bevy_simple_prefs::PrefsHistoryState<#name>>()
+                                .record(changes)
+                        });
+
+                        #[cfg(target_arch = "wasm32")]
+                        ::bevy_simple_prefs::__stage_flush::<#name>(world, false);
+
+                        // Bypass change detection, which would otherwise prevent the next save.
+                        let mut status = world.resource_mut::<::bevy_simple_prefs::PrefsStatus<#name>>();
+                        let status = status.bypass_change_detection();
+                        status.save_count += 1;
+                        status.save_requested = false;
+                        let last_content_hash = status.content_hash;
+
+                        let mut settings = world.resource::<::bevy_simple_prefs::PrefsSettings<#name>>().clone();
+                        let preserved = world
+                            .resource::<::bevy_simple_prefs::PrefsPreservedFields<#name>>()
+                            .preserved
+                            .clone();
+
+                        let serialized = serialize_in_place.then(|| {
+                            // Move the values into `to_save` rather than cloning them, and move
+                            // them back once they've been serialized.
+                            #(#field_swaps)*
+                            let serialized = settings.serialize(&to_save, &preserved);
+                            #(#field_swaps)*
+                            serialized
+                        });
+
+                        let task = ::bevy::tasks::IoTaskPool::get().spawn(async move {
+                            let _entered = span.entered();
 
-                        ::bevy::tasks::IoTaskPool::get()
-                            .spawn(async move {
-                                ::bevy::log::debug!("bevy_simple_prefs saving");
+                            ::bevy::log::debug!("bevy_simple_prefs saving");
 
-                                let Ok(serialized_value) = ::bevy_simple_prefs::serialize(&to_save) else {
-                                    bevy::log::error!("Failed to serialize prefs.");
+                            let result = serialized.unwrap_or_else(|| settings.serialize(&to_save, &preserved));
+
+                            #[cfg(not(target_arch = "wasm32"))]
+                            let path = settings.path.clone();
+
+                            let result = result.and_then(|serialized_value| {
+                                // Skip writing if the stored preferences are already identical.
+                                let content_hash = ::bevy_simple_prefs::content_hash(&serialized_value);
+                                let bytes = serialized_value.len();
+                                if last_content_hash == Some(content_hash) {
+                                    return Ok((content_hash, bytes, false));
+                                }
+
+                                #[cfg(not(target_arch = "wasm32"))]
+                                settings.write_journaled(serialized_value, field_changes.as_deref().unwrap_or_default())?;
+                                #[cfg(target_arch = "wasm32")]
+                                settings.write(serialized_value)?;
+
+                                #[cfg(not(target_arch = "wasm32"))]
+                                if let (Some(history), Some(entries)) = (&settings.history, history_entries) {
+                                    ::bevy_simple_prefs::append_history(&settings.dir(), &settings.filename, history, &entries, settings.file_mode);
+                                }
+
+                                Ok((content_hash, bytes, true))
+                            });
+
+                            // Saving may have fallen back to `fallback_path`.
+                            #[cfg(not(target_arch = "wasm32"))]
+                            let fallback_path = (settings.path != path).then_some(settings.path);
+
+                            let mut command_queue = ::bevy::ecs::world::CommandQueue::default();
+                            command_queue.push(move |world: &mut World| {
+                                // The plugin may have been removed while saving.
+                                if !world.contains_resource::<::bevy_simple_prefs::PrefsStatus<#name>>() {
                                     return;
-                                };
+                                }
+
+                                #[cfg(not(target_arch = "wasm32"))]
+                                if let Some(path) = fallback_path {
+                                    world.resource_mut::<::bevy_simple_prefs::PrefsSettings<#name>>().path = path;
+                                }
+
+                                // Failed saves are retried, and only reported once the last attempt fails.
+                                if ::bevy_simple_prefs::__retry_save::<#name>(world, result.as_ref().err()) {
+                                    return;
+                                }
+
+                                match result {
+                                    Ok((content_hash, bytes, changed)) => {
+                                        world.remove_resource::<::bevy_simple_prefs::PrefsSaveError<#name>>();
+                                        let mut status = world.resource_mut::<::bevy_simple_prefs::PrefsStatus<#name>>();
+                                        let status = status.bypass_change_detection();
+                                        status.content_hash = Some(content_hash);
+                                        status.last_save_bytes = Some(bytes);
+                                        status.__record_save(None);
+                                        world.send_event(::bevy_simple_prefs::PrefsSaved::<#name>::new(changed));
+                                    }
+                                    Err(e) => {
+                                        ::bevy::log::warn!(
+                                            "Failed to save {}: {}",
+                                            <#name as ::bevy::reflect::TypePath>::type_path(),
+                                            e
+                                        );
+                                        world
+                                            .resource_mut::<::bevy_simple_prefs::PrefsStatus<#name>>()
+                                            .bypass_change_detection()
+                                            .__record_save(Some(e.clone()));
+                                        world.insert_resource(::bevy_simple_prefs::PrefsSaveError::<#name>::new(e));
+                                    }
+                                }
+                            });
+
+                            command_queue
+                        });
 
-                                ::bevy_simple_prefs::save_str(&path, &filename, &serialized_value);
-                            }).detach();
+                        world.spawn(::bevy_simple_prefs::SavePrefsTask::<#name>::new(task));
                     }
 
-                    #[cfg(not(target_arch = "wasm32"))]
                     fn load(world: &mut World) {
-                        ::bevy::log::debug!("bevy_simple_prefs initiating load task");
+                        let span = ::bevy::log::info_span!(
+                            "prefs_load",
+                            prefs = <#name as ::bevy::reflect::TypePath>::type_path()
+                        );
+                        let _entered = span.clone().entered();
 
-                        let settings = world.resource::<::bevy_simple_prefs::PrefsSettings<#name>>();
-                        let path = settings.path.clone();
-                        let filename = settings.filename.clone();
+                        let settings = world.resource::<::bevy_simple_prefs::PrefsSettings<#name>>().clone();
+                        let blocking = settings.blocking_load;
 
-                        let entity = world.spawn_empty().id();
+                        if ::bevy_simple_prefs::__queue_load::<#name>(world, blocking) {
+                            ::bevy::log::debug!("bevy_simple_prefs queued load");
+                            return;
+                        }
+
+                        world
+                            .resource_mut::<::bevy_simple_prefs::PrefsStatus<#name>>()
+                            .bypass_change_detection()
+                            .__begin_load();
+
+                        // LocalStorage can only be read synchronously, so read it now and leave
+                        // decompression and deserialization to the task so that they happen in a
+                        // later frame.
+                        #[cfg(target_arch = "wasm32")]
+                        let serialized_value = {
+                            let serialized_value = settings.read_raw();
+                            world.resource_mut::<::bevy_simple_prefs::PrefsStatus<#name>>().load_progress = 0.5;
+                            serialized_value
+                        };
+
+                        let load = move || {
+                            let _entered = span.entered();
 
-                        let task = ::bevy::tasks::IoTaskPool::get().spawn(async move {
                             ::bevy::log::debug!("bevy_simple_prefs loading");
 
-                            let val = (|| {
-                                let Some(serialized_value) = ::bevy_simple_prefs::load_str(&path, &filename) else {
-                                    return #name::default();
+                            #[cfg(not(target_arch = "wasm32"))]
+                            let serialized_value = settings.read();
+                            #[cfg(target_arch = "wasm32")]
+                            let serialized_value = settings.finish_read(serialized_value);
+
+                            let (content_hash, metadata) = match &serialized_value {
+                                Ok(Some(serialized_value)) => (
+                                    Some(::bevy_simple_prefs::content_hash(serialized_value)),
+                                    Some(::bevy_simple_prefs::read_metadata(serialized_value)),
+                                ),
+                                _ => (None, None),
+                            };
+
+                            let ((val, preserved), error) = (|| {
+                                let serialized_value = match serialized_value {
+                                    Ok(Some(serialized_value)) => Some(serialized_value),
+                                    Ok(None) => settings.first_run_default(),
+                                    Err(e) => {
+                                        ::bevy::log::warn!("Failed to load prefs: {}", e);
+                                        return (Default::default(), Some(e));
+                                    }
                                 };
 
-                                match ::bevy_simple_prefs::deserialize(&serialized_value) {
-                                    Ok(v) => v,
+                                let result = match serialized_value {
+                                    Some(serialized_value) => settings.deserialize(&serialized_value),
+                                    None => settings.base().map(|base| (base, Default::default())),
+                                };
+                                #[cfg(not(target_arch = "wasm32"))]
+                                let result = result.and_then(|(val, mut preserved)| {
+                                    let val = settings.apply_overrides(val, &mut preserved)?;
+                                    Ok((val, preserved))
+                                });
+
+                                match result {
+                                    Ok(v) => (v, None),
                                     Err(e) => {
                                         ::bevy::log::error!("Failed to deserialize prefs: {}", e);
-                                        return #name::default();
+                                        (Default::default(), Some(e))
                                     }
                                 }
                             })();
 
+                            // The preferences file is authoritative, but if it couldn't be loaded,
+                            // fall back to the first mirror that can be.
+                            #[cfg(not(target_arch = "wasm32"))]
+                            let ((val, preserved), error) = match error
+                                .is_some()
+                                .then(|| settings.read_mirrors())
+                                .flatten()
+                            {
+                                Some(mirrored) => (mirrored, None),
+                                None => ((val, preserved), error),
+                            };
+
                             let mut command_queue = ::bevy::ecs::world::CommandQueue::default();
                             command_queue.push(move |world: &mut World| {
-                                #(#field_inserts;)*;
-                                world.resource_mut::<::bevy_simple_prefs::PrefsStatus<#name>>().loaded = true;
-                                world.despawn(entity);
+                                // The plugin may have been removed while loading.
+                                if !world.contains_resource::<::bevy_simple_prefs::PrefsStatus<#name>>() {
+                                    return;
+                                }
+
+                                let mut val = val;
+                                #[allow(unused_mut)]
+                                let mut write_back = false;
+                                #(#field_validations)*
+                                write_back |= world
+                                    .resource::<::bevy_simple_prefs::PrefsSettings<#name>>()
+                                    .post_load
+                                    .is_some_and(|post_load| post_load(&mut val));
+
+                                if world.resource::<::bevy_simple_prefs::PrefsSettings<#name>>().log_loaded {
+                                    let mut summary = format!(
+                                        "Loaded {}:",
+                                        <#name as ::bevy::reflect::TypePath>::type_path()
+                                    );
+                                    #(#field_load_logs)*
+                                    ::bevy::log::debug!("{}", summary);
+                                }
+
+                                #[cfg(not(target_arch = "wasm32"))]
+                                if world.resource::<::bevy_simple_prefs::PrefsSettings<#name>>().history.is_some() {
+                                    let mut history_state = world.resource_mut::<::bevy_simple_prefs::PrefsHistoryState<#name>>();
+                                    #(#field_history_values)*
+                                }
+
+                                // Loaded values aren't changes made by the app, so they don't trigger
+                                // change detection.
+                                #(#field_loads)*
+                                world.insert_resource(::bevy_simple_prefs::PrefsPreservedFields::<#name>::new(preserved));
+                                match &error {
+                                    Some(e) => world.insert_resource(::bevy_simple_prefs::PrefsLoadError::<#name>::new(e.clone())),
+                                    None => {
+                                        world.remove_resource::<::bevy_simple_prefs::PrefsLoadError<#name>>();
+                                    }
+                                }
+                                let mut status = world.resource_mut::<::bevy_simple_prefs::PrefsStatus<#name>>();
+                                status.__finish_load();
+                                status.load_count += 1;
+                                status.content_hash = content_hash;
+                                // The version header is only written for versions other than `0`.
+                                status.loaded_version = metadata
+                                    .as_ref()
+                                    .map(|metadata| metadata.version)
+                                    .filter(|version| *version > 0);
+                                status.metadata = metadata;
+                                status.__record_load(error);
+                                if write_back {
+                                    status.save_requested = true;
+                                }
                             });
 
                             command_queue
-                        });
+                        };
 
-                        world.entity_mut(entity).insert(::bevy_simple_prefs::LoadPrefsTask(task));
+                        if blocking {
+                            load().apply(world);
+                            return;
+                        }
+
+                        ::bevy::log::debug!("bevy_simple_prefs initiating load task");
+
+                        let task = ::bevy::tasks::IoTaskPool::get().spawn(async move { load() });
+
+                        world.spawn(::bevy_simple_prefs::LoadPrefsTask::<#name>::new(task));
                     }
 
-                    // There's no task pool and no multi-threading on wasm, so just load everything,
-                    // toss it into the world, and update `PrefsStatus`.
-                    #[cfg(target_arch = "wasm32")]
-                    fn load(world: &mut World) {
-                        ::bevy::log::debug!("bevy_simple_prefs loading");
+                    fn reset(world: &mut World) {
+                        Self::insert(#name::default(), world);
+                    }
 
-                        let settings = world.resource::<::bevy_simple_prefs::PrefsSettings<#name>>();
+                    fn begin_edit(world: &mut World) {
+                        #(#field_stages)*
+                    }
 
-                        let val = (|| {
-                            let Some(serialized_value) = ::bevy_simple_prefs::load_str(&settings.path, &settings.filename) else {
-                                return #name::default();
-                            };
+                    fn commit_edit(world: &mut World) {
+                        #(#field_commits)*
+                    }
 
-                            match ::bevy_simple_prefs::deserialize(&serialized_value) {
-                                Ok(v) => v,
-                                Err(e) => {
-                                    ::bevy::log::error!("bevy_simple_prefs failed to deserialize prefs: {}", e);
-                                    return #name::default();
-                                }
-                            }
-                        })();
+                    fn cancel_edit(world: &mut World) {
+                        #(#field_unstages)*
+                    }
+
+                    fn current(world: &World) -> Self {
+                        #name {
+                            #(#field_currents,)*
+                        }
+                    }
+
+                    fn insert(self, world: &mut World) {
+                        let val = self;
+
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if world.resource::<::bevy_simple_prefs::PrefsSettings<#name>>().history.is_some() {
+                            let mut history_state = world.resource_mut::<::bevy_simple_prefs::PrefsHistoryState<#name>>();
+                            history_state.values.clear();
+                            #(#field_history_values)*
+                        }
 
                         #(#field_inserts;)*;
+                        // Prevent the inserted values from being saved.
+                        world.resource_mut::<::bevy_simple_prefs::PrefsStatus<#name>>().set_changed();
+                    }
+
+                    fn insert_loaded(self, world: &mut World) {
+                        let val = self;
+
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if world.resource::<::bevy_simple_prefs::PrefsSettings<#name>>().history.is_some() {
+                            let mut history_state = world.resource_mut::<::bevy_simple_prefs::PrefsHistoryState<#name>>();
+                            history_state.values.clear();
+                            #(#field_history_values)*
+                        }
 
-                        world.resource_mut::<::bevy_simple_prefs::PrefsStatus<#name>>().loaded = true;
+                        #(#field_loads)*
                     }
 
                     fn init(app: &mut App) {